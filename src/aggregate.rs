@@ -0,0 +1,216 @@
+//! Summarizing a numeric column per category, to feed bar charts and box plots.
+use crate::{
+    box_plot::box_plot,
+    histogram::histogram,
+    stats::{quantile, sorted_present},
+    Categorical, Chart, Column, Error, Table,
+};
+use std::{collections::HashMap, fmt, hash::Hash, sync::Arc};
+
+/// A way of summarizing a group of values as one number.
+///
+/// Missing (NaN) values are skipped. Aggregates of an empty group are NaN (missing), except
+/// `Count` and `Sum`, which are 0.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Aggregate {
+    Count,
+    Sum,
+    Mean,
+    Median,
+    Min,
+    Max,
+    /// The `p`th quantile, for `p` between 0 and 1, linearly interpolating between values.
+    Quantile(f64),
+}
+
+impl Aggregate {
+    /// Summarize some sorted, non-missing values.
+    fn apply(self, sorted: &[f64]) -> f64 {
+        let n = sorted.len() as f64;
+        match self {
+            Aggregate::Count => n,
+            Aggregate::Sum => sorted.iter().sum(),
+            Aggregate::Mean => sorted.iter().sum::<f64>() / n,
+            Aggregate::Median => quantile(sorted, 0.5),
+            Aggregate::Min => sorted.first().copied().unwrap_or(f64::NAN),
+            Aggregate::Max => sorted.last().copied().unwrap_or(f64::NAN),
+            Aggregate::Quantile(p) => quantile(sorted, p),
+        }
+    }
+}
+
+/// Values grouped by category.
+///
+/// The results of `aggregate` pair up with `histogram::histogram`, and `box_plot` draws the
+/// distribution of each group.
+///
+/// ```ignore
+/// let table = Table::from_csv_path("monica.csv")?;
+/// let (edlevel, systm) = GroupBy::from_table(&table, "edlevel", "systm")?.mean();
+/// let chart = histogram(edlevel, systm);
+/// ```
+#[derive(Debug, Clone)]
+pub struct GroupBy<L> {
+    categories: Vec<L>,
+    /// The sorted, non-missing values of each category.
+    groups: Vec<Vec<f64>>,
+}
+
+impl<L: Clone + Eq + Hash> GroupBy<L> {
+    /// Group `values` by the key at the same index, with categories in the order they first
+    /// appear.
+    ///
+    /// A category whose values are all missing still has an (empty) group.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are a different number of keys and values.
+    pub fn new(keys: impl IntoIterator<Item = L>, values: &[f64]) -> Self {
+        match Self::try_new(keys, values) {
+            Ok(groups) => groups,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Like `new`, but returns an error if there are a different number of keys and values.
+    pub fn try_new(keys: impl IntoIterator<Item = L>, values: &[f64]) -> Result<Self, Error> {
+        let keys: Vec<L> = keys.into_iter().collect();
+        if keys.len() != values.len() {
+            return Err(Error::MismatchedLengths {
+                expected: values.len(),
+                found: keys.len(),
+            });
+        }
+        Ok(Self::from_pairs(
+            keys.into_iter().zip(values.iter().copied()),
+        ))
+    }
+
+    fn from_pairs(pairs: impl IntoIterator<Item = (L, f64)>) -> Self {
+        let mut categories: Vec<L> = vec![];
+        let mut groups: Vec<Vec<f64>> = vec![];
+        let mut index: HashMap<L, usize> = HashMap::new();
+        for (key, value) in pairs {
+            let idx = *index.entry(key).or_insert_with_key(|key| {
+                categories.push(key.clone());
+                groups.push(vec![]);
+                categories.len() - 1
+            });
+            groups[idx].push(value);
+        }
+        let groups = groups.iter().map(|g| sorted_present(g)).collect();
+        Self { categories, groups }
+    }
+
+    /// The categories, in the same order as the groups.
+    pub fn categories(&self) -> Categorical<L> {
+        Categorical::new(self.categories.clone())
+    }
+
+    /// The sorted, non-missing values of each category.
+    pub fn groups(&self) -> &[Vec<f64>] {
+        &self.groups
+    }
+
+    /// Summarize each group, returning the categories and their summaries in the same order.
+    pub fn aggregate(&self, aggregate: Aggregate) -> (Categorical<L>, Arc<[f64]>) {
+        let values = self.groups.iter().map(|g| aggregate.apply(g)).collect();
+        (self.categories(), values)
+    }
+
+    /// The number of non-missing values in each group.
+    pub fn count(&self) -> (Categorical<L>, Arc<[f64]>) {
+        self.aggregate(Aggregate::Count)
+    }
+
+    pub fn sum(&self) -> (Categorical<L>, Arc<[f64]>) {
+        self.aggregate(Aggregate::Sum)
+    }
+
+    pub fn mean(&self) -> (Categorical<L>, Arc<[f64]>) {
+        self.aggregate(Aggregate::Mean)
+    }
+
+    pub fn median(&self) -> (Categorical<L>, Arc<[f64]>) {
+        self.aggregate(Aggregate::Median)
+    }
+
+    pub fn quantile(&self, p: f64) -> (Categorical<L>, Arc<[f64]>) {
+        self.aggregate(Aggregate::Quantile(p))
+    }
+}
+
+impl<L> GroupBy<L>
+where
+    L: Clone + Eq + Hash + fmt::Debug + fmt::Display + 'static,
+{
+    /// A bar chart of one summary of each group.
+    pub fn bar_chart(&self, aggregate: Aggregate) -> Chart {
+        let (categories, values) = self.aggregate(aggregate);
+        histogram(categories, values)
+    }
+
+    /// A box plot of each group.
+    pub fn box_plot(&self) -> Chart {
+        box_plot(self.categories(), &self.groups)
+    }
+}
+
+impl GroupBy<Arc<str>> {
+    /// Group the numbers in column `value` of `table` by the labels in column `key`.
+    ///
+    /// Rows with a missing key are skipped. If `key` is a numeric or timestamp column, the
+    /// categories are in ascending order, otherwise in the order they first appear.
+    pub fn from_table(table: &Table, key: &str, value: &str) -> Result<Self, Error> {
+        let values = table.numbers(value)?;
+        let keys = table.try_column(key)?;
+        let mut order: Vec<usize> = (0..keys.len()).collect();
+        if let Column::Number(numbers) | Column::Timestamp(numbers) = keys {
+            order.sort_by(|&a, &b| numbers[a].total_cmp(&numbers[b]));
+        }
+        Ok(Self::from_pairs(order.into_iter().filter_map(|idx| {
+            keys.label(idx).map(|label| (label, values[idx]))
+        })))
+    }
+}
+
+#[test]
+fn test_group_by() {
+    let table = Table::new()
+        .with_column(
+            "edlevel",
+            Column::infer(&["primary", "tertiary", "primary", "secondary", "NA"]),
+        )
+        .with_column("systm", vec![120., 140., 130., f64::NAN, 150.]);
+    let groups = GroupBy::from_table(&table, "edlevel", "systm").unwrap();
+    let (categories, means) = groups.mean();
+    assert_eq!(
+        categories.categories(),
+        &["primary".into(), "tertiary".into(), "secondary".into()] as &[Arc<str>]
+    );
+    assert_eq!(means[..2], [125., 140.]);
+    assert!(means[2].is_nan());
+    assert_eq!(&groups.count().1[..], &[2., 1., 0.]);
+    assert_eq!(&groups.aggregate(Aggregate::Max).1[..2], &[130., 140.]);
+    assert!(matches!(
+        GroupBy::from_table(&table, "edlevel", "nope"),
+        Err(Error::UnknownColumn(_))
+    ));
+
+    let by_number = GroupBy::new([3, 1, 3], &[1., 2., 5.]);
+    assert_eq!(by_number.categories().categories(), &[3, 1]);
+    assert_eq!(&by_number.median().1[..], &[3., 2.]);
+    assert!(matches!(
+        GroupBy::try_new([1, 2], &[1.]),
+        Err(Error::MismatchedLengths {
+            expected: 1,
+            found: 2
+        })
+    ));
+
+    use crate::chart::test_render;
+    test_render(groups.bar_chart(Aggregate::Mean));
+    test_render(groups.box_plot());
+}
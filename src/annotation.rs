@@ -0,0 +1,385 @@
+//! Reference lines, shaded spans, arrows and text drawn with the traces of a chart.
+use crate::{theme, Interval};
+use piet_common::{
+    kurbo::{BezPath, Line, Point, Rect, Size, Vec2},
+    Color, Error as PietError, Piet, PietTextLayout, RenderContext, StrokeStyle, Text,
+    TextAttribute, TextLayout, TextLayoutBuilder,
+};
+
+/// A position in the chart area.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Position {
+    /// A position in data coordinates, using the x and y ranges of the `Annotations`.
+    Data(f64, f64),
+    /// A position as a fraction of the chart area, from `(0, 0)` at the top left to `(1, 1)` at
+    /// the bottom right.
+    Area(f64, f64),
+}
+
+/// Whether an annotation is drawn before or after the traces.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Layer {
+    BelowTraces,
+    AboveTraces,
+}
+
+#[derive(Debug, Clone)]
+enum Shape {
+    HLine(f64),
+    VLine(f64),
+    XSpan(f64, f64),
+    YSpan(f64, f64),
+    Arrow(Position, Position),
+    Text(Position),
+}
+
+/// Something drawn on a chart to mark a value, range or event.
+///
+/// Lines and spans may have a text label, and arrows may have text at their tail.
+#[derive(Clone)]
+pub struct Annotation {
+    shape: Shape,
+    /// The color of lines and text, and (with `fill_opacity`) spans.
+    pub color: Color,
+    /// The width of lines and arrows.
+    pub stroke_width: f64,
+    /// The style of lines and arrows, including any dash pattern.
+    pub stroke_style: StrokeStyle,
+    /// The opacity of shaded spans.
+    pub fill_opacity: f64,
+    /// Whether to draw before or after the traces.
+    pub layer: Layer,
+    /// The font size of any text.
+    pub font_size: f64,
+    text: Option<String>,
+
+    // Retained
+    layout: Option<PietTextLayout>,
+}
+
+impl Annotation {
+    fn new(shape: Shape, layer: Layer) -> Self {
+        Self {
+            shape,
+            color: theme::ANNOTATION_COLOR,
+            stroke_width: 1.5,
+            stroke_style: StrokeStyle::new(),
+            fill_opacity: 0.15,
+            layer,
+            font_size: theme::DATA_LABEL_FONT_SIZE,
+            text: None,
+            layout: None,
+        }
+    }
+
+    /// A horizontal line across the chart at data value `y` (e.g. a threshold).
+    pub fn hline(y: f64) -> Self {
+        Self::new(Shape::HLine(y), Layer::AboveTraces)
+    }
+
+    /// A vertical line across the chart at data value `x` (e.g. an event).
+    pub fn vline(x: f64) -> Self {
+        Self::new(Shape::VLine(x), Layer::AboveTraces)
+    }
+
+    /// A shaded band across the chart between data values `x0` and `x1`.
+    pub fn x_span(x0: f64, x1: f64) -> Self {
+        Self::new(Shape::XSpan(x0, x1), Layer::BelowTraces)
+    }
+
+    /// A shaded band across the chart between data values `y0` and `y1`.
+    pub fn y_span(y0: f64, y1: f64) -> Self {
+        Self::new(Shape::YSpan(y0, y1), Layer::BelowTraces)
+    }
+
+    /// An arrow pointing from `from` to `to`. Text added with `with_text` is drawn at `from`.
+    pub fn arrow(from: Position, to: Position) -> Self {
+        Self::new(Shape::Arrow(from, to), Layer::AboveTraces)
+    }
+
+    /// Some text, centered on `at`.
+    pub fn text(at: Position, text: impl Into<String>) -> Self {
+        Self::new(Shape::Text(at), Layer::AboveTraces).with_text(text)
+    }
+
+    /// Label the annotation. Lines are labelled at their end, and spans in their top left corner.
+    pub fn with_text(mut self, text: impl Into<String>) -> Self {
+        self.text = Some(text.into());
+        self
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_stroke_width(mut self, stroke_width: f64) -> Self {
+        self.stroke_width = stroke_width;
+        self
+    }
+
+    /// Draw dashed lines, alternating between drawn and skipped lengths from `pattern`.
+    pub fn with_dash(mut self, pattern: &'static [f64]) -> Self {
+        self.stroke_style = self.stroke_style.dash_pattern(pattern);
+        self
+    }
+
+    pub fn with_fill_opacity(mut self, fill_opacity: f64) -> Self {
+        self.fill_opacity = fill_opacity;
+        self
+    }
+
+    pub fn with_layer(mut self, layer: Layer) -> Self {
+        self.layer = layer;
+        self
+    }
+}
+
+/// A set of annotations, positioned using the same x and y ranges as the traces of a chart.
+///
+/// Annotations are drawn in the order they were added, within their layer, and are clipped to
+/// the chart area.
+pub struct Annotations {
+    /// The range that x values should be shown over
+    x_range: Interval,
+    /// The range that y values should be shown over
+    y_range: Interval,
+    items: Vec<Annotation>,
+
+    // Retained
+    /// The size of the chart area.
+    pub size: Option<Size>,
+}
+
+impl Annotations {
+    pub fn new(x_range: Interval, y_range: Interval) -> Self {
+        Self {
+            x_range,
+            y_range,
+            items: vec![],
+            size: None,
+        }
+    }
+
+    pub fn with(mut self, annotation: Annotation) -> Self {
+        self.push(annotation);
+        self
+    }
+
+    /// Add an annotation.
+    ///
+    /// Call `Chart::layout` after changing the annotations.
+    pub fn push(&mut self, annotation: Annotation) {
+        self.items.push(annotation);
+        self.size = None;
+    }
+
+    /// Remove all the annotations.
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.size = None;
+    }
+
+    pub fn set_x_interval(&mut self, new_range: Interval) {
+        self.x_range = new_range;
+        self.size = None;
+    }
+
+    pub fn set_y_interval(&mut self, new_range: Interval) {
+        self.y_range = new_range;
+        self.size = None;
+    }
+
+    fn x_pos(&self, x: f64, size: Size) -> f64 {
+        self.x_range.t(x) * size.width
+    }
+
+    fn y_pos(&self, y: f64, size: Size) -> f64 {
+        (1. - self.y_range.t(y)) * size.height
+    }
+
+    fn to_point(&self, position: Position, size: Size) -> Point {
+        match position {
+            Position::Data(x, y) => Point::new(self.x_pos(x, size), self.y_pos(y, size)),
+            Position::Area(x, y) => Point::new(x * size.width, y * size.height),
+        }
+    }
+
+    pub(crate) fn layout(&mut self, size: Size, rc: &mut Piet) -> Result<(), PietError> {
+        self.size = Some(size);
+        for item in &mut self.items {
+            item.layout = match &item.text {
+                Some(text) => Some(
+                    rc.text()
+                        .new_text_layout(text.clone())
+                        .default_attribute(TextAttribute::FontSize(item.font_size))
+                        .default_attribute(TextAttribute::TextColor(item.color.clone()))
+                        .build()?,
+                ),
+                None => None,
+            };
+        }
+        Ok(())
+    }
+
+    /// Draw the annotations in `layer`, in chart area coordinates.
+    pub(crate) fn draw(&self, layer: Layer, rc: &mut Piet) {
+        let size = self.size.unwrap();
+        let area = size.to_rect();
+        for item in self.in_layer(layer) {
+            self.draw_item(item, area, rc);
+        }
+    }
+
+    /// The annotations in `layer`, in the order they were added.
+    fn in_layer(&self, layer: Layer) -> impl Iterator<Item = &Annotation> {
+        self.items.iter().filter(move |item| item.layer == layer)
+    }
+
+    fn draw_item(&self, item: &Annotation, area: Rect, rc: &mut Piet) {
+        let size = area.size();
+        let stroke = |line: Line, rc: &mut Piet| {
+            rc.stroke_styled(line, &item.color, item.stroke_width, &item.stroke_style);
+        };
+        let text_size = item
+            .layout
+            .as_ref()
+            .map(|layout| layout.size())
+            .unwrap_or(Size::ZERO);
+        // Where to put the top left of the text.
+        let text_pos = match item.shape {
+            Shape::HLine(y) => {
+                let y = self.y_pos(y, size);
+                if !(area.y0..=area.y1).contains(&y) {
+                    return;
+                }
+                stroke(Line::new((area.x0, y), (area.x1, y)), rc);
+                Point::new(
+                    area.x1 - text_size.width - theme::MARGIN * 0.5,
+                    y - text_size.height - 2.,
+                )
+            }
+            Shape::VLine(x) => {
+                let x = self.x_pos(x, size);
+                if !(area.x0..=area.x1).contains(&x) {
+                    return;
+                }
+                stroke(Line::new((x, area.y0), (x, area.y1)), rc);
+                Point::new(x + 4., area.y0 + theme::MARGIN * 0.5)
+            }
+            Shape::XSpan(x0, x1) => {
+                let rect = Rect::new(self.x_pos(x0, size), area.y0, self.x_pos(x1, size), area.y1)
+                    .abs()
+                    .intersect(area);
+                if rect.width() <= 0. {
+                    return;
+                }
+                rc.fill(rect, &item.color.clone().with_alpha(item.fill_opacity));
+                rect.origin() + Vec2::new(4., theme::MARGIN * 0.5)
+            }
+            Shape::YSpan(y0, y1) => {
+                let rect = Rect::new(area.x0, self.y_pos(y0, size), area.x1, self.y_pos(y1, size))
+                    .abs()
+                    .intersect(area);
+                if rect.height() <= 0. {
+                    return;
+                }
+                rc.fill(rect, &item.color.clone().with_alpha(item.fill_opacity));
+                rect.origin() + Vec2::new(4., 2.)
+            }
+            Shape::Arrow(from, to) => {
+                let (from, to) = (self.to_point(from, size), self.to_point(to, size));
+                stroke(Line::new(from, to), rc);
+                draw_arrow_head(from, to, item, rc);
+                from - text_size.to_vec2() * 0.5
+            }
+            Shape::Text(at) => self.to_point(at, size) - text_size.to_vec2() * 0.5,
+        };
+        if let Some(layout) = &item.layout {
+            rc.draw_text(layout, text_pos);
+        }
+    }
+}
+
+/// Draw a filled triangle at `to`, pointing away from `from`.
+fn draw_arrow_head(from: Point, to: Point, item: &Annotation, rc: &mut Piet) {
+    if let Some(path) = arrow_head(from, to, item.stroke_width) {
+        rc.fill(path, &item.color);
+    }
+}
+
+/// A triangle with its tip at `to`, pointing away from `from`, sized for a line `stroke_width`
+/// wide.
+///
+/// Returns `None` if `from` and `to` are the same point, so there is no direction to point in.
+fn arrow_head(from: Point, to: Point, stroke_width: f64) -> Option<BezPath> {
+    let direction = to - from;
+    let len = direction.hypot();
+    if len <= 0. {
+        return None;
+    }
+    let direction = direction / len;
+    let normal = Vec2::new(-direction.y, direction.x);
+    let head = 3. * stroke_width + 4.;
+    let base = to - direction * head;
+    let mut path = BezPath::new();
+    path.move_to(to);
+    path.line_to(base + normal * head * 0.5);
+    path.line_to(base - normal * head * 0.5);
+    path.close_path();
+    Some(path)
+}
+
+#[test]
+fn test_annotation_positions() {
+    let annotations = Annotations::new(Interval::new(0., 10.), Interval::new(-1., 1.));
+    let size = Size::new(200., 100.);
+    assert_eq!(
+        annotations.to_point(Position::Data(5., 1.), size),
+        Point::new(100., 0.)
+    );
+    assert_eq!(
+        annotations.to_point(Position::Data(0., -1.), size),
+        Point::new(0., 100.)
+    );
+    assert_eq!(
+        annotations.to_point(Position::Area(0.25, 0.5), size),
+        Point::new(50., 50.)
+    );
+}
+
+#[test]
+fn test_annotation_layers() {
+    use crate::chart::with_test_context;
+    let mut annotations = Annotations::new(Interval::new(0., 10.), Interval::new(0., 10.))
+        .with(Annotation::hline(5.).with_text("threshold"))
+        .with(Annotation::x_span(2., 4.))
+        .with(Annotation::vline(3.).with_layer(Layer::BelowTraces))
+        .with(Annotation::text(Position::Area(0.5, 0.5), "note"));
+    let below: Vec<_> = annotations.in_layer(Layer::BelowTraces).collect();
+    assert!(matches!(below[0].shape, Shape::XSpan(..)));
+    assert!(matches!(below[1].shape, Shape::VLine(..)));
+    assert_eq!(annotations.in_layer(Layer::AboveTraces).count(), 2);
+
+    with_test_context(|rc| {
+        annotations.layout(Size::new(200., 100.), rc)?;
+        annotations.draw(Layer::BelowTraces, rc);
+        annotations.draw(Layer::AboveTraces, rc);
+        Ok(())
+    });
+    assert!(annotations.items[0].layout.is_some());
+    assert!(annotations.items[1].layout.is_none());
+    annotations.clear();
+    assert_eq!(annotations.size, None);
+}
+
+#[test]
+fn test_arrow_head() {
+    use piet_common::kurbo::Shape as _;
+    assert!(arrow_head(Point::new(1., 1.), Point::new(1., 1.), 1.).is_none());
+    // Pointing right, with the tip at `to` and a base `3 * stroke_width + 4` wide behind it.
+    let head = arrow_head(Point::ZERO, Point::new(20., 0.), 2.).unwrap();
+    let bounds = head.bounding_box();
+    assert_eq!(bounds, Rect::new(10., -5., 20., 5.));
+    assert!(head.contains(Point::new(15., 0.)));
+}
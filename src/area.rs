@@ -0,0 +1,415 @@
+use crate::{
+    missing::{is_missing, present_runs},
+    prelude::*,
+    theme, Chart, Error, GridStyle, Interval, Trace,
+};
+use piet_common::{
+    kurbo::{BezPath, Point, Size},
+    Color, Error as PietError, Piet, RenderContext,
+};
+use std::{any::Any, sync::Arc};
+
+/// Create a chart with the area between a series of `(x, y)` values and 0 filled.
+pub fn area_chart(values: impl Into<Arc<[(f64, f64)]>>) -> Chart {
+    let values = values.into();
+    let (x_range, y_range): (Interval, Interval) = values.iter().copied().unzip();
+    let (x_range, y_range) = (x_range.to_rounded(), y_range.include_zero().to_rounded());
+    Chart::new()
+        .with_left_axis(y_range.ticker().reverse())
+        .with_left_grid(GridStyle::default())
+        .with_bottom_axis(x_range.ticker())
+        .with_trace(AreaTrace::new(
+            values,
+            Baseline::Value(0.),
+            x_range,
+            y_range,
+        ))
+}
+
+/// Create a chart with the given series stacked on top of each other.
+///
+/// All the series share the x values `xs`, and the y interval is calculated from the stack
+/// totals. A missing (NaN) value leaves a gap in its layer and every layer above it. Each series
+/// gets the next color from the theme.
+///
+/// # Panics
+///
+/// Panics if any series has a different length to `xs`.
+pub fn stacked_area_chart<S>(
+    xs: impl Into<Arc<[f64]>>,
+    series: impl IntoIterator<Item = S>,
+    offset: StackOffset,
+) -> Chart
+where
+    S: Into<Arc<[f64]>>,
+{
+    match try_stacked_area_chart(xs, series, offset) {
+        Ok(chart) => chart,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Like `stacked_area_chart`, but returns an error if any series has a different length to
+/// `xs`.
+pub fn try_stacked_area_chart<S>(
+    xs: impl Into<Arc<[f64]>>,
+    series: impl IntoIterator<Item = S>,
+    offset: StackOffset,
+) -> Result<Chart, Error>
+where
+    S: Into<Arc<[f64]>>,
+{
+    let xs = xs.into();
+    let series: Vec<Arc<[f64]>> = series.into_iter().map(Into::into).collect();
+    if let Some(values) = series.iter().find(|values| values.len() != xs.len()) {
+        return Err(Error::MismatchedLengths {
+            expected: xs.len(),
+            found: values.len(),
+        });
+    }
+    let stacked = stack(&series, offset);
+
+    let x_range = Interval::from_iter(xs.iter().copied()).to_rounded();
+    let mut y_range: Interval = stacked
+        .iter()
+        .flat_map(|layer| layer.iter().flat_map(|&(lower, upper)| [lower, upper]))
+        .collect();
+    if offset == StackOffset::Zero {
+        y_range = y_range.include_zero();
+    }
+    let y_range = y_range.to_rounded();
+
+    let mut chart = Chart::new()
+        .with_left_axis(y_range.ticker().reverse())
+        .with_left_grid(GridStyle::default())
+        .with_bottom_axis(x_range.ticker());
+    for (idx, layer) in stacked.into_iter().enumerate() {
+        let (lower, upper): (Vec<_>, Vec<_>) = layer
+            .into_iter()
+            .zip(xs.iter().copied())
+            .map(|((lower, upper), x)| ((x, lower), (x, upper)))
+            .unzip();
+        chart = chart.with_trace(
+            AreaTrace::new(upper, Baseline::Series(lower.into()), x_range, y_range)
+                .with_color(theme::series_color(idx)),
+        );
+    }
+    Ok(chart)
+}
+
+/// How to position the bottom of a stack of series.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum StackOffset {
+    /// The stack starts at 0 (a stacked area chart).
+    Zero,
+    /// The bottom of the stack moves to minimise the weighted change in slope of all the layers
+    /// (a streamgraph). This is the same method as d3's `stackOffsetWiggle`.
+    Wiggle,
+}
+
+/// Stack `series` on top of each other, returning the `(lower, upper)` edge of each layer at each
+/// index.
+///
+/// A missing (NaN) value makes the edges of its layer, and of every layer above it, NaN at that
+/// index. (A streamgraph baseline counts missing values as 0.) The result has the same length as
+/// the shortest series.
+pub fn stack(series: &[Arc<[f64]>], offset: StackOffset) -> Vec<Vec<(f64, f64)>> {
+    let len = series.iter().map(|values| values.len()).min().unwrap_or(0);
+    let value = |i: usize, j: usize| {
+        let v = series[i][j];
+        if is_missing(v) {
+            0.
+        } else {
+            v
+        }
+    };
+
+    // The bottom of the stack at each index.
+    let mut baseline = vec![0.; len];
+    if offset == StackOffset::Wiggle && len > 0 {
+        let mut y = 0.;
+        for j in 1..len {
+            let (mut total, mut weighted) = (0., 0.);
+            for i in 0..series.len() {
+                let vij = value(i, j);
+                // the change in the middle of layer i
+                let mut change = (vij - value(i, j - 1)) * 0.5;
+                for k in 0..i {
+                    change += value(k, j) - value(k, j - 1);
+                }
+                total += vij;
+                weighted += change * vij;
+            }
+            baseline[j - 1] = y;
+            if total != 0. {
+                y -= weighted / total;
+            }
+        }
+        baseline[len - 1] = y;
+    }
+
+    let mut out = Vec::with_capacity(series.len());
+    let mut lower = baseline;
+    for values in series {
+        let layer: Vec<(f64, f64)> = lower
+            .iter()
+            .zip(values.iter())
+            .map(|(&lower, &v)| {
+                let v = if is_missing(v) { f64::NAN } else { v };
+                (lower, lower + v)
+            })
+            .collect();
+        lower = layer.iter().map(|&(_, upper)| upper).collect();
+        out.push(layer);
+    }
+    out
+}
+
+/// The bottom edge of an area.
+#[derive(Debug, Clone)]
+pub enum Baseline {
+    /// A horizontal line at a constant y value.
+    Value(f64),
+    /// Another series. Points are paired with the upper series by index.
+    Series(Arc<[(f64, f64)]>),
+}
+
+/// The area between a series of points and a baseline.
+///
+/// Points where either edge has a NaN coordinate are missing: the area is broken either side of
+/// them.
+pub struct AreaTrace {
+    /// The points of the top edge of the area, sorted by x.
+    ///
+    /// Not public because we have retained state that depends on them.
+    values: Arc<[(f64, f64)]>,
+    /// The bottom edge of the area.
+    baseline: Baseline,
+    /// The range that x values should be shown over
+    x_range: Interval,
+    /// The range that y values should be shown over
+    y_range: Interval,
+    /// The color of the area and its top edge.
+    pub color: Color,
+    /// The opacity of the fill.
+    pub fill_opacity: f64,
+    /// The width of the line along the top edge. Set to 0 to not draw it.
+    pub stroke_width: f64,
+
+    // Retained
+    /// The size of the chart area.
+    pub size: Option<Size>,
+}
+
+impl AreaTrace {
+    pub fn new(
+        values: impl Into<Arc<[(f64, f64)]>>,
+        baseline: Baseline,
+        x_range: Interval,
+        y_range: Interval,
+    ) -> Self {
+        Self {
+            values: values.into(),
+            baseline,
+            x_range,
+            y_range,
+            color: theme::series_color(0),
+            fill_opacity: theme::AREA_FILL_OPACITY,
+            stroke_width: 1.5,
+            size: None,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_fill_opacity(mut self, fill_opacity: f64) -> Self {
+        self.fill_opacity = fill_opacity;
+        self
+    }
+
+    pub fn with_stroke_width(mut self, stroke_width: f64) -> Self {
+        self.stroke_width = stroke_width;
+        self
+    }
+
+    pub fn values(&self) -> &[(f64, f64)] {
+        &self.values
+    }
+
+    pub fn baseline(&self) -> &Baseline {
+        &self.baseline
+    }
+
+    pub fn set_values(&mut self, new_values: impl Into<Arc<[(f64, f64)]>>, baseline: Baseline) {
+        self.values = new_values.into();
+        self.baseline = baseline;
+        self.size = None;
+    }
+
+    /// Convert a value to its position in the chart area.
+    fn to_point(&self, (x, y): (f64, f64), size: Size) -> Point {
+        // The y position is reversed (because we want 0 at the bottom, not the top)
+        Point::new(
+            self.x_range.t(x) * size.width,
+            (1. - self.y_range.t(y)) * size.height,
+        )
+    }
+
+    /// The `(upper, lower)` value pairs for each point.
+    fn edges(&self) -> Vec<((f64, f64), (f64, f64))> {
+        self.values
+            .iter()
+            .enumerate()
+            .map(|(idx, &upper)| {
+                let lower = match &self.baseline {
+                    Baseline::Value(y) => (upper.0, *y),
+                    Baseline::Series(lower) => {
+                        lower.get(idx).copied().unwrap_or((f64::NAN, f64::NAN))
+                    }
+                };
+                (upper, lower)
+            })
+            .collect()
+    }
+
+    /// The outline of each unbroken part of the area, and its top edge.
+    fn paths(&self, size: Size) -> Vec<(BezPath, BezPath)> {
+        let edges = self.edges();
+        let missing = |&((x0, y0), (x1, y1)): &((f64, f64), (f64, f64))| {
+            is_missing(x0) || is_missing(y0) || is_missing(x1) || is_missing(y1)
+        };
+        present_runs(&edges, missing)
+            .into_iter()
+            .map(|run| {
+                let run = &edges[run];
+                let mut area = BezPath::new();
+                let mut top = BezPath::new();
+                for (idx, &(upper, _)) in run.iter().enumerate() {
+                    let point = self.to_point(upper, size);
+                    if idx == 0 {
+                        area.move_to(point);
+                        top.move_to(point);
+                    } else {
+                        area.line_to(point);
+                        top.line_to(point);
+                    }
+                }
+                for &(_, lower) in run.iter().rev() {
+                    area.line_to(self.to_point(lower, size));
+                }
+                area.close_path();
+                // A single point has no area, so show its extent with a line instead.
+                if let [(_, lower)] = run {
+                    top.line_to(self.to_point(*lower, size));
+                }
+                (area, top)
+            })
+            .collect()
+    }
+}
+
+impl Trace for AreaTrace {
+    fn layout(&mut self, size: Size, _rc: &mut Piet) -> Result<(), PietError> {
+        self.size = Some(size);
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        self.size.unwrap()
+    }
+
+    fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
+    fn draw(&self, rc: &mut Piet) {
+        let size = self.size.unwrap();
+        let fill = self.color.clone().with_alpha(self.fill_opacity);
+        for (area, top) in self.paths(size) {
+            rc.fill(area, &fill);
+            if self.stroke_width > 0. {
+                rc.stroke(top, &self.color, self.stroke_width);
+            }
+        }
+    }
+
+    fn set_intervals(&mut self, x_range: Interval, y_range: Interval) {
+        self.x_range = x_range;
+        self.y_range = y_range;
+    }
+
+    fn has_intervals(&self) -> bool {
+        true
+    }
+
+    fn is_empty(&self) -> bool {
+        self.values
+            .iter()
+            .all(|&(x, y)| is_missing(x) || is_missing(y))
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[test]
+fn test_stack() {
+    let series: Vec<Arc<[f64]>> = vec![vec![1., 2.].into(), vec![3., f64::NAN].into()];
+    let stacked = stack(&series, StackOffset::Zero);
+    assert_eq!(stacked[0], vec![(0., 1.), (0., 2.)]);
+    assert_eq!(stacked[1][0], (1., 4.));
+    assert!(stacked[1][1].0 == 2. && stacked[1][1].1.is_nan());
+
+    // A missing value breaks every layer above it.
+    let series: Vec<Arc<[f64]>> = vec![vec![1., f64::NAN, 1.].into(), vec![1., 1., 1.].into()];
+    let stacked = stack(&series, StackOffset::Zero);
+    assert!(stacked[1][1].0.is_nan() && stacked[1][1].1.is_nan());
+    let mut chart = crate::chart::test_render(stacked_area_chart(
+        vec![0., 1., 2.],
+        series,
+        StackOffset::Zero,
+    ));
+    for trace in chart.traces_mut::<AreaTrace>() {
+        assert_eq!(trace.paths(trace.size()).len(), 2);
+    }
+
+    // A single constant layer doesn't wiggle.
+    let series: Vec<Arc<[f64]>> = vec![vec![2., 2., 2.].into()];
+    let stacked = stack(&series, StackOffset::Wiggle);
+    assert_eq!(stacked[0], vec![(0., 2.), (0., 2.), (0., 2.)]);
+}
+
+#[test]
+fn test_area_chart_degenerate_data() {
+    use crate::chart::test_degenerate;
+    test_degenerate(area_chart(vec![]), true);
+    let (x_range, y_range) = test_degenerate(area_chart(vec![(1., 2.)]), false);
+    assert!(x_range.unwrap().contains(1.) && y_range.unwrap().contains(2.));
+    let (_, y_range) = test_degenerate(area_chart(vec![(0., 0.), (1., 0.)]), false);
+    assert!(y_range.unwrap().contains(0.));
+    let no_series: Vec<Vec<f64>> = vec![];
+    test_degenerate(
+        stacked_area_chart(vec![], no_series, StackOffset::Zero),
+        true,
+    );
+    let (_, y_range) = test_degenerate(
+        stacked_area_chart(vec![1.], [vec![2.]], StackOffset::Zero),
+        false,
+    );
+    assert!(y_range.unwrap().contains(0.) && y_range.unwrap().contains(2.));
+    test_degenerate(
+        stacked_area_chart(vec![1., 2.], [vec![2., 2.]], StackOffset::Wiggle),
+        false,
+    );
+    assert!(matches!(
+        try_stacked_area_chart(vec![1., 2.], [vec![2., 2.], vec![3.]], StackOffset::Zero),
+        Err(Error::MismatchedLengths {
+            expected: 2,
+            found: 1
+        })
+    ));
+}
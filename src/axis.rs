@@ -1,5 +1,5 @@
 // TODO implement toPrecision from javascript - it gives better results.
-use crate::{theme, ticker::Ticker};
+use crate::{theme, ticker::Ticker, Error, Interval};
 use piet_common::{
     kurbo::{Line, Point, Rect, Size},
     Color, Error as PietError, Piet, PietTextLayout, RenderContext, Text, TextAttribute,
@@ -110,6 +110,21 @@ impl<T: Ticker> Axis<T> {
         self.is_layout_valid = false;
     }
 
+    /// Change the interval shown by the ticker (see `Ticker::set_interval`).
+    pub fn set_interval(&mut self, interval: Interval) {
+        self.ticker.set_interval(interval);
+        self.is_layout_valid = false;
+    }
+
+    /// Like `size`, but returns an error rather than panicking if `layout` hasn't been called.
+    pub fn try_size(&self) -> Result<Size, Error> {
+        self.check_layout()?;
+        Ok(self.size())
+    }
+
+    /// # Panics
+    ///
+    /// Panics if `layout` hasn't been called.
     pub fn size(&self) -> Size {
         self.assert_layout();
 
@@ -133,6 +148,20 @@ impl<T: Ticker> Axis<T> {
         }
     }
 
+    /// The number of tick labels, including any that aren't drawn.
+    ///
+    /// Only valid after `layout` has been called.
+    pub fn label_count(&self) -> usize {
+        self.label_layouts.len()
+    }
+
+    /// The number of tick labels that aren't drawn because they would overlap.
+    ///
+    /// Only valid after `layout` has been called.
+    pub fn dropped_label_count(&self) -> usize {
+        self.label_layouts.len() - self.labels_to_draw.len()
+    }
+
     /// Call this before draw.
     pub fn layout(&mut self, axis_len: f64, rc: &mut Piet) -> Result<(), PietError> {
         self.is_layout_valid = true;
@@ -143,7 +172,18 @@ impl<T: Ticker> Axis<T> {
         Ok(())
     }
 
+    /// Like `draw`, but returns an error rather than panicking if `layout` hasn't been called.
+    pub fn try_draw(&self, rc: &mut Piet) -> Result<(), Error> {
+        self.check_layout()?;
+        self.draw(rc);
+        Ok(())
+    }
+
     /// Draw the layout
+    ///
+    /// # Panics
+    ///
+    /// Panics if `layout` hasn't been called.
     pub fn draw(&self, rc: &mut Piet) {
         let Size { width, height } = self.size();
 
@@ -275,7 +315,13 @@ impl<T: Ticker> Axis<T> {
             step += 1;
         }
         // If we can't layout anything, then show nothing.
-        println!("can't layout anything");
+        if !self.label_layouts.is_empty() {
+            log_warn!(
+                "can't fit any of {} axis labels in {}px",
+                self.label_layouts.len(),
+                self.axis_len
+            );
+        }
         self.labels_to_draw.clear();
     }
 
@@ -315,18 +361,26 @@ impl<T: Ticker> Axis<T> {
         false
     }
 
+    fn check_layout(&self) -> Result<(), Error> {
+        if self.is_layout_valid {
+            Ok(())
+        } else {
+            Err(Error::LayoutNotPerformed)
+        }
+    }
+
     fn assert_layout(&self) {
-        if !self.is_layout_valid {
-            panic!("layout not called");
+        if let Err(e) = self.check_layout() {
+            panic!("{}", e);
         }
     }
 }
 
 /// The label's text layout with position information
 #[derive(Clone)]
-struct Label {
-    pos: Point,
-    layout: PietTextLayout,
+pub(crate) struct Label {
+    pub(crate) pos: Point,
+    pub(crate) layout: PietTextLayout,
 }
 
 impl Label {
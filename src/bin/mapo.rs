@@ -0,0 +1,332 @@
+//! Plot CSV data from the command line.
+//!
+//! ```text
+//! mapo monica.csv --chart bar -x edlevel -y systm --aggregate mean -o systm.png
+//! cat prices.csv | mapo --chart line -x date -y close -o prices.png --width 1200 --dpi 192
+//! mapo monica.csv --spec chart.toml -o chart.svg
+//! ```
+//!
+//! The image format comes from the extension of the output file: `.png`, or `.svg` where
+//! piet-common draws with cairo (Linux and the BSDs).
+use clap::Parser;
+use mapo::{
+    spec::{ChartSpec, ColorMapName, Mark, TraceSpec},
+    Aggregate, Chart, Table,
+};
+use piet_common::{
+    kurbo::{Rect, Size},
+    Color, Device, Piet, RenderContext,
+};
+use std::{
+    error::Error,
+    ffi::OsStr,
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// Render a chart of a CSV file (with a header row) to a PNG or SVG image.
+#[derive(Parser)]
+#[clap(name = "mapo", version)]
+struct Args {
+    /// The CSV file to plot. Reads stdin if missing or `-`.
+    input: Option<PathBuf>,
+    /// Where to write the image, with a `.png` or `.svg` extension.
+    #[clap(short, long)]
+    output: PathBuf,
+    /// A chart spec, as JSON or (with a `.toml` extension) TOML, instead of the chart flags.
+    #[clap(long, conflicts_with_all = &["chart", "x", "y", "color", "size", "aggregate"])]
+    spec: Option<PathBuf>,
+    /// The type of chart: scatter, line, area, bar or box.
+    #[clap(long, parse(try_from_str = parse_mark))]
+    chart: Option<Mark>,
+    /// The column for x values (the categories of bar and box charts).
+    #[clap(short)]
+    x: Option<String>,
+    /// The column for y values.
+    #[clap(short)]
+    y: Option<String>,
+    /// A column to color by: one series per category, or a color map for numbers.
+    #[clap(long)]
+    color: Option<String>,
+    /// A numeric column to size scatter points by.
+    #[clap(long)]
+    size: Option<String>,
+    /// How to summarize y for each bar: count, sum, mean, median, min, max or a quantile like
+    /// q0.9.
+    #[clap(long, parse(try_from_str = parse_aggregate))]
+    aggregate: Option<Aggregate>,
+    /// The width of the image, in points.
+    #[clap(long, default_value = "800", parse(try_from_str = parse_positive))]
+    width: f64,
+    /// The height of the image, in points.
+    #[clap(long, default_value = "600", parse(try_from_str = parse_positive))]
+    height: f64,
+    /// The resolution of a PNG image. 96 is one pixel per point.
+    #[clap(long, default_value = "96", parse(try_from_str = parse_positive))]
+    dpi: f64,
+    /// The background color, as a hex string.
+    #[clap(long, default_value = "#ffffff")]
+    background: String,
+    /// Comma-separated hex colors given to each series in turn.
+    #[clap(long)]
+    palette: Option<String>,
+    /// The color map for numeric color columns: viridis, red_blue or greys.
+    #[clap(long, parse(try_from_str = parse_color_map))]
+    color_map: Option<ColorMapName>,
+}
+
+fn main() {
+    if let Err(e) = run(Args::parse()) {
+        eprintln!("mapo: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let format = output_format(&args.output)?;
+
+    let table = read_table(args.input.as_deref(), io::stdin().lock())?;
+    let mut spec = match &args.spec {
+        Some(path) => read_spec(path)?,
+        None => spec_from_args(&args)?,
+    };
+    if let Some(palette) = &args.palette {
+        spec.theme.palette = palette.split(',').map(|c| c.trim().to_string()).collect();
+    }
+    if let Some(color_map) = args.color_map {
+        spec.theme.color_map = color_map;
+    }
+    let mut chart = spec.to_chart(&table)?;
+
+    let background = Color::from_hex_str(&args.background)
+        .map_err(|_| format!("invalid background color \"{}\"", args.background))?;
+    let size = Size::new(args.width, args.height);
+    match format {
+        Format::Png => write_png(&mut chart, size, args.dpi, &background, &args.output),
+        Format::Svg => write_svg(&mut chart, size, &background, &args.output),
+    }
+}
+
+/// The image formats that can be written.
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Format {
+    Png,
+    Svg,
+}
+
+/// The format to write, from the extension of `path`.
+fn output_format(path: &Path) -> Result<Format, String> {
+    let ext = path.extension().and_then(OsStr::to_str);
+    match ext.map(str::to_ascii_lowercase).as_deref() {
+        Some("png") => Ok(Format::Png),
+        Some("svg") => Ok(Format::Svg),
+        _ => Err("the output must be a `.png` or `.svg` file".into()),
+    }
+}
+
+/// Read CSV from `input`, or from `stdin` if `input` is missing or `-`.
+fn read_table(input: Option<&Path>, stdin: impl io::Read) -> Result<Table, mapo::Error> {
+    match input {
+        Some(path) if path != Path::new("-") => Table::from_csv_path(path),
+        _ => Table::from_csv(stdin),
+    }
+}
+
+/// Fill the background, then lay out and draw `chart` to fill `size`.
+fn draw(
+    chart: &mut Chart,
+    size: Size,
+    background: &Color,
+    rc: &mut Piet,
+) -> Result<(), mapo::Error> {
+    rc.fill(Rect::from_origin_size((0., 0.), size), background);
+    chart.layout(size, rc)?;
+    chart.try_draw(rc)
+}
+
+fn write_png(
+    chart: &mut Chart,
+    size: Size,
+    dpi: f64,
+    background: &Color,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let scale = dpi / 96.;
+    let mut device = Device::new()?;
+    let mut bitmap = device.bitmap_target(
+        (size.width * scale).round() as usize,
+        (size.height * scale).round() as usize,
+        scale,
+    )?;
+    let mut rc = bitmap.render_context();
+    draw(chart, size, background, &mut rc)?;
+    rc.finish()?;
+    drop(rc);
+    bitmap.save_to_file(path)?;
+    Ok(())
+}
+
+/// piet-common draws with cairo here, so cairo can write the SVG.
+#[cfg(all(unix, not(any(target_os = "macos", target_os = "ios"))))]
+fn write_svg(
+    chart: &mut Chart,
+    size: Size,
+    background: &Color,
+    path: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let surface = cairo::SvgSurface::new(size.width, size.height, Some(path))?;
+    let cr = cairo::Context::new(&surface)?;
+    let mut rc = piet_common::CairoRenderContext::new(&cr);
+    draw(chart, size, background, &mut rc)?;
+    rc.finish()?;
+    drop(rc);
+    surface.finish();
+    Ok(())
+}
+
+#[cfg(not(all(unix, not(any(target_os = "macos", target_os = "ios")))))]
+fn write_svg(_: &mut Chart, _: Size, _: &Color, _: &Path) -> Result<(), Box<dyn Error>> {
+    Err("SVG output is only supported where piet-common draws with cairo".into())
+}
+
+fn read_spec(path: &Path) -> Result<ChartSpec, Box<dyn Error>> {
+    let text = fs::read_to_string(path)?;
+    if path.extension() == Some(OsStr::new("toml")) {
+        Ok(toml::from_str(&text)?)
+    } else {
+        Ok(serde_json::from_str(&text)?)
+    }
+}
+
+fn spec_from_args(args: &Args) -> Result<ChartSpec, Box<dyn Error>> {
+    let (mark, x) = match (args.chart, &args.x) {
+        (Some(mark), Some(x)) => (mark, x),
+        _ => return Err("either --spec, or --chart and -x, are needed".into()),
+    };
+    let mut trace = TraceSpec::new(mark, x);
+    trace.encoding.y = args.y.clone();
+    trace.encoding.color = args.color.clone();
+    trace.encoding.size = args.size.clone();
+    trace.aggregate = args.aggregate;
+    Ok(ChartSpec::new().with_trace(trace))
+}
+
+/// Parse a number that is positive and finite, e.g. a width.
+fn parse_positive(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(value) if value > 0. && value.is_finite() => Ok(value),
+        _ => Err(format!("\"{}\" is not a positive, finite number", s)),
+    }
+}
+
+fn parse_mark(s: &str) -> Result<Mark, String> {
+    Ok(match s {
+        "scatter" => Mark::Scatter,
+        "line" => Mark::Line,
+        "area" => Mark::Area,
+        "bar" => Mark::Bar,
+        "box" => Mark::Box,
+        _ => return Err(format!("unknown chart type \"{}\"", s)),
+    })
+}
+
+fn parse_aggregate(s: &str) -> Result<Aggregate, String> {
+    Ok(match s {
+        "count" => Aggregate::Count,
+        "sum" => Aggregate::Sum,
+        "mean" => Aggregate::Mean,
+        "median" => Aggregate::Median,
+        "min" => Aggregate::Min,
+        "max" => Aggregate::Max,
+        _ => match s.strip_prefix('q').and_then(|p| p.parse::<f64>().ok()) {
+            Some(p) if (0. ..=1.).contains(&p) => Aggregate::Quantile(p),
+            _ => return Err(format!("unknown aggregate \"{}\"", s)),
+        },
+    })
+}
+
+fn parse_color_map(s: &str) -> Result<ColorMapName, String> {
+    Ok(match s {
+        "viridis" => ColorMapName::Viridis,
+        "red_blue" => ColorMapName::RedBlue,
+        "greys" => ColorMapName::Greys,
+        _ => return Err(format!("unknown color map \"{}\"", s)),
+    })
+}
+
+#[test]
+fn test_parse_positive() {
+    assert_eq!(parse_positive("800"), Ok(800.));
+    assert_eq!(parse_positive("1.5"), Ok(1.5));
+    for s in ["0", "-1", "inf", "NaN", "1e400", "wide"] {
+        assert!(parse_positive(s).is_err(), "{}", s);
+    }
+    assert!(Args::try_parse_from(["mapo", "-o", "chart.png", "--width", "inf"]).is_err());
+}
+
+#[test]
+fn test_parse_mark() {
+    assert_eq!(parse_mark("scatter"), Ok(Mark::Scatter));
+    assert_eq!(parse_mark("box"), Ok(Mark::Box));
+    assert!(parse_mark("pie").is_err());
+    assert!(parse_mark("Bar").is_err());
+}
+
+#[test]
+fn test_parse_aggregate() {
+    assert_eq!(parse_aggregate("mean"), Ok(Aggregate::Mean));
+    assert_eq!(parse_aggregate("count"), Ok(Aggregate::Count));
+    assert_eq!(parse_aggregate("q0.9"), Ok(Aggregate::Quantile(0.9)));
+    assert_eq!(parse_aggregate("q1"), Ok(Aggregate::Quantile(1.)));
+    for invalid in ["q", "q1.5", "q-0.1", "qnan", "0.5", "average"] {
+        assert!(parse_aggregate(invalid).is_err(), "{}", invalid);
+    }
+}
+
+#[test]
+fn test_spec_from_args() {
+    let args = Args::parse_from([
+        "mapo",
+        "-o",
+        "out.png",
+        "--chart",
+        "bar",
+        "-x",
+        "edlevel",
+        "-y",
+        "systm",
+        "--aggregate",
+        "q0.5",
+    ]);
+    assert_eq!(args.input, None);
+    let spec = spec_from_args(&args).unwrap();
+    assert_eq!(spec.traces.len(), 1);
+    let trace = &spec.traces[0];
+    assert_eq!(trace.mark, Mark::Bar);
+    assert_eq!(trace.encoding.x, "edlevel");
+    assert_eq!(trace.encoding.y.as_deref(), Some("systm"));
+    assert_eq!(trace.aggregate, Some(Aggregate::Quantile(0.5)));
+
+    let no_x = Args::parse_from(["mapo", "-o", "out.png", "--chart", "line"]);
+    assert!(spec_from_args(&no_x).is_err());
+}
+
+#[test]
+fn test_output_format() {
+    assert_eq!(output_format(Path::new("chart.png")), Ok(Format::Png));
+    assert_eq!(output_format(Path::new("out/chart.SVG")), Ok(Format::Svg));
+    assert!(output_format(Path::new("chart.pdf")).is_err());
+    assert!(output_format(Path::new("chart")).is_err());
+}
+
+#[test]
+fn test_read_table_from_stdin() {
+    let csv = "x,y\n1,2\n3,4\n".as_bytes();
+    for input in [None, Some(Path::new("-"))] {
+        let table = read_table(input, csv).unwrap();
+        assert_eq!(table.len(), 2);
+        assert_eq!(&table.numbers("y").unwrap()[..], &[2., 4.]);
+    }
+    assert!(read_table(Some(Path::new("no/such/file.csv")), "".as_bytes()).is_err());
+}
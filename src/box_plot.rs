@@ -1,251 +1,240 @@
-use druid::{
-    im::Vector,
-    kurbo::{Line, Rect},
-    piet::PietText,
-    theme::LABEL_COLOR,
-    ArcStr, BoxConstraints, Color, Data, Env, Event, EventCtx, KeyOrValue, LayoutCtx, LifeCycle,
-    LifeCycleCtx, PaintCtx, RenderContext, Size, TextLayout, UpdateCtx, Widget,
-};
-use druid_lens_compose::ComposeLens;
-
+//! Box plots, showing the quartiles, whiskers and outliers of groups of values.
 use crate::{
-    axes::{data_as_range, Scale},
-    GRAPH_INSETS,
+    histogram::auto_bar_width,
+    prelude::*,
+    stats::{sorted_present, BoxStats},
+    theme, Categorical, Chart, Error, GridStyle, Interval, Trace,
+};
+use piet_common::{
+    kurbo::{Circle, Line, Rect, Size},
+    Color, Error as PietError, Piet, RenderContext,
 };
+use std::{any::Any, fmt};
+
+/// The radius of the circles drawn for outliers.
+const OUTLIER_RADIUS: f64 = 2.5;
+
+/// Create a box plot, with a box showing the distribution of each group of values.
+///
+/// Groups are paired with `labels` by index. NaN values are skipped.
+///
+/// # Panics
+///
+/// Panics if the number of groups and labels is different.
+pub fn box_plot<L, S>(
+    labels: impl Into<Categorical<L>>,
+    groups: impl IntoIterator<Item = S>,
+) -> Chart
+where
+    L: Clone + fmt::Debug + fmt::Display + 'static,
+    S: AsRef<[f64]>,
+{
+    match try_box_plot(labels, groups) {
+        Ok(chart) => chart,
+        Err(e) => panic!("{}", e),
+    }
+}
 
-/// A histogram of equal width categories
-#[derive(Debug, Clone, Data, ComposeLens)]
-pub struct BoxPlotData {
-    pub title: ArcStr,
-    pub data_points: Vector<f64>,
+/// Like `box_plot`, but returns an error if the number of groups and labels is different.
+pub fn try_box_plot<L, S>(
+    labels: impl Into<Categorical<L>>,
+    groups: impl IntoIterator<Item = S>,
+) -> Result<Chart, Error>
+where
+    L: Clone + fmt::Debug + fmt::Display + 'static,
+    S: AsRef<[f64]>,
+{
+    let labels = labels.into();
+    let groups: Vec<S> = groups.into_iter().collect();
+    if groups.len() != labels.categories().len() {
+        return Err(Error::MismatchedLengths {
+            expected: labels.categories().len(),
+            found: groups.len(),
+        });
+    }
+    let y_range = groups
+        .iter()
+        .flat_map(|values| values.as_ref().iter().copied())
+        .collect::<Interval>()
+        .to_rounded();
+    Ok(Chart::new()
+        .with_left_axis(y_range.ticker().reverse())
+        .with_left_grid(GridStyle::default())
+        .with_bottom_axis(labels.space_around_ticker())
+        .with_trace(BoxPlotTrace::new(groups, y_range)))
 }
 
-#[derive(Clone)]
-pub struct BoxPlot {
-    title_layout: TextLayout<ArcStr>,
-    // retained sorted list of data points
-    sorted_data_points: Option<Vec<f64>>,
-    graph_color: KeyOrValue<Color>,
-    // retained state for rendering the y axis.
-    y_scale: Option<Scale>,
+/// Draws a box plot of each of several groups of values: a box from the first to the third
+/// quartile with a line at the median, whiskers reaching the furthest values within 1.5 × the
+/// interquartile range of the box, and a circle for each value beyond the whiskers.
+///
+/// Groups are evenly spaced across the chart, to match a `space_around_ticker` on a `Categorical`
+/// axis.
+pub struct BoxPlotTrace {
+    /// The non-missing values of each group, sorted.
+    ///
+    /// Not public because we have retained state that depends on them.
+    groups: Vec<Vec<f64>>,
+    /// The quartiles and whiskers of each group, or `None` if the group is empty.
+    stats: Vec<Option<BoxStats>>,
+    /// The range that values should be shown over
+    y_range: Interval,
+    /// The color of the boxes, whiskers and outliers.
+    pub color: Color,
+    /// The opacity of the inside of the boxes.
+    pub fill_opacity: f64,
+    /// The width of the lines.
+    pub stroke_width: f64,
+    /// The width of each box. Chosen from the spacing of the groups if `None`.
+    pub width: Option<f64>,
+
+    // Retained
+    /// The size of the chart area.
+    pub size: Option<Size>,
 }
 
-impl BoxPlot {
-    pub fn new() -> Self {
-        let mut title_layout = TextLayout::new();
-        title_layout.set_text_size(20.);
-        BoxPlot {
-            title_layout,
-            sorted_data_points: None,
-            graph_color: LABEL_COLOR.into(),
-            y_scale: None,
-        }
+impl BoxPlotTrace {
+    /// A box plot of each group in `groups`. NaN values are skipped.
+    pub fn new<S: AsRef<[f64]>>(groups: impl IntoIterator<Item = S>, y_range: Interval) -> Self {
+        let mut trace = Self {
+            groups: vec![],
+            stats: vec![],
+            y_range,
+            color: theme::BAR_COLOR,
+            fill_opacity: theme::AREA_FILL_OPACITY,
+            stroke_width: 1.5,
+            width: None,
+            size: None,
+        };
+        trace.set_groups(groups);
+        trace
     }
 
-    /// Rebuild any parts of the retained state that need rebuilding.
-    fn rebuild_if_needed(
-        &mut self,
-        text: &mut PietText,
-        data: &BoxPlotData,
-        env: &Env,
-        size: Size,
-    ) {
-        self.title_layout.rebuild_if_needed(text, env);
-        if self.sorted_data_points.is_none() {
-            let mut dp: Vec<f64> = data.data_points.iter().copied().collect();
-            dp.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
-            self.sorted_data_points = Some(dp);
-        }
-        if self.y_scale.is_none() {
-            self.y_scale = Some(Scale::new_y(data_as_range(
-                self.sorted_data_points.as_ref().unwrap().iter().copied(),
-            )));
-        }
-        let graph_bounds = self.graph_bounds(size);
-        let y_scale = self.y_scale.as_mut().unwrap();
-        y_scale.set_graph_bounds(graph_bounds);
-        y_scale.rebuild_if_needed(text, env);
+    pub fn with_y_range(mut self, y_range: Interval) -> Self {
+        self.y_range = y_range;
+        self.size = None;
+        self
     }
 
-    pub fn graph_bounds(&self, size: Size) -> Rect {
-        size.to_rect().inset(GRAPH_INSETS)
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
     }
-}
 
-impl Widget<BoxPlotData> for BoxPlot {
-    fn event(&mut self, ctx: &mut EventCtx, event: &Event, data: &mut BoxPlotData, env: &Env) {}
-
-    fn lifecycle(
-        &mut self,
-        ctx: &mut LifeCycleCtx,
-        event: &LifeCycle,
-        data: &BoxPlotData,
-        env: &Env,
-    ) {
-        match event {
-            LifeCycle::WidgetAdded => {
-                self.title_layout.set_text(data.title.clone());
-            }
-            _ => (),
-        }
+    pub fn with_width(mut self, width: f64) -> Self {
+        self.width = Some(width);
+        self
     }
 
-    fn update(
-        &mut self,
-        ctx: &mut UpdateCtx,
-        old_data: &BoxPlotData,
-        data: &BoxPlotData,
-        env: &Env,
-    ) {
-        if !Data::same(&old_data.title, &data.title) {
-            self.title_layout.set_text(data.title.clone());
-        }
-        if self.title_layout.needs_rebuild_after_update(ctx) {
-            ctx.request_layout();
+    /// The sorted, non-missing values of each group.
+    pub fn groups(&self) -> &[Vec<f64>] {
+        &self.groups
+    }
+
+    pub fn set_groups<S: AsRef<[f64]>>(&mut self, groups: impl IntoIterator<Item = S>) {
+        self.groups = groups
+            .into_iter()
+            .map(|values| sorted_present(values.as_ref()))
+            .collect();
+        self.stats = self.groups.iter().map(|g| BoxStats::new(g)).collect();
+        self.size = None;
+    }
+
+    fn y_pos(&self, y: f64, size: Size) -> f64 {
+        (1. - self.y_range.t(y)) * size.height
+    }
+
+    fn draw_group(&self, values: &[f64], stats: BoxStats, x: f64, width: f64, rc: &mut Piet) {
+        let size = self.size.unwrap();
+        let (x0, x1) = (x - width * 0.5, x + width * 0.5);
+        let y = |v| self.y_pos(v, size);
+
+        // Whiskers, with caps half the width of the box.
+        for (from, to) in [(stats.q1, stats.low), (stats.q3, stats.high)] {
+            rc.stroke(
+                Line::new((x, y(from)), (x, y(to))),
+                &self.color,
+                self.stroke_width,
+            );
+            let cap = Line::new((x - width * 0.25, y(to)), (x + width * 0.25, y(to)));
+            rc.stroke(cap, &self.color, self.stroke_width);
         }
-        if !Data::same(&old_data.data_points, &data.data_points) {
-            // might be cheaper to just always recreate
-            if old_data.data_points != data.data_points {
-                self.sorted_data_points = None;
-                self.y_scale = None;
-                ctx.request_layout();
-            }
-        } else {
-            if let Some(y_scale) = self.y_scale.as_mut() {
-                if y_scale.update(ctx) {
-                    ctx.request_layout();
-                }
-            }
+
+        let quartiles = Rect::new(x0, y(stats.q3), x1, y(stats.q1));
+        rc.fill(quartiles, &self.color.clone().with_alpha(self.fill_opacity));
+        rc.stroke(quartiles, &self.color, self.stroke_width);
+        let median = Line::new((x0, y(stats.median)), (x1, y(stats.median)));
+        rc.stroke(median, &self.color, self.stroke_width * 2.);
+
+        let outliers = values
+            .iter()
+            .copied()
+            .filter(|v| *v < stats.low || *v > stats.high);
+        for v in outliers {
+            let circle = Circle::new((x, y(v)), OUTLIER_RADIUS);
+            rc.stroke(circle, &self.color, self.stroke_width * 0.5);
         }
     }
+}
 
-    fn layout(
-        &mut self,
-        ctx: &mut LayoutCtx,
-        bc: &BoxConstraints,
-        data: &BoxPlotData,
-        env: &Env,
-    ) -> Size {
-        let size = bc.max();
-        self.rebuild_if_needed(ctx.text(), data, env, size);
-        size
-    }
-
-    fn paint(&mut self, ctx: &mut PaintCtx, data: &BoxPlotData, env: &Env) {
-        let size = ctx.size();
-        let bounds = size.to_rect();
-        let graph_bounds = self.graph_bounds(size);
-        let axes_brush = ctx.solid_brush(Color::hlc(0.0, 60.0, 0.0));
-        let text_brush = ctx.solid_brush(Color::WHITE);
-        let bar_brush = ctx.solid_brush(Color::hlc(0.0, 50.0, 50.0));
-
-        // data stats
-        let mut data_points = data.data_points.clone();
-        data_points.sort_by(|left, right| left.partial_cmp(right).expect("cannot sort NaNs"));
-        assert!(data.data_points.len() > 0);
-        let data_min = *data_points.front().unwrap();
-        let data_qn10 = quantile(&data_points, 0.1);
-        let data_qn25 = quantile(&data_points, 0.25);
-        let data_qn50 = quantile(&data_points, 0.5);
-        let data_qn75 = quantile(&data_points, 0.75);
-        let data_qn90 = quantile(&data_points, 0.9);
-        let data_max = *data_points.back().unwrap();
-
-        // title
-        let title_size = self.title_layout.size();
-        self.title_layout
-            .draw(ctx, ((size.width - title_size.width) * 0.5, 40.0));
-
-        let datum_to_height = |datum: f64| -> f64 {
-            let t = (datum - data_min) / (data_max - data_min);
-            graph_bounds.y1 - t * graph_bounds.height()
-        };
+impl Trace for BoxPlotTrace {
+    fn layout(&mut self, size: Size, _rc: &mut Piet) -> Result<(), PietError> {
+        self.size = Some(size);
+        Ok(())
+    }
 
-        // y axis
-        self.y_scale.as_mut().unwrap().draw(ctx, env, true, true);
-
-        // data
-        const PLOT_WIDTH: f64 = 32.0;
-        let x_center =
-            ((graph_bounds.x1 + graph_bounds.x0) * 0.5).max(graph_bounds.x0 + PLOT_WIDTH * 0.5);
-        let horiz_line = |datum| {
-            let y = datum_to_height(datum);
-            Line::new(
-                (x_center - PLOT_WIDTH * 0.5, y),
-                (x_center + PLOT_WIDTH * 0.5, y),
-            )
-        };
-        ctx.stroke(horiz_line(data_qn90), &text_brush, 1.0);
-        ctx.stroke(
-            Line::new(
-                (x_center, datum_to_height(data_qn90)),
-                (x_center, datum_to_height(data_qn75)),
-            ),
-            &text_brush,
-            1.0,
-        );
-        ctx.stroke(
-            Rect::new(
-                x_center - PLOT_WIDTH * 0.5,
-                datum_to_height(data_qn75),
-                x_center + PLOT_WIDTH * 0.5,
-                datum_to_height(data_qn25),
-            ),
-            &text_brush,
-            1.0,
-        );
-        ctx.stroke(horiz_line(data_qn50), &text_brush, 1.0);
-        ctx.stroke(
-            Line::new(
-                (x_center, datum_to_height(data_qn25)),
-                (x_center, datum_to_height(data_qn10)),
-            ),
-            &text_brush,
-            1.0,
-        );
-        ctx.stroke(horiz_line(data_qn10), &text_brush, 1.0);
-
-        let mut draw_cross = |(x, y)| {
-            let cross = Rect::from_center_size((x, y), (PLOT_WIDTH * 0.25, PLOT_WIDTH * 0.25));
-            ctx.stroke(
-                Line::new((cross.x0, cross.y0), (cross.x1, cross.y1)),
-                &text_brush,
-                1.0,
-            );
-            ctx.stroke(
-                Line::new((cross.x0, cross.y1), (cross.x1, cross.y0)),
-                &text_brush,
-                1.0,
-            );
-        };
-        for datum in data_points.iter().copied() {
-            let mut prev_datum = None;
-            if datum < data_qn10 || datum > data_qn90 {
-                if let Some(d) = prev_datum {
-                    if d == datum {
-                        continue;
-                    }
-                }
-                /*
-                ctx.stroke(
-                    Circle::new((x_center, datum_to_height(datum)), 4.0),
-                    &text_brush,
-                    1.0,
-                );
-                */
-                draw_cross((x_center, datum_to_height(datum)));
-                prev_datum = Some(datum);
+    fn size(&self) -> Size {
+        self.size.unwrap()
+    }
+
+    fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
+    fn draw(&self, rc: &mut Piet) {
+        let gap = self.size.unwrap().width / self.groups.len() as f64;
+        let width = self.width.unwrap_or_else(|| auto_bar_width(gap));
+        for (idx, (values, stats)) in self.groups.iter().zip(&self.stats).enumerate() {
+            if let Some(stats) = stats {
+                self.draw_group(values, *stats, gap * (0.5 + idx as f64), width, rc);
             }
         }
     }
+
+    /// The x positions come from the order of the groups, so only the y interval changes.
+    fn set_intervals(&mut self, _x_range: Interval, y_range: Interval) {
+        self.y_range = y_range;
+        self.size = None;
+    }
+
+    fn has_intervals(&self) -> bool {
+        true
+    }
+
+    fn is_empty(&self) -> bool {
+        self.groups.iter().all(|group| group.is_empty())
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
 }
 
-/// Get the pth quantile from data.
-fn quantile(data: &Vector<f64>, p: f64) -> f64 {
-    let np1 = (data.len() + 1) as f64;
-    let k = (p * np1).floor() as usize;
-    let x_k = data.iter().copied().nth(k).unwrap();
-    let x_kp1 = data.iter().copied().nth(k + 1).unwrap();
-    let alpha = p * np1 - k as f64;
-    x_k + alpha * (x_kp1 - x_k)
+#[test]
+fn test_box_plot_degenerate_data() {
+    use crate::chart::test_degenerate;
+    test_degenerate(box_plot(Vec::<&str>::new(), Vec::<Vec<f64>>::new()), true);
+    assert!(matches!(
+        try_box_plot(vec!["a", "b"], [vec![1.]]),
+        Err(Error::MismatchedLengths {
+            expected: 2,
+            found: 1
+        })
+    ));
+    let (_, y_range) = test_degenerate(box_plot(vec!["a", "b"], [vec![], vec![1.]]), false);
+    assert!(y_range.unwrap().contains(1.));
+    let (_, y_range) = test_degenerate(box_plot(vec!["a"], [vec![2., 2., f64::NAN]]), false);
+    assert!(y_range.unwrap().contains(2.));
+    let (_, y_range) = test_degenerate(box_plot(vec!["a"], [vec![1., 2., 3., 4., 100.]]), false);
+    assert!(y_range.unwrap().contains(100.));
 }
@@ -1,10 +1,12 @@
 use crate::{
+    annotation::Layer,
     axis::{Axis, Direction, LabelPosition},
-    theme, Ticker, Trace,
+    theme, Annotations, Colorbar, Error, Interval, Ticker, Trace,
 };
 use piet_common::{
-    kurbo::{Affine, Line, Point, Rect, Size},
-    Color, Error as PietError, Piet, RenderContext,
+    kurbo::{Affine, Insets, Line, Point, Rect, Size},
+    Color, Error as PietError, Piet, PietTextLayout, RenderContext, Text, TextAttribute,
+    TextLayout, TextLayoutBuilder,
 };
 use std::any::Any;
 
@@ -25,8 +27,21 @@ pub struct Chart {
     /// An optional axis right of the chart.
     right_axis: Option<Axis<Box<dyn Ticker>>>,
     right_grid: Option<GridStyle>,
-    /// Histogram trace
-    traces: Vec<Box<dyn Trace>>,
+    /// An optional colorbar, drawn right of (if vertical) or below (if horizontal) the chart and
+    /// its axes.
+    colorbar: Option<Colorbar>,
+    /// The traces, and whether each is clipped to the chart area.
+    traces: Vec<(Box<dyn Trace>, bool)>,
+    /// Optional reference lines, spans and text, drawn with the traces.
+    annotations: Option<Annotations>,
+    /// Space left empty around the outside of the chart.
+    padding: Insets,
+    /// A fixed size for the chart area, rather than filling the available space.
+    plot_size: Option<Size>,
+    /// A fixed width / height ratio for the chart area.
+    aspect_ratio: Option<f64>,
+    /// Whether (and how) to make a unit on the x axis the same length as a unit on the y axis.
+    equal_scale: Option<EqualScale>,
 
     // Retained
     /// The size that everything should fit in (inc. axes).
@@ -35,6 +50,12 @@ pub struct Chart {
     ///
     /// Only valid after call to `layout`.
     chart_area: Option<Rect>,
+    /// The x and y intervals of the axes before they were made equal scale.
+    data_ranges: Option<(Interval, Interval)>,
+    /// The "no data" message, if all the traces are empty.
+    no_data: Option<PietTextLayout>,
+    /// What happened during the last call to `layout`.
+    layout_report: Option<LayoutReport>,
 }
 
 impl Chart {
@@ -48,9 +69,18 @@ impl Chart {
             left_grid: None,
             right_axis: None,
             right_grid: None,
+            colorbar: None,
             traces: vec![],
+            annotations: None,
+            padding: Insets::ZERO,
+            plot_size: None,
+            aspect_ratio: None,
+            equal_scale: None,
             size: None,
             chart_area: None,
+            data_ranges: None,
+            no_data: None,
+            layout_report: None,
         }
     }
 
@@ -134,15 +164,121 @@ impl Chart {
         self
     }
 
+    pub fn with_colorbar(mut self, colorbar: Colorbar) -> Self {
+        self.colorbar = Some(colorbar);
+        self
+    }
+
+    pub fn set_colorbar(&mut self, colorbar: Option<Colorbar>) -> &mut Self {
+        self.colorbar = colorbar;
+        self
+    }
+
+    /// Add a trace, clipped to the chart area.
     pub fn with_trace(mut self, trace: impl Trace + 'static) -> Self {
-        self.traces.push(Box::new(trace));
+        self.traces.push((Box::new(trace), true));
+        self
+    }
+
+    /// Add a trace that isn't clipped to the chart area, so it can draw over the axes (e.g. so
+    /// markers at the edge aren't cut in half).
+    pub fn with_unclipped_trace(mut self, trace: impl Trace + 'static) -> Self {
+        self.traces.push((Box::new(trace), false));
+        self
+    }
+
+    pub fn with_annotations(mut self, annotations: Annotations) -> Self {
+        self.annotations = Some(annotations);
+        self
+    }
+
+    pub fn set_annotations(&mut self, annotations: Option<Annotations>) -> &mut Self {
+        self.annotations = annotations;
+        self
+    }
+
+    /// Get the annotations, e.g. to add to them. Call `layout` after changing them.
+    pub fn annotations_mut(&mut self) -> Option<&mut Annotations> {
+        self.annotations.as_mut()
+    }
+
+    /// Leave `padding` empty around the outside of the chart.
+    pub fn with_padding(mut self, padding: impl Into<Insets>) -> Self {
+        self.padding = padding.into();
+        self
+    }
+
+    pub fn set_padding(&mut self, padding: impl Into<Insets>) -> &mut Self {
+        self.padding = padding.into();
+        self
+    }
+
+    /// Use a fixed size for the chart area (where the traces are drawn), with the axes around it.
+    ///
+    /// The chart and axes are centered in the space available, and overflow it if it is too
+    /// small. This takes precedence over any aspect ratio.
+    pub fn with_plot_size(mut self, plot_size: Size) -> Self {
+        self.plot_size = Some(plot_size);
+        self
+    }
+
+    pub fn set_plot_size(&mut self, plot_size: Option<Size>) -> &mut Self {
+        self.plot_size = plot_size;
+        self
+    }
+
+    /// Keep the chart area at `ratio` (width / height), making it as large as will fit.
+    ///
+    /// # Panics
+    ///
+    /// Panics unless `0 < ratio < ∞`.
+    pub fn with_aspect_ratio(mut self, ratio: f64) -> Self {
+        self.set_aspect_ratio(Some(ratio));
+        self
+    }
+
+    /// # Panics
+    ///
+    /// Panics unless `0 < ratio < ∞`.
+    pub fn set_aspect_ratio(&mut self, ratio: Option<f64>) -> &mut Self {
+        if let Err(e) = self.try_set_aspect_ratio(ratio) {
+            panic!("{}", e);
+        }
+        self
+    }
+
+    /// Like `set_aspect_ratio`, but returns `Error::InvalidAspectRatio` unless `0 < ratio < ∞`.
+    pub fn try_set_aspect_ratio(&mut self, ratio: Option<f64>) -> Result<&mut Self, Error> {
+        if let Some(ratio) = ratio {
+            if !(ratio > 0. && ratio.is_finite()) {
+                return Err(Error::InvalidAspectRatio(ratio));
+            }
+        }
+        self.aspect_ratio = ratio;
+        Ok(self)
+    }
+
+    /// Make a unit on the x axis the same length as a unit on the y axis, e.g. for maps or other
+    /// geometric data.
+    ///
+    /// The x interval is taken from the bottom (or top) axis and the y interval from the left (or
+    /// right) axis when the chart is first laid out, so those axes must use interval tickers.
+    /// `EqualScale::AdjustArea` takes precedence over any aspect ratio, and a fixed plot size
+    /// takes precedence over both.
+    pub fn with_equal_scale(mut self, equal_scale: EqualScale) -> Self {
+        self.set_equal_scale(Some(equal_scale));
+        self
+    }
+
+    pub fn set_equal_scale(&mut self, equal_scale: Option<EqualScale>) -> &mut Self {
+        self.equal_scale = equal_scale;
         self
     }
 
     pub fn traces_mut<T: Trace>(&mut self) -> impl Iterator<Item = &mut T> {
         self.traces
             .iter_mut()
-            .filter_map(|trace| trace.as_any().downcast_mut())
+            .filter_map(|(trace, _)| trace.as_any().downcast_mut())
     }
 
     /// # Panics
@@ -152,65 +288,220 @@ impl Chart {
         self.size.unwrap()
     }
 
+    /// Like `size`, but returns an error rather than panicking if `layout` hasn't been called.
+    pub fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
+    /// Diagnostics from the last call to `layout`, or `None` if it hasn't been called.
+    pub fn layout_report(&self) -> Option<&LayoutReport> {
+        self.layout_report.as_ref()
+    }
+
     /// Lay out the axes and calculate the chart area available.
     ///
+    /// The space taken by the axes depends on their labels, which depend on the length of the
+    /// axes. We start by reserving no space for the axes, lay them out at the chart size that
+    /// leaves, and then grow the reserved space to what they need. The reserved space never
+    /// shrinks, so the chart area never grows, and we stop as soon as the axes fit in the space
+    /// reserved for them (normally after 2 or 3 rounds).
+    ///
+    /// This is a search, not an exact solve: if the axes still don't fit after
+    /// `MAX_LAYOUT_ITERATIONS` (8) rounds, the last chart size is used and the chart overflows.
+    /// `LayoutReport::fits` is `false` when this happens.
+    ///
     /// Once the chart area has been calculated, each trace will have its `layout` method called.
+    /// Use `layout_report` afterwards to see how well the chart fitted.
     ///
     /// This function must be called before `draw`, both after creation and after anything changes.
     pub fn layout(&mut self, size: Size, rc: &mut Piet) -> Result<(), PietError> {
         self.size = Some(size);
-        // Loop until our layout fits.
-        // The initial guess is the whole area (we know this will be too big, bug it gives a first
-        // estimate for the axis sizes.
-        let mut chart_size = size;
-        // We abuse labelled loops so we can run some code if the for loop finishes before a
-        // solution has been found.
-        'found_height: loop {
-            // We expect this loop to complete after 2 loops.
-            for _ in 0..10 {
-                // Lay out the axes at the current size.
-                self.layout_axes(chart_size, rc)?;
-                // This size contains the space we need for the axes
-                let axis_size = self.axis_size();
-                if axis_size.height + chart_size.height < size.height
-                    && axis_size.width + chart_size.width < size.width
-                {
-                    // we've found a valid chart size
-                    break 'found_height;
-                }
-                // Chart size is still too big, try shrinking it to what would have fit with the
-                // current axes, minus a small delta to try to take fp accuracy out of the equation.
-                chart_size.height = size.height - axis_size.height - 1e-8;
-                chart_size.width = size.width - axis_size.width - 1e-8;
+        let available = non_negative(Size::new(
+            size.width - self.padding.x_value(),
+            size.height - self.padding.y_value(),
+        ));
+        let equal_scale = self.resolved_equal_scale();
+        let data_ranges = equal_scale.and(self.data_ranges());
+        // The space reserved for the axes and colorbar.
+        let mut reserved = Size::ZERO;
+        let mut iterations = 0;
+        let chart_size = loop {
+            iterations += 1;
+            let chart_size = self.constrain_chart_size(
+                non_negative(available - reserved),
+                equal_scale,
+                data_ranges,
+            );
+            if let (Some(EqualScale::AdjustIntervals), Some((x_range, y_range))) =
+                (equal_scale, data_ranges)
+            {
+                let (x_range, y_range) = equal_scale_intervals(x_range, y_range, chart_size);
+                self.set_axis_intervals(x_range, y_range);
             }
-            // We didn't find a solution, so warn and just draw as best we can
-            // TODO make a log msg
-            eprintln!("We didn't find a valid chart size, so the chart may overflow");
-            chart_size *= 0.9;
             self.layout_axes(chart_size, rc)?;
-            break;
+            let axis_size = self.axis_size();
+            if (axis_size.width <= reserved.width && axis_size.height <= reserved.height)
+                || iterations == MAX_LAYOUT_ITERATIONS
+            {
+                break chart_size;
+            }
+            reserved = Size::new(
+                reserved.width.max(axis_size.width),
+                reserved.height.max(axis_size.height),
+            );
+        };
+        let axis_size = self.axis_size();
+        // Ignore rounding error from subtracting and re-adding the reserved space.
+        let overflow = non_negative(axis_size + chart_size - available - Size::new(1e-6, 1e-6));
+        let fits = overflow == Size::ZERO;
+        if !fits {
+            log_warn!(
+                "the chart needs {:?} more space than the {:?} available, so it will overflow",
+                overflow,
+                available
+            );
         }
 
+        // Center the chart and axes in any space left over (e.g. because of a fixed aspect ratio).
+        let offset = (available - axis_size - chart_size).to_vec2() * 0.5;
         let chart_tl = Point::new(
-            self.left_axis
-                .as_ref()
-                .map(|axis| axis.size().width)
-                .unwrap_or(0.),
-            self.top_axis
-                .as_ref()
-                .map(|axis| axis.size().height)
-                .unwrap_or(0.),
-        );
+            self.padding.x0
+                + self
+                    .left_axis
+                    .as_ref()
+                    .map(|axis| axis.size().width)
+                    .unwrap_or(0.),
+            self.padding.y0
+                + self
+                    .top_axis
+                    .as_ref()
+                    .map(|axis| axis.size().height)
+                    .unwrap_or(0.),
+        ) + offset;
         self.chart_area = Some(Rect::from_origin_size(chart_tl, chart_size));
-        for trace in &mut self.traces {
+        if let (Some(EqualScale::AdjustIntervals), Some((x_range, y_range))) =
+            (equal_scale, data_ranges)
+        {
+            let (x_range, y_range) = equal_scale_intervals(x_range, y_range, chart_size);
+            for (trace, _) in &mut self.traces {
+                trace.set_intervals(x_range, y_range);
+            }
+            if let Some(annotations) = &mut self.annotations {
+                annotations.set_x_interval(x_range);
+                annotations.set_y_interval(y_range);
+            }
+        }
+        for (trace, _) in &mut self.traces {
             trace.layout(chart_size, rc)?;
         }
+        if let Some(annotations) = &mut self.annotations {
+            annotations.layout(chart_size, rc)?;
+        }
+        self.no_data = if self.traces.iter().all(|(trace, _)| trace.is_empty()) {
+            Some(
+                rc.text()
+                    .new_text_layout(theme::NO_DATA_MESSAGE)
+                    .default_attribute(TextAttribute::FontSize(theme::NO_DATA_FONT_SIZE))
+                    .default_attribute(TextAttribute::TextColor(theme::AXES_COLOR))
+                    .build()?,
+            )
+        } else {
+            None
+        };
 
-        //println!("{:#?}", self.left_axis);
-        //println!("{:#?}", self.bottom_axis);
+        let axis_report = |axis: &Option<Axis<Box<dyn Ticker>>>| {
+            axis.as_ref().map(|axis| AxisReport {
+                labels: axis.label_count(),
+                dropped_labels: axis.dropped_label_count(),
+            })
+        };
+        self.layout_report = Some(LayoutReport {
+            iterations,
+            fits,
+            overflow,
+            top_axis: axis_report(&self.top_axis),
+            bottom_axis: axis_report(&self.bottom_axis),
+            left_axis: axis_report(&self.left_axis),
+            right_axis: axis_report(&self.right_axis),
+            chart_area: Rect::from_origin_size(chart_tl, chart_size),
+        });
         Ok(())
     }
 
+    /// The chart size to use, given the space left after reserving space for the axes.
+    fn constrain_chart_size(
+        &self,
+        available: Size,
+        equal_scale: Option<EqualScale>,
+        data_ranges: Option<(Interval, Interval)>,
+    ) -> Size {
+        if let Some(plot_size) = self.plot_size {
+            return plot_size;
+        }
+        let aspect_ratio = match (equal_scale, data_ranges) {
+            (Some(EqualScale::AdjustArea), Some((x_range, y_range))) => {
+                Some(x_range.size() / y_range.size())
+            }
+            _ => self.aspect_ratio,
+        };
+        match aspect_ratio {
+            Some(ratio) => {
+                let width = available.width.min(available.height * ratio);
+                Size::new(width, width / ratio)
+            }
+            None => available,
+        }
+    }
+
+    /// The equal scale method to use: `AdjustIntervals` only works if every trace can change its
+    /// intervals, so otherwise we adjust the chart area instead.
+    fn resolved_equal_scale(&self) -> Option<EqualScale> {
+        match self.equal_scale {
+            Some(EqualScale::AdjustIntervals)
+                if !self.traces.iter().all(|(trace, _)| trace.has_intervals()) =>
+            {
+                log_warn!(
+                    "a trace can't change its intervals, so equal scale will adjust the chart \
+                     area instead"
+                );
+                Some(EqualScale::AdjustArea)
+            }
+            equal_scale => equal_scale,
+        }
+    }
+
+    /// The x and y intervals to make equal scale, remembered from the first time they're needed
+    /// so that repeated layouts don't keep widening them.
+    fn data_ranges(&mut self) -> Option<(Interval, Interval)> {
+        if self.data_ranges.is_none() {
+            let interval = |axis: &Option<Axis<Box<dyn Ticker>>>| {
+                axis.as_ref().and_then(|axis| axis.ticker().interval())
+            };
+            let x_range = interval(&self.bottom_axis).or_else(|| interval(&self.top_axis));
+            let y_range = interval(&self.left_axis).or_else(|| interval(&self.right_axis));
+            self.data_ranges = x_range.zip(y_range);
+            if self.data_ranges.is_none() {
+                log_warn!("equal scale needs interval tickers on an x axis and a y axis");
+            }
+        }
+        self.data_ranges
+    }
+
+    fn set_axis_intervals(&mut self, x_range: Interval, y_range: Interval) {
+        for axis in [&mut self.top_axis, &mut self.bottom_axis]
+            .into_iter()
+            .flatten()
+        {
+            axis.set_interval(x_range);
+        }
+        for axis in [&mut self.left_axis, &mut self.right_axis]
+            .into_iter()
+            .flatten()
+        {
+            axis.set_interval(y_range);
+        }
+    }
+
     /// Lays out the axes for a given chart size.
     fn layout_axes(&mut self, chart_size: Size, rc: &mut Piet) -> Result<(), PietError> {
         if let Some(axis) = &mut self.top_axis {
@@ -225,42 +516,68 @@ impl Chart {
         if let Some(axis) = &mut self.right_axis {
             axis.layout(chart_size.height, rc)?;
         }
+        if let Some(colorbar) = &mut self.colorbar {
+            let len = match colorbar.direction() {
+                Direction::Vertical => chart_size.height,
+                Direction::Horizontal => chart_size.width,
+            };
+            colorbar.layout(len, rc)?;
+        }
         Ok(())
     }
 
     fn axis_size(&self) -> Size {
-        Size {
-            width: self
-                .left_axis
-                .as_ref()
-                .map(|axis| axis.size().width)
-                .unwrap_or(0.)
-                + self
-                    .right_axis
+        let colorbar_size = self
+            .colorbar
+            .as_ref()
+            .map(|colorbar| match colorbar.direction() {
+                Direction::Vertical => Size::new(colorbar.size().width, 0.),
+                Direction::Horizontal => Size::new(0., colorbar.size().height),
+            })
+            .unwrap_or(Size::ZERO);
+        colorbar_size
+            + Size {
+                width: self
+                    .left_axis
                     .as_ref()
                     .map(|axis| axis.size().width)
-                    .unwrap_or(0.),
-            height: self
-                .top_axis
-                .as_ref()
-                .map(|axis| axis.size().height)
-                .unwrap_or(0.)
-                + self
-                    .bottom_axis
+                    .unwrap_or(0.)
+                    + self
+                        .right_axis
+                        .as_ref()
+                        .map(|axis| axis.size().width)
+                        .unwrap_or(0.),
+                height: self
+                    .top_axis
                     .as_ref()
                     .map(|axis| axis.size().height)
-                    .unwrap_or(0.),
-        }
+                    .unwrap_or(0.)
+                    + self
+                        .bottom_axis
+                        .as_ref()
+                        .map(|axis| axis.size().height)
+                        .unwrap_or(0.),
+            }
     }
 
-    /// Draw the histogram at (0,0).
+    /// Draw the chart at (0,0).
     ///
     /// # Panics
     ///
-    /// Panics if `layout` was not called.
+    /// Panics if the chart hasn't been laid out since it last changed, or if the render context
+    /// returns an error.
     pub fn draw(&self, rc: &mut Piet) {
-        //self.draw_grid(rc);
-        let chart_area = self.chart_area.unwrap();
+        if let Err(e) = self.try_draw(rc) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Draw the chart at (0,0), returning an error if the render context does.
+    ///
+    /// Changing a trace, axis or annotation invalidates its layout, so this returns
+    /// `Error::LayoutNotPerformed` (without drawing anything) if `layout` hasn't been called since.
+    pub fn try_draw(&self, rc: &mut Piet) -> Result<(), Error> {
+        let chart_area = self.check_layout()?;
 
         // Draw gridlines
         self.draw_grid(chart_area, rc);
@@ -268,21 +585,37 @@ impl Chart {
         // draw the chart data first, so the axes are on top
         rc.with_save(|rc| {
             rc.transform(Affine::translate(chart_area.origin().to_vec2()));
-            for trace in &self.traces {
-                trace.draw(rc);
+            let clip = chart_area.size().to_rect();
+            if let Some(annotations) = &self.annotations {
+                with_clip(clip, rc, |rc| annotations.draw(Layer::BelowTraces, rc))?;
+            }
+            for (trace, clipped) in &self.traces {
+                if *clipped {
+                    with_clip(clip, rc, |rc| trace.draw(rc))?;
+                } else {
+                    trace.draw(rc);
+                }
+            }
+            if let Some(annotations) = &self.annotations {
+                with_clip(clip, rc, |rc| annotations.draw(Layer::AboveTraces, rc))?;
+            }
+            if let Some(layout) = &self.no_data {
+                let pos = (chart_area.size().to_vec2() - layout.size().to_vec2()) * 0.5;
+                rc.draw_text(layout, pos.to_point());
             }
             Ok(())
-        })
-        .unwrap();
+        })?;
         // now draw axes
         // top
         if let Some(axis) = self.top_axis.as_ref() {
             rc.with_save(|rc| {
-                rc.transform(Affine::translate((chart_area.x0, 0.)));
+                rc.transform(Affine::translate((
+                    chart_area.x0,
+                    chart_area.y0 - axis.size().height,
+                )));
                 axis.draw(rc);
                 Ok(())
-            })
-            .unwrap();
+            })?;
         }
         // bottom
         if let Some(axis) = self.bottom_axis.as_ref() {
@@ -290,17 +623,18 @@ impl Chart {
                 rc.transform(Affine::translate((chart_area.x0, chart_area.y1)));
                 axis.draw(rc);
                 Ok(())
-            })
-            .unwrap();
+            })?;
         }
         // left
         if let Some(axis) = self.left_axis.as_ref() {
             rc.with_save(|rc| {
-                rc.transform(Affine::translate((0., chart_area.y0)));
+                rc.transform(Affine::translate((
+                    chart_area.x0 - axis.size().width,
+                    chart_area.y0,
+                )));
                 axis.draw(rc);
                 Ok(())
-            })
-            .unwrap();
+            })?;
         }
         // right
         if let Some(axis) = self.right_axis.as_ref() {
@@ -308,9 +642,58 @@ impl Chart {
                 rc.transform(Affine::translate((chart_area.x1, chart_area.y0)));
                 axis.draw(rc);
                 Ok(())
-            })
-            .unwrap();
+            })?;
         }
+        if let (Some(colorbar), Some(origin)) = (&self.colorbar, self.colorbar_origin(chart_area)) {
+            rc.with_save(|rc| {
+                rc.transform(Affine::translate(origin.to_vec2()));
+                colorbar.draw(rc);
+                Ok(())
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Check that every part of the chart has been laid out, returning the chart area.
+    fn check_layout(&self) -> Result<Rect, Error> {
+        let chart_area = self.chart_area.ok_or(Error::LayoutNotPerformed)?;
+        for (trace, _) in &self.traces {
+            trace.try_size()?;
+        }
+        if let Some(annotations) = &self.annotations {
+            annotations.size.ok_or(Error::LayoutNotPerformed)?;
+        }
+        let axes = [
+            &self.top_axis,
+            &self.bottom_axis,
+            &self.left_axis,
+            &self.right_axis,
+        ];
+        for axis in axes.into_iter().flatten() {
+            axis.try_size()?;
+        }
+        if let Some(colorbar) = &self.colorbar {
+            colorbar.try_size()?;
+        }
+        Ok(chart_area)
+    }
+
+    /// Where the top left of the colorbar goes: outside any axis on the same side of the chart
+    /// area.
+    pub(crate) fn colorbar_origin(&self, chart_area: Rect) -> Option<Point> {
+        let axis_extent = |axis: &Option<Axis<Box<dyn Ticker>>>, extent: fn(Size) -> f64| {
+            axis.as_ref().map(|axis| extent(axis.size())).unwrap_or(0.)
+        };
+        Some(match self.colorbar.as_ref()?.direction() {
+            Direction::Vertical => Point::new(
+                chart_area.x1 + axis_extent(&self.right_axis, |size| size.width),
+                chart_area.y0,
+            ),
+            Direction::Horizontal => Point::new(
+                chart_area.x0,
+                chart_area.y1 + axis_extent(&self.bottom_axis, |size| size.height),
+            ),
+        })
     }
 
     /// Draw on the gridlines.
@@ -368,6 +751,76 @@ impl Default for Chart {
     }
 }
 
+/// Run `f` with drawing clipped to `rect`.
+fn with_clip(rect: Rect, rc: &mut Piet, f: impl FnOnce(&mut Piet)) -> Result<(), PietError> {
+    rc.with_save(|rc| {
+        rc.clip(rect);
+        f(rc);
+        Ok(())
+    })
+}
+
+/// How `Chart` makes a unit on the x axis the same length as a unit on the y axis.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum EqualScale {
+    /// Change the shape of the chart area to match the x and y intervals.
+    AdjustArea,
+    /// Widen the x or y interval about its center to match the shape of the chart area, so the
+    /// chart area can fill the space available.
+    ///
+    /// Falls back to `AdjustArea` if any trace can't change its intervals (see
+    /// `Trace::has_intervals`).
+    AdjustIntervals,
+}
+
+/// Widen either `x_range` or `y_range` so that they have the same scale when drawn in `size`.
+fn equal_scale_intervals(x_range: Interval, y_range: Interval, size: Size) -> (Interval, Interval) {
+    if size.width <= 0. || size.height <= 0. {
+        return (x_range, y_range);
+    }
+    // Use the larger number of units per pixel for both.
+    let scale = (x_range.size() / size.width).max(y_range.size() / size.height);
+    (
+        x_range.scale_center(scale * size.width / x_range.size()),
+        y_range.scale_center(scale * size.height / y_range.size()),
+    )
+}
+
+/// The most rounds of axis layout `Chart::layout` will do before giving up on finding a chart size
+/// where everything fits.
+const MAX_LAYOUT_ITERATIONS: usize = 8;
+
+/// `size` with any negative dimensions replaced by 0.
+fn non_negative(size: Size) -> Size {
+    Size::new(size.width.max(0.), size.height.max(0.))
+}
+
+/// Diagnostics from `Chart::layout`, for finding out why a chart doesn't look right.
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutReport {
+    /// How many times the axes were laid out while searching for a chart size that fits.
+    pub iterations: usize,
+    /// Whether a chart size was found where the chart area and axes fit in the available size.
+    pub fits: bool,
+    /// How far the chart area and axes extend past the available size (zero if they fit).
+    pub overflow: Size,
+    pub top_axis: Option<AxisReport>,
+    pub bottom_axis: Option<AxisReport>,
+    pub left_axis: Option<AxisReport>,
+    pub right_axis: Option<AxisReport>,
+    /// The final chart area (where the traces are drawn).
+    pub chart_area: Rect,
+}
+
+/// Diagnostics for one axis of a chart.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct AxisReport {
+    /// The number of tick labels the ticker produced.
+    pub labels: usize,
+    /// The number of those labels that aren't drawn because they would overlap.
+    pub dropped_labels: usize,
+}
+
 pub struct GridStyle {
     pub stroke_width: f64,
     pub color: Color,
@@ -381,3 +834,232 @@ impl Default for GridStyle {
         }
     }
 }
+
+/// Run `f` with a render context for a bitmap, panicking on any error.
+#[cfg(test)]
+pub(crate) fn with_test_context(f: impl FnOnce(&mut Piet) -> Result<(), Error>) {
+    let mut device = piet_common::Device::new().unwrap();
+    let mut target = device.bitmap_target(400, 300, 1.).unwrap();
+    let mut rc = target.render_context();
+    f(&mut rc).unwrap();
+    rc.finish().unwrap();
+}
+
+/// Lay out and draw `chart`, panicking on any error.
+#[cfg(test)]
+pub(crate) fn test_render(mut chart: Chart) -> Chart {
+    with_test_context(|rc| {
+        chart.layout(Size::new(400., 300.), rc)?;
+        chart.try_draw(rc)
+    });
+    chart
+}
+
+/// Render `chart` with `test_render`, check that it shows the "no data" message exactly when
+/// `no_data` is true and that its x and y axes have valid intervals, and return those intervals
+/// (`None` for an axis without an interval, e.g. a categorical one).
+#[cfg(test)]
+pub(crate) fn test_degenerate(chart: Chart, no_data: bool) -> (Option<Interval>, Option<Interval>) {
+    let chart = test_render(chart);
+    assert_eq!(chart.no_data.is_some(), no_data, "wrong \"no data\" state");
+    let interval = |axis: &Option<Axis<Box<dyn Ticker>>>| {
+        axis.as_ref().and_then(|axis| axis.ticker().interval())
+    };
+    let x_range = interval(&chart.bottom_axis).or_else(|| interval(&chart.top_axis));
+    let y_range = interval(&chart.left_axis).or_else(|| interval(&chart.right_axis));
+    for range in x_range.iter().chain(&y_range) {
+        assert!(range.is_valid(), "invalid axis interval {:?}", range);
+    }
+    (x_range, y_range)
+}
+
+#[test]
+fn test_no_data_message() {
+    use crate::line::line_chart;
+    assert!(test_render(Chart::new()).no_data.is_some());
+    let empty: Vec<Vec<(f64, f64)>> = vec![vec![], vec![(f64::NAN, 1.)]];
+    assert!(test_render(line_chart(empty)).no_data.is_some());
+    assert!(test_render(line_chart([vec![(1., 2.)]])).no_data.is_none());
+}
+
+#[test]
+fn test_layout_report() {
+    use crate::line::line_chart;
+    let chart = Chart::new();
+    assert!(chart.layout_report().is_none());
+    let chart = test_render(line_chart([vec![(0., 1.), (10., 2.)]]));
+    let report = chart.layout_report().unwrap();
+    assert!(report.fits);
+    assert!(report.iterations >= 1);
+    assert_eq!(report.overflow, Size::ZERO);
+    assert_eq!(report.chart_area, chart.chart_area.unwrap());
+    assert!(report.left_axis.is_some() && report.bottom_axis.is_some());
+    assert!(report.top_axis.is_none() && report.right_axis.is_none());
+}
+
+#[test]
+fn test_draw_stale_layout() {
+    use crate::{
+        annotation::Annotation,
+        box_plot::{box_plot, BoxPlotTrace},
+        line::{line_chart, LineTrace},
+        scatter::ScatterTrace,
+    };
+    let stale = |chart: &Chart| {
+        with_test_context(|rc| {
+            assert!(matches!(chart.try_draw(rc), Err(Error::LayoutNotPerformed)));
+            Ok(())
+        })
+    };
+    stale(&line_chart([vec![(0., 1.), (10., 2.)]]));
+
+    let mut chart = test_render(box_plot(vec!["a"], [vec![1., 2., 3.]]));
+    for trace in chart.traces_mut::<BoxPlotTrace>() {
+        trace.set_groups([vec![2., 3., 4.]]);
+    }
+    stale(&chart);
+
+    let mut chart = test_render(line_chart([vec![(0., 1.), (10., 2.)]]));
+    for trace in chart.traces_mut::<LineTrace>() {
+        trace.set_values(vec![(0., 2.), (10., 1.)]);
+    }
+    stale(&chart);
+    let mut chart = test_render(chart);
+    for trace in chart.traces_mut::<LineTrace>() {
+        trace.set_y_interval(Interval::new(0., 5.));
+    }
+    stale(&chart);
+
+    let range = Interval::new(0., 1.);
+    let mut chart =
+        test_render(Chart::new().with_trace(ScatterTrace::new(vec![(0.5, 0.5)], range, range)));
+    for trace in chart.traces_mut::<ScatterTrace>() {
+        trace.set_highlighted([0]);
+    }
+    stale(&chart);
+    let mut chart = test_render(chart);
+    for trace in chart.traces_mut::<ScatterTrace>() {
+        trace.set_x_interval(Interval::new(0., 2.));
+    }
+    stale(&chart);
+
+    let annotations = Annotations::new(Interval::new(0., 10.), Interval::new(1., 2.));
+    let mut chart =
+        test_render(line_chart([vec![(0., 1.), (10., 2.)]]).with_annotations(annotations));
+    chart
+        .annotations_mut()
+        .unwrap()
+        .push(Annotation::hline(1.5));
+    stale(&chart);
+    test_render(chart);
+}
+
+#[test]
+fn test_unclipped_trace() {
+    use crate::scatter::ScatterTrace;
+    let range = Interval::new(0., 1.);
+    let trace = || ScatterTrace::new(vec![(0., 0.), (2., 1.)], range, range);
+    let chart = test_render(
+        Chart::new()
+            .with_trace(trace())
+            .with_unclipped_trace(trace()),
+    );
+    let clipped: Vec<bool> = chart.traces.iter().map(|(_, clipped)| *clipped).collect();
+    assert_eq!(clipped, [true, false]);
+}
+
+#[test]
+fn test_layout_constraints() {
+    use crate::line::line_chart;
+    let chart = || line_chart([vec![(0., 1.), (10., 2.)]]);
+
+    for ratio in [0., -1., f64::NAN, f64::INFINITY] {
+        assert!(matches!(
+            chart().try_set_aspect_ratio(Some(ratio)),
+            Err(Error::InvalidAspectRatio(_))
+        ));
+    }
+
+    let square = test_render(chart().with_aspect_ratio(1.))
+        .chart_area
+        .unwrap();
+    assert!((square.width() - square.height()).abs() < 1e-9);
+    assert!(square.height() > 200.);
+    // The chart is centered horizontally in the leftover space.
+    assert!(square.x0 > 50.);
+
+    let padded = test_render(chart().with_padding(20.)).chart_area.unwrap();
+    assert!(padded.x0 >= 20. && padded.y0 >= 20.);
+    assert!(padded.x1 <= 380. && padded.y1 <= 280.);
+
+    let fixed = test_render(chart().with_plot_size(Size::new(100., 50.)));
+    assert_eq!(fixed.chart_area.unwrap().size(), Size::new(100., 50.));
+    assert!(fixed.layout_report().unwrap().fits);
+    let too_big = test_render(chart().with_plot_size(Size::new(500., 50.)));
+    let report = too_big.layout_report().unwrap();
+    assert!(!report.fits && report.overflow.width > 100.);
+}
+
+#[test]
+fn test_equal_scale() {
+    use crate::{line::LineTrace, ticker::TickerExt};
+    let chart = || {
+        let (x_range, y_range) = (Interval::new(0., 10.), Interval::new(0., 20.));
+        Chart::new()
+            .with_left_axis(y_range.ticker().reverse())
+            .with_bottom_axis(x_range.ticker())
+            .with_trace(LineTrace::new(vec![(0., 0.), (10., 20.)], x_range, y_range))
+    };
+    let scale = |chart: &Chart| {
+        let area = chart.chart_area.unwrap();
+        let x_range = chart
+            .bottom_axis
+            .as_ref()
+            .unwrap()
+            .ticker()
+            .interval()
+            .unwrap();
+        let y_range = chart
+            .left_axis
+            .as_ref()
+            .unwrap()
+            .ticker()
+            .interval()
+            .unwrap();
+        (
+            area.width() / x_range.size(),
+            area.height() / y_range.size(),
+        )
+    };
+
+    let area = test_render(chart().with_equal_scale(EqualScale::AdjustArea));
+    let (x_scale, y_scale) = scale(&area);
+    assert!((x_scale - y_scale).abs() < 1e-9);
+
+    let mut intervals = test_render(chart().with_equal_scale(EqualScale::AdjustIntervals));
+    let (x_scale, y_scale) = scale(&intervals);
+    assert!((x_scale - y_scale).abs() < 1e-9);
+    assert!(intervals.chart_area.unwrap().width() > area.chart_area.unwrap().width());
+    // Laying out again doesn't keep widening the intervals.
+    let before = intervals.bottom_axis.as_ref().unwrap().ticker().interval();
+    intervals = test_render(intervals);
+    assert_eq!(
+        intervals.bottom_axis.as_ref().unwrap().ticker().interval(),
+        before
+    );
+}
+
+#[test]
+fn test_equal_scale_fallback() {
+    use crate::{heatmap::HeatmapTrace, ticker::TickerExt, ColorMap, ColorScale};
+    // Heatmap cells fill the chart area whatever the intervals, so the area is adjusted instead.
+    let (x_range, y_range) = (Interval::new(0., 10.), Interval::new(0., 20.));
+    let scale = ColorScale::new(ColorMap::viridis(), Interval::new(0., 1.));
+    let chart = Chart::new()
+        .with_left_axis(y_range.ticker().reverse())
+        .with_bottom_axis(x_range.ticker())
+        .with_trace(HeatmapTrace::new(1, 1, vec![0.5], scale))
+        .with_equal_scale(EqualScale::AdjustIntervals);
+    let area = test_render(chart).chart_area.unwrap();
+    assert!((area.width() / area.height() - 0.5).abs() < 1e-9);
+}
@@ -0,0 +1,178 @@
+use crate::{Error, Interval};
+use piet_common::Color;
+use std::sync::Arc;
+
+/// A continuous mapping from `0..=1` to colors, made by linearly interpolating between color
+/// stops.
+#[derive(Debug, Clone)]
+pub struct ColorMap {
+    /// `(position, color)` pairs, sorted by position, with positions in `0..=1`.
+    stops: Arc<[(f64, Color)]>,
+}
+
+impl ColorMap {
+    /// Create a color map from a list of `(position, color)` stops.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there are no stops, or if the positions are not sorted and in `0..=1`.
+    pub fn new(stops: impl Into<Arc<[(f64, Color)]>>) -> Self {
+        match Self::try_new(stops) {
+            Ok(map) => map,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Like `new`, but returns `Error::EmptyData` if there are no stops, or
+    /// `Error::InvalidInterval` for the first pair of positions that are out of order (counting
+    /// 0 before the first stop and 1 after the last).
+    pub fn try_new(stops: impl Into<Arc<[(f64, Color)]>>) -> Result<Self, Error> {
+        let stops = stops.into();
+        if stops.is_empty() {
+            return Err(Error::EmptyData);
+        }
+        let positions = stops.iter().map(|(pos, _)| *pos);
+        let mut prev = 0.;
+        for pos in positions.chain([1.]) {
+            if pos.is_nan() || pos < prev {
+                return Err(Error::InvalidInterval {
+                    min: prev,
+                    max: pos,
+                });
+            }
+            prev = pos;
+        }
+        Ok(Self { stops })
+    }
+
+    /// Create a color map from evenly spaced colors.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `colors` is empty.
+    pub fn from_colors(colors: &[Color]) -> Self {
+        let last = colors.len().saturating_sub(1).max(1) as f64;
+        Self::new(
+            colors
+                .iter()
+                .enumerate()
+                .map(|(idx, color)| (idx as f64 / last, color.clone()))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// The perceptually uniform 'viridis' color map from matplotlib.
+    pub fn viridis() -> Self {
+        Self::from_colors(&[
+            Color::rgb8(0x44, 0x01, 0x54),
+            Color::rgb8(0x47, 0x2d, 0x7b),
+            Color::rgb8(0x3b, 0x52, 0x8b),
+            Color::rgb8(0x2c, 0x72, 0x8e),
+            Color::rgb8(0x21, 0x91, 0x8c),
+            Color::rgb8(0x28, 0xae, 0x80),
+            Color::rgb8(0x5e, 0xc9, 0x62),
+            Color::rgb8(0xad, 0xdc, 0x30),
+            Color::rgb8(0xfd, 0xe7, 0x25),
+        ])
+    }
+
+    /// A diverging red-white-blue color map, useful for data centered on 0 (e.g. correlations).
+    pub fn red_blue() -> Self {
+        Self::from_colors(&[
+            Color::rgb8(0xb2, 0x18, 0x2b),
+            Color::rgb8(0xef, 0x8a, 0x62),
+            Color::rgb8(0xf7, 0xf7, 0xf7),
+            Color::rgb8(0x67, 0xa9, 0xcf),
+            Color::rgb8(0x21, 0x66, 0xac),
+        ])
+    }
+
+    /// Shades of grey from white to black.
+    pub fn greys() -> Self {
+        Self::from_colors(&[Color::WHITE, Color::BLACK])
+    }
+
+    /// The color at position `t`.
+    ///
+    /// `t` is clamped to `0..=1`. NaN values are transparent.
+    pub fn color(&self, t: f64) -> Color {
+        if t.is_nan() {
+            return Color::TRANSPARENT;
+        }
+        let t = t.clamp(0., 1.);
+        let next = self.stops.iter().position(|(pos, _)| *pos >= t);
+        match next {
+            Some(0) => self.stops[0].1.clone(),
+            Some(idx) => {
+                let (p0, ref c0) = self.stops[idx - 1];
+                let (p1, ref c1) = self.stops[idx];
+                lerp_color(c0, c1, (t - p0) / (p1 - p0))
+            }
+            None => self.stops[self.stops.len() - 1].1.clone(),
+        }
+    }
+}
+
+impl Default for ColorMap {
+    fn default() -> Self {
+        Self::viridis()
+    }
+}
+
+/// A color map applied to an interval of data values.
+#[derive(Debug, Clone)]
+pub struct ColorScale {
+    /// The colors to use.
+    pub map: ColorMap,
+    /// The values that map to the start and end of the color map.
+    pub range: Interval,
+}
+
+impl ColorScale {
+    pub fn new(map: ColorMap, range: Interval) -> Self {
+        Self { map, range }
+    }
+
+    /// The color for `value`. Values outside `range` get the color at the nearest end.
+    pub fn color(&self, value: f64) -> Color {
+        self.map.color(self.range.t(value))
+    }
+}
+
+/// Linearly interpolate between two colors in RGBA space.
+fn lerp_color(c0: &Color, c1: &Color, t: f64) -> Color {
+    let (r0, g0, b0, a0) = c0.as_rgba();
+    let (r1, g1, b1, a1) = c1.as_rgba();
+    let lerp = |v0: f64, v1: f64| v0 + (v1 - v0) * t;
+    Color::rgba(lerp(r0, r1), lerp(g0, g1), lerp(b0, b1), lerp(a0, a1))
+}
+
+#[test]
+fn test_color_map_interpolates() {
+    let map = ColorMap::greys();
+    assert_eq!(map.color(0.).as_rgba_u32(), Color::WHITE.as_rgba_u32());
+    assert_eq!(map.color(2.).as_rgba_u32(), Color::BLACK.as_rgba_u32());
+    let (r, g, b, _) = map.color(0.5).as_rgba();
+    assert!((r - 0.5).abs() < 1e-2 && r == g && g == b);
+}
+
+#[test]
+fn test_color_map_try_new() {
+    let stops = |positions: &[f64]| -> Vec<(f64, Color)> {
+        positions.iter().map(|&pos| (pos, Color::BLACK)).collect()
+    };
+    assert!(ColorMap::try_new(stops(&[0., 0.5, 0.5, 1.])).is_ok());
+    assert!(matches!(
+        ColorMap::try_new(stops(&[])),
+        Err(Error::EmptyData)
+    ));
+    assert!(matches!(
+        ColorMap::try_new(stops(&[0., 0.6, 0.4])),
+        Err(Error::InvalidInterval { min, max }) if min == 0.6 && max == 0.4
+    ));
+    assert!(matches!(
+        ColorMap::try_new(stops(&[0.5, 1.5])),
+        Err(Error::InvalidInterval { min, max }) if min == 1.5 && max == 1.
+    ));
+    assert!(ColorMap::try_new(stops(&[f64::NAN])).is_err());
+}
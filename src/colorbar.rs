@@ -0,0 +1,194 @@
+use crate::{
+    axis::{Axis, Direction, LabelPosition},
+    prelude::*,
+    theme, ColorScale, Error, Ticker,
+};
+use piet_common::{
+    kurbo::{Affine, Point, Rect, Size},
+    Color, Error as PietError, FixedLinearGradient, GradientStop, Piet, RenderContext,
+};
+
+/// The number of samples of the color map we use to build the gradient.
+const GRADIENT_STOPS: usize = 32;
+
+/// A strip showing the colors of a `ColorScale`, with an axis labelling the values.
+///
+/// A vertical colorbar is drawn to the right of the chart, and a horizontal one below it.
+#[derive(Debug)]
+pub struct Colorbar {
+    scale: ColorScale,
+    direction: Direction,
+    /// How thick the color strip is.
+    pub thickness: f64,
+    /// The axis showing the values for the colors.
+    axis: Axis<Box<dyn Ticker>>,
+}
+
+impl Colorbar {
+    /// A vertical colorbar, with the smallest value at the bottom.
+    pub fn new(scale: ColorScale) -> Self {
+        let ticker = Box::new(scale.range.ticker().reverse()) as Box<dyn Ticker>;
+        Self {
+            axis: Axis::new(Direction::Vertical, LabelPosition::After, ticker),
+            scale,
+            direction: Direction::Vertical,
+            thickness: theme::COLORBAR_THICKNESS,
+        }
+    }
+
+    /// A horizontal colorbar, with the smallest value on the left.
+    pub fn horizontal(scale: ColorScale) -> Self {
+        let ticker = Box::new(scale.range.ticker()) as Box<dyn Ticker>;
+        Self {
+            axis: Axis::new(Direction::Horizontal, LabelPosition::After, ticker),
+            scale,
+            direction: Direction::Horizontal,
+            thickness: theme::COLORBAR_THICKNESS,
+        }
+    }
+
+    pub fn with_thickness(mut self, thickness: f64) -> Self {
+        self.thickness = thickness;
+        self
+    }
+
+    /// The color scale this colorbar shows.
+    pub fn scale(&self) -> &ColorScale {
+        &self.scale
+    }
+
+    /// Whether the colorbar is vertical or horizontal.
+    pub fn direction(&self) -> Direction {
+        self.direction
+    }
+
+    /// Lay out the colorbar for the given length (along the color strip).
+    pub fn layout(&mut self, len: f64, rc: &mut Piet) -> Result<(), PietError> {
+        self.axis.layout(len, rc)
+    }
+
+    /// Like `size`, but returns an error rather than panicking if `layout` hasn't been called.
+    pub fn try_size(&self) -> Result<Size, Error> {
+        self.axis.try_size()?;
+        Ok(self.size())
+    }
+
+    /// The size of the colorbar, including the gap between it and the chart.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `layout` has not been called.
+    pub fn size(&self) -> Size {
+        let axis_size = self.axis.size();
+        let strip = theme::MARGIN + self.thickness;
+        match self.direction {
+            Direction::Vertical => Size::new(strip + axis_size.width, axis_size.height),
+            Direction::Horizontal => Size::new(axis_size.width, strip + axis_size.height),
+        }
+    }
+
+    /// Draw the colorbar at `(0, 0)`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `layout` has not been called.
+    pub fn draw(&self, rc: &mut Piet) {
+        let len = match self.direction {
+            Direction::Vertical => self.axis.size().height,
+            Direction::Horizontal => self.axis.size().width,
+        };
+        let (strip, start, end, axis_offset) = match self.direction {
+            Direction::Vertical => (
+                Rect::new(theme::MARGIN, 0., theme::MARGIN + self.thickness, len),
+                Point::new(0., len),
+                Point::ZERO,
+                (theme::MARGIN + self.thickness, 0.),
+            ),
+            Direction::Horizontal => (
+                Rect::new(0., theme::MARGIN, len, theme::MARGIN + self.thickness),
+                Point::ZERO,
+                Point::new(len, 0.),
+                (0., theme::MARGIN + self.thickness),
+            ),
+        };
+        let stops = (0..GRADIENT_STOPS)
+            .map(|idx| {
+                let t = idx as f64 / (GRADIENT_STOPS - 1) as f64;
+                GradientStop {
+                    pos: t as f32,
+                    color: self.scale.map.color(t),
+                }
+            })
+            .collect();
+        if let Ok(brush) = rc.gradient(FixedLinearGradient { start, end, stops }) {
+            rc.fill(strip, &brush);
+        }
+        rc.stroke(strip, &Color::grey8(80), 1.);
+
+        rc.with_save(|rc| {
+            rc.transform(Affine::translate(axis_offset));
+            self.axis.draw(rc);
+            Ok(())
+        })
+        .unwrap();
+    }
+}
+
+#[test]
+fn test_colorbar_layout() {
+    use crate::{chart::with_test_context, ColorMap, Interval};
+    let scale = ColorScale::new(ColorMap::viridis(), Interval::new(0., 10.));
+    let mut vertical = Colorbar::new(scale.clone()).with_thickness(20.);
+    let mut horizontal = Colorbar::horizontal(scale);
+    with_test_context(|rc| {
+        vertical.layout(200., rc)?;
+        horizontal.layout(300., rc)?;
+        Ok(())
+    });
+    // The strip and gap are added across the axis, and the length runs along it.
+    let size = vertical.size();
+    assert_eq!(size.height, 200.);
+    assert_eq!(size.width, theme::MARGIN + 20. + vertical.axis.size().width);
+    let size = horizontal.size();
+    assert_eq!(size.width, 300.);
+    assert_eq!(
+        size.height,
+        theme::MARGIN + theme::COLORBAR_THICKNESS + horizontal.axis.size().height
+    );
+}
+
+#[test]
+fn test_colorbar_placement() {
+    use crate::{chart::test_render, Chart, ColorMap, Interval};
+    let scale = ColorScale::new(ColorMap::viridis(), Interval::new(0., 10.));
+    let range = Interval::new(0., 1.);
+    for colorbar in [Colorbar::new(scale.clone()), Colorbar::horizontal(scale)] {
+        let direction = colorbar.direction();
+        let chart = test_render(
+            Chart::new()
+                .with_left_axis(range.ticker().reverse())
+                .with_right_axis(range.ticker().reverse())
+                .with_bottom_axis(range.ticker())
+                .with_colorbar(colorbar),
+        );
+        let area = chart.layout_report().unwrap().chart_area;
+        let origin = chart.colorbar_origin(area).unwrap();
+        let report = chart.layout_report().unwrap();
+        match direction {
+            // Right of the right axis, level with the chart area.
+            Direction::Vertical => {
+                let right = report.right_axis.as_ref().unwrap();
+                assert!(right.labels > 0);
+                assert!(origin.x > area.x1);
+                assert_eq!(origin.y, area.y0);
+            }
+            // Below the bottom axis, in line with the chart area.
+            Direction::Horizontal => {
+                assert_eq!(origin.x, area.x0);
+                assert!(origin.y > area.y1);
+            }
+        }
+        // The colorbar fits in the chart.
+        assert!(report.fits);
+    }
+}
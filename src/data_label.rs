@@ -0,0 +1,235 @@
+use crate::{axis::Label, interval::format_value, theme};
+use piet_common::{
+    kurbo::{Point, Rect, Size},
+    Color, Error as PietError, Piet, PietTextLayout, RenderContext, Text, TextAttribute,
+    TextLayout, TextLayoutBuilder,
+};
+use std::{fmt, sync::Arc};
+
+/// Where a data label should be drawn, relative to the end of the bar (or the point) it labels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum LabelPlacement {
+    /// Just inside the end of the bar, or centered on the point.
+    Inside,
+    /// Just past the end of the bar, or next to the point.
+    ///
+    /// If there isn't room outside, the label will be moved inside if it fits there.
+    Outside,
+}
+
+/// Labels showing the value of individual bars or points.
+///
+/// Labels that would overlap a label that has already been placed, or that don't fit (either in
+/// the chart area or, for `LabelPlacement::Inside`, in the bar), are not drawn.
+#[derive(Clone)]
+pub struct DataLabels {
+    /// Where to draw the labels.
+    pub placement: LabelPlacement,
+    /// The font size of the labels.
+    pub font_size: f64,
+    /// The color of the label text.
+    pub color: Color,
+    /// Converts a value to the text of its label.
+    ///
+    /// Defaults to the same formatting as the tick labels on numeric axes.
+    formatter: Arc<dyn Fn(f64) -> String>,
+
+    // Retained
+    /// The labels that we will draw.
+    labels: Vec<Label>,
+}
+
+impl fmt::Debug for DataLabels {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("DataLabels")
+            .field("placement", &self.placement)
+            .field("font_size", &self.font_size)
+            .field("color", &self.color)
+            .field("labels", &self.labels)
+            .finish()
+    }
+}
+
+impl DataLabels {
+    pub fn new() -> Self {
+        Self {
+            placement: LabelPlacement::Outside,
+            font_size: theme::DATA_LABEL_FONT_SIZE,
+            color: theme::DATA_LABEL_COLOR,
+            formatter: Arc::new(format_value),
+            labels: vec![],
+        }
+    }
+
+    pub fn with_placement(mut self, placement: LabelPlacement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    pub fn with_font_size(mut self, font_size: f64) -> Self {
+        self.font_size = font_size;
+        self
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Use a custom function to convert values into label text.
+    pub fn with_formatter(mut self, formatter: impl Fn(f64) -> String + 'static) -> Self {
+        self.formatter = Arc::new(formatter);
+        self
+    }
+
+    /// Format a value using this label's formatter.
+    pub fn format(&self, value: f64) -> String {
+        (self.formatter)(value)
+    }
+
+    /// Remove all placed labels, ready for a new layout.
+    pub(crate) fn clear(&mut self) {
+        self.labels.clear();
+    }
+
+    /// Create the text layout for a label.
+    pub(crate) fn build_layout(
+        &self,
+        text: String,
+        rc: &mut Piet,
+    ) -> Result<PietTextLayout, PietError> {
+        rc.text()
+            .new_text_layout(text)
+            .default_attribute(TextAttribute::FontSize(self.font_size))
+            .default_attribute(TextAttribute::TextColor(self.color.clone()))
+            .build()
+    }
+
+    /// Place a label at the first of `candidates` (top-left positions) where it fits inside
+    /// `bounds` and doesn't overlap any label already placed.
+    ///
+    /// Returns `false` if the label couldn't be placed, in which case it will not be drawn.
+    pub(crate) fn place(
+        &mut self,
+        layout: PietTextLayout,
+        candidates: impl IntoIterator<Item = Point>,
+        bounds: Rect,
+    ) -> bool {
+        let size = layout.size();
+        for pos in candidates {
+            let rect = Rect::from_origin_size(pos, size);
+            if !rect_contains(bounds, rect) {
+                continue;
+            }
+            if self
+                .labels
+                .iter()
+                .any(|label| label.rect().intersect(rect).area() > 0.)
+            {
+                continue;
+            }
+            self.labels.push(Label { pos, layout });
+            return true;
+        }
+        false
+    }
+
+    /// Draw all placed labels.
+    pub(crate) fn draw(&self, rc: &mut Piet) {
+        for label in &self.labels {
+            rc.draw_text(&label.layout, label.pos);
+        }
+    }
+}
+
+impl Default for DataLabels {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Candidate positions for the label of a bar.
+///
+/// `end` is the y coordinate of the value end of the bar, `base` the y coordinate of the
+/// baseline, and `x` the center of the bar.
+pub(crate) fn bar_label_candidates(
+    placement: LabelPlacement,
+    label_size: Size,
+    x: f64,
+    end: f64,
+    base: f64,
+    bar_width: f64,
+) -> Vec<Point> {
+    let gap = theme::MARGIN * 0.5;
+    let x = x - label_size.width * 0.5;
+    // bars going up the screen are for positive values.
+    let upward = end <= base;
+    let outside = if upward {
+        Point::new(x, end - gap - label_size.height)
+    } else {
+        Point::new(x, end + gap)
+    };
+    let inside = if upward {
+        Point::new(x, end + gap)
+    } else {
+        Point::new(x, end - gap - label_size.height)
+    };
+    let fits_inside =
+        label_size.width <= bar_width && label_size.height + 2. * gap <= (base - end).abs();
+
+    let mut candidates = vec![];
+    if placement == LabelPlacement::Outside {
+        candidates.push(outside);
+    }
+    if fits_inside {
+        candidates.push(inside);
+    }
+    candidates
+}
+
+/// Candidate positions for the label of a point at `center` with radius `radius`.
+pub(crate) fn point_label_candidates(
+    placement: LabelPlacement,
+    label_size: Size,
+    center: Point,
+    radius: f64,
+) -> Vec<Point> {
+    match placement {
+        LabelPlacement::Inside => vec![Point::new(
+            center.x - label_size.width * 0.5,
+            center.y - label_size.height * 0.5,
+        )],
+        LabelPlacement::Outside => {
+            let gap = radius + theme::MARGIN * 0.5;
+            let (left, right) = (center.x - gap - label_size.width, center.x + gap);
+            let (above, below) = (center.y - gap - label_size.height, center.y + gap);
+            vec![
+                Point::new(right, above),
+                Point::new(left, above),
+                Point::new(right, below),
+                Point::new(left, below),
+            ]
+        }
+    }
+}
+
+/// Whether `inner` is completely inside `outer`.
+fn rect_contains(outer: Rect, inner: Rect) -> bool {
+    inner.x0 >= outer.x0 && inner.x1 <= outer.x1 && inner.y0 >= outer.y0 && inner.y1 <= outer.y1
+}
+
+#[test]
+fn test_bar_label_candidates() {
+    let label = Size::new(10., 10.);
+    // A tall positive bar: outside label above the bar, falls back to inside.
+    let candidates = bar_label_candidates(LabelPlacement::Outside, label, 50., 20., 100., 20.);
+    assert_eq!(candidates.len(), 2);
+    assert!(candidates[0].y + label.height < 20.);
+    assert!(candidates[1].y > 20.);
+    // A bar too short for the label to fit inside.
+    let candidates = bar_label_candidates(LabelPlacement::Inside, label, 50., 95., 100., 20.);
+    assert!(candidates.is_empty());
+    // A negative bar: the outside label goes below the end of the bar.
+    let candidates = bar_label_candidates(LabelPlacement::Outside, label, 50., 150., 100., 20.);
+    assert!(candidates[0].y > 150.);
+}
@@ -0,0 +1,452 @@
+//! Traces that summarise large numbers of `(x, y)` points by counting the points in each cell of
+//! a grid, and coloring the cells by count.
+use crate::{prelude::*, Chart, ColorMap, ColorScale, Colorbar, Error, Interval, Trace};
+use piet_common::{
+    kurbo::{BezPath, Point, Rect, Size},
+    Error as PietError, Piet, RenderContext,
+};
+use std::{any::Any, sync::Arc};
+
+/// Create a 2D histogram of `values`, with `bins.0` columns and `bins.1` rows of cells.
+///
+/// The axes are the same as those `Scatter` would use for the same data.
+pub fn histogram_2d(values: impl Into<Arc<[(f64, f64)]>>, bins: (usize, usize)) -> Chart {
+    let values = values.into();
+    let (x_range, y_range) = rounded_intervals(&values);
+    let trace = Histogram2dTrace::new(values, x_range, y_range, bins);
+    let colorbar = Colorbar::new(trace.scale().clone());
+    density_chart(x_range, y_range, colorbar).with_trace(trace)
+}
+
+/// Create a hexagonal binning plot of `values`, with `gridsize` hexagons across the x axis.
+///
+/// The axes are the same as those `Scatter` would use for the same data.
+pub fn hexbin(values: impl Into<Arc<[(f64, f64)]>>, gridsize: usize) -> Chart {
+    let values = values.into();
+    let (x_range, y_range) = rounded_intervals(&values);
+    let trace = HexbinTrace::new(values, x_range, y_range, gridsize);
+    let colorbar = Colorbar::new(trace.scale().clone());
+    density_chart(x_range, y_range, colorbar).with_trace(trace)
+}
+
+fn rounded_intervals(values: &[(f64, f64)]) -> (Interval, Interval) {
+    let (x_range, y_range): (Interval, Interval) = values.iter().copied().unzip();
+    (x_range.to_rounded(), y_range.to_rounded())
+}
+
+fn density_chart(x_range: Interval, y_range: Interval, colorbar: Colorbar) -> Chart {
+    Chart::new()
+        .with_left_axis(y_range.ticker().reverse())
+        .with_bottom_axis(x_range.ticker())
+        .with_colorbar(colorbar)
+}
+
+/// The color scale for a set of counts, from 0 to the largest count.
+fn count_scale(map: ColorMap, counts: &[u32]) -> ColorScale {
+    let max = counts.iter().copied().max().unwrap_or(0).max(1);
+    ColorScale::new(map, Interval::new(0., max as f64))
+}
+
+/// Counts points in a rectangular grid of cells.
+pub struct Histogram2dTrace {
+    /// The points being counted.
+    ///
+    /// Not public because we have retained state that depends on them.
+    values: Arc<[(f64, f64)]>,
+    x_range: Interval,
+    y_range: Interval,
+    /// The number of (columns, rows) of cells.
+    bins: (usize, usize),
+    map: ColorMap,
+
+    // Retained
+    /// The number of points in each cell, row-major with the row for the smallest y first.
+    counts: Vec<u32>,
+    scale: ColorScale,
+    /// The size of the chart area.
+    pub size: Option<Size>,
+}
+
+impl Histogram2dTrace {
+    /// A 2D histogram trace.
+    ///
+    /// Points outside `x_range` or `y_range`, or containing NaN, are not counted.
+    pub fn new(
+        values: impl Into<Arc<[(f64, f64)]>>,
+        x_range: Interval,
+        y_range: Interval,
+        bins: (usize, usize),
+    ) -> Self {
+        let mut trace = Self {
+            values: values.into(),
+            x_range,
+            y_range,
+            bins: (bins.0.max(1), bins.1.max(1)),
+            map: ColorMap::viridis(),
+            counts: vec![],
+            scale: count_scale(ColorMap::viridis(), &[]),
+            size: None,
+        };
+        trace.count();
+        trace
+    }
+
+    pub fn with_color_map(mut self, map: ColorMap) -> Self {
+        self.map = map;
+        self.count();
+        self
+    }
+
+    pub fn values(&self) -> &[(f64, f64)] {
+        &self.values
+    }
+
+    pub fn set_values(&mut self, new_values: impl Into<Arc<[(f64, f64)]>>) {
+        self.values = new_values.into();
+        self.count();
+        self.size = None;
+    }
+
+    /// The number of points in each cell, row-major with the row for the smallest y first.
+    pub fn counts(&self) -> &[u32] {
+        &self.counts
+    }
+
+    /// The color scale for the counts.
+    pub fn scale(&self) -> &ColorScale {
+        &self.scale
+    }
+
+    fn count(&mut self) {
+        let (cols, rows) = self.bins;
+        self.counts.clear();
+        self.counts.resize(cols * rows, 0);
+        for &(x, y) in self.values.iter() {
+            let (tx, ty) = (self.x_range.t(x), self.y_range.t(y));
+            if !(0. ..=1.).contains(&tx) || !(0. ..=1.).contains(&ty) {
+                // also skips NaN
+                continue;
+            }
+            // Values at the max go in the last cell.
+            let col = ((tx * cols as f64) as usize).min(cols - 1);
+            let row = ((ty * rows as f64) as usize).min(rows - 1);
+            self.counts[row * cols + col] += 1;
+        }
+        self.scale = count_scale(self.map.clone(), &self.counts);
+    }
+}
+
+impl Trace for Histogram2dTrace {
+    fn layout(&mut self, size: Size, _rc: &mut Piet) -> Result<(), PietError> {
+        self.size = Some(size);
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        self.size.unwrap()
+    }
+
+    fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
+    fn draw(&self, rc: &mut Piet) {
+        let size = self.size.unwrap();
+        let (cols, rows) = self.bins;
+        let cell_width = size.width / cols as f64;
+        let cell_height = size.height / rows as f64;
+        for (idx, &count) in self.counts.iter().enumerate() {
+            if count == 0 {
+                continue;
+            }
+            let (row, col) = (idx / cols, idx % cols);
+            // The first row is at the bottom.
+            let y1 = size.height - row as f64 * cell_height;
+            let cell = Rect::new(
+                col as f64 * cell_width,
+                y1 - cell_height,
+                (col + 1) as f64 * cell_width,
+                y1,
+            );
+            rc.fill(cell, &self.scale.color(count as f64));
+        }
+    }
+
+    fn set_intervals(&mut self, x_range: Interval, y_range: Interval) {
+        self.x_range = x_range;
+        self.y_range = y_range;
+        self.count();
+        self.size = None;
+    }
+
+    fn has_intervals(&self) -> bool {
+        true
+    }
+
+    fn is_empty(&self) -> bool {
+        self.counts.iter().all(|&count| count == 0)
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Counts points in a grid of hexagons.
+///
+/// The hexagons are regular in data space when `y_range.size() / x_range.size()` is the same as
+/// the aspect ratio of the chart area, and are stretched otherwise.
+pub struct HexbinTrace {
+    /// The points being counted.
+    ///
+    /// Not public because we have retained state that depends on them.
+    values: Arc<[(f64, f64)]>,
+    x_range: Interval,
+    y_range: Interval,
+    /// The number of hexagons across the x axis.
+    gridsize: usize,
+    map: ColorMap,
+
+    // Retained
+    /// The hexagons with at least one point in them, as `(center, count)` with the center in
+    /// data space.
+    cells: Vec<((f64, f64), u32)>,
+    scale: ColorScale,
+    /// The size of the chart area.
+    pub size: Option<Size>,
+}
+
+impl HexbinTrace {
+    /// A hexagonal binning trace.
+    ///
+    /// Points outside `x_range` or `y_range`, or containing NaN, are not counted.
+    pub fn new(
+        values: impl Into<Arc<[(f64, f64)]>>,
+        x_range: Interval,
+        y_range: Interval,
+        gridsize: usize,
+    ) -> Self {
+        let mut trace = Self {
+            values: values.into(),
+            x_range,
+            y_range,
+            gridsize: gridsize.max(1),
+            map: ColorMap::viridis(),
+            cells: vec![],
+            scale: count_scale(ColorMap::viridis(), &[]),
+            size: None,
+        };
+        trace.count();
+        trace
+    }
+
+    pub fn with_color_map(mut self, map: ColorMap) -> Self {
+        self.map = map;
+        self.count();
+        self
+    }
+
+    pub fn values(&self) -> &[(f64, f64)] {
+        &self.values
+    }
+
+    pub fn set_values(&mut self, new_values: impl Into<Arc<[(f64, f64)]>>) {
+        self.values = new_values.into();
+        self.count();
+        self.size = None;
+    }
+
+    /// The color scale for the counts.
+    pub fn scale(&self) -> &ColorScale {
+        &self.scale
+    }
+
+    /// The number of (columns, rows) in the grid of hexagons.
+    ///
+    /// There are 2 interleaved lattices of hexagons, the first with one more column and row than
+    /// the second.
+    fn grid(&self) -> (usize, usize) {
+        let nx = self.gridsize;
+        // choose rows so the hexagons are regular when the data is square.
+        let ny = ((nx as f64) / 3f64.sqrt()).max(1.);
+        (nx, ny as usize)
+    }
+
+    /// The size of a grid cell in data space.
+    fn cell_size(&self) -> (f64, f64) {
+        let (nx, ny) = self.grid();
+        (
+            self.x_range.size() / nx as f64,
+            self.y_range.size() / ny as f64,
+        )
+    }
+
+    fn count(&mut self) {
+        let (nx, ny) = self.grid();
+        let (sx, sy) = self.cell_size();
+        let (nx1, ny1) = (nx + 1, ny + 1);
+        let mut lattice1 = vec![0u32; nx1 * ny1];
+        let mut lattice2 = vec![0u32; nx * ny];
+
+        for &(x, y) in self.values.iter() {
+            let (tx, ty) = (self.x_range.t(x), self.y_range.t(y));
+            if !(0. ..=1.).contains(&tx) || !(0. ..=1.).contains(&ty) {
+                // also skips NaN
+                continue;
+            }
+            // position in units of cells
+            let (x, y) = ((x - self.x_range.min()) / sx, (y - self.y_range.min()) / sy);
+            let (ix1, iy1) = (x.round(), y.round());
+            let (ix2, iy2) = (x.floor(), y.floor());
+            // Find the closest center, scaling y so the hexagons are regular.
+            let d1 = (x - ix1).powi(2) + 3. * (y - iy1).powi(2);
+            let d2 = (x - ix2 - 0.5).powi(2) + 3. * (y - iy2 - 0.5).powi(2);
+            if d1 < d2 {
+                lattice1[ix1 as usize * ny1 + iy1 as usize] += 1;
+            } else if (ix2 as usize) < nx && (iy2 as usize) < ny {
+                lattice2[ix2 as usize * ny + iy2 as usize] += 1;
+            } else {
+                // on the max edge, which always belongs to the first lattice.
+                lattice1[ix1 as usize * ny1 + iy1 as usize] += 1;
+            }
+        }
+
+        let (min_x, min_y) = (self.x_range.min(), self.y_range.min());
+        self.cells.clear();
+        for (idx, &count) in lattice1.iter().enumerate() {
+            if count > 0 {
+                let (ix, iy) = ((idx / ny1) as f64, (idx % ny1) as f64);
+                self.cells.push(((min_x + ix * sx, min_y + iy * sy), count));
+            }
+        }
+        for (idx, &count) in lattice2.iter().enumerate() {
+            if count > 0 {
+                let (ix, iy) = ((idx / ny) as f64 + 0.5, (idx % ny) as f64 + 0.5);
+                self.cells.push(((min_x + ix * sx, min_y + iy * sy), count));
+            }
+        }
+        let counts: Vec<u32> = self.cells.iter().map(|(_, count)| *count).collect();
+        self.scale = count_scale(self.map.clone(), &counts);
+    }
+}
+
+impl Trace for HexbinTrace {
+    fn layout(&mut self, size: Size, _rc: &mut Piet) -> Result<(), PietError> {
+        self.size = Some(size);
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        self.size.unwrap()
+    }
+
+    fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
+    fn draw(&self, rc: &mut Piet) {
+        let size = self.size.unwrap();
+        let (sx, sy) = self.cell_size();
+        // cell size in pixels
+        let (px, py) = (
+            sx / self.x_range.size() * size.width,
+            sy / self.y_range.size() * size.height,
+        );
+        // hexagon vertex offsets, in units of (px, py / 3)
+        const OFFSETS: [(f64, f64); 6] = [
+            (0.5, -0.5),
+            (0.5, 0.5),
+            (0., 1.),
+            (-0.5, 0.5),
+            (-0.5, -0.5),
+            (0., -1.),
+        ];
+        for &((x, y), count) in &self.cells {
+            let center = Point::new(
+                self.x_range.t(x) * size.width,
+                (1. - self.y_range.t(y)) * size.height,
+            );
+            let mut hexagon = BezPath::new();
+            for (idx, (dx, dy)) in OFFSETS.iter().enumerate() {
+                let vertex = (center.x + dx * px, center.y + dy * py / 3.);
+                if idx == 0 {
+                    hexagon.move_to(vertex);
+                } else {
+                    hexagon.line_to(vertex);
+                }
+            }
+            hexagon.close_path();
+            rc.fill(hexagon, &self.scale.color(count as f64));
+        }
+    }
+
+    fn set_intervals(&mut self, x_range: Interval, y_range: Interval) {
+        self.x_range = x_range;
+        self.y_range = y_range;
+        self.count();
+        self.size = None;
+    }
+
+    fn has_intervals(&self) -> bool {
+        true
+    }
+
+    fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[test]
+fn test_histogram_2d_counts() {
+    let values = vec![
+        (0.1, 0.1),
+        (0.2, 0.2),
+        (0.9, 0.9),
+        (1., 1.),
+        (f64::NAN, 0.5),
+        (2., 0.),
+    ];
+    let trace = Histogram2dTrace::new(values, Interval::new(0., 1.), Interval::new(0., 1.), (2, 2));
+    assert_eq!(trace.counts(), &[2, 0, 0, 2]);
+}
+
+#[test]
+fn test_hexbin_counts_every_point() {
+    let values: Vec<_> = (0..=100)
+        .map(|i| (i as f64 / 100., (i * 37 % 101) as f64 / 100.))
+        .collect();
+    let trace = HexbinTrace::new(values, Interval::new(0., 1.), Interval::new(0., 1.), 7);
+    let total: u32 = trace.cells.iter().map(|(_, count)| count).sum();
+    assert_eq!(total, 101);
+}
+
+#[test]
+fn test_density_degenerate_data() {
+    use crate::chart::test_degenerate;
+    test_degenerate(histogram_2d(vec![], (4, 4)), true);
+    test_degenerate(hexbin(vec![], 4), true);
+    for values in [vec![(1., 2.)], vec![(1., 2.), (1., 2.)]] {
+        for chart in [histogram_2d(values.clone(), (4, 4)), hexbin(values, 4)] {
+            let (x_range, y_range) = test_degenerate(chart, false);
+            assert!(x_range.unwrap().contains(1.) && y_range.unwrap().contains(2.));
+        }
+    }
+}
+
+#[test]
+fn test_density_equal_scale() {
+    use crate::{chart::test_render, EqualScale};
+    let values = vec![(0., 0.), (10., 20.), (5., 10.)];
+    let mut chart =
+        test_render(histogram_2d(values, (4, 4)).with_equal_scale(EqualScale::AdjustIntervals));
+    let area = chart.layout_report().unwrap().chart_area;
+    let trace = chart.traces_mut::<Histogram2dTrace>().next().unwrap();
+    let (x_range, y_range) = (trace.x_range, trace.y_range);
+    assert!((area.width() / x_range.size() - area.height() / y_range.size()).abs() < 1e-9);
+    // The cells are recounted over the new intervals.
+    assert_eq!(trace.counts().iter().sum::<u32>(), 3);
+}
@@ -0,0 +1,96 @@
+//! Empirical cumulative distribution functions.
+use crate::{
+    line::{Interpolation, LineTrace},
+    prelude::*,
+    stats::sorted_present,
+    theme, Chart, GridStyle, Interval,
+};
+
+/// Create a chart with the empirical CDF of each sample, for comparing their distributions.
+///
+/// Each sample gets the next color from the theme. NaN values are skipped.
+pub fn ecdf_chart<S>(samples: impl IntoIterator<Item = S>) -> Chart
+where
+    S: AsRef<[f64]>,
+{
+    let samples: Vec<Vec<(f64, f64)>> = samples
+        .into_iter()
+        .map(|values| ecdf(values.as_ref()))
+        .collect();
+    let x_range = samples
+        .iter()
+        .flat_map(|steps| steps.iter().map(|(x, _)| *x))
+        .collect::<Interval>()
+        .to_rounded();
+    let y_range = Interval::new(0., 1.);
+    let mut chart = Chart::new()
+        .with_left_axis(y_range.ticker().reverse())
+        .with_left_grid(GridStyle::default())
+        .with_bottom_axis(x_range.ticker())
+        .with_bottom_grid(GridStyle::default());
+    for (idx, steps) in samples.iter().enumerate() {
+        chart = chart
+            .with_trace(ecdf_trace_from_steps(steps, x_range).with_color(theme::series_color(idx)));
+    }
+    chart
+}
+
+/// The empirical CDF of some values: the fraction of values less than or equal to each distinct
+/// value, as `(value, fraction)` pairs sorted by value.
+///
+/// NaN values are skipped.
+pub fn ecdf(values: &[f64]) -> Vec<(f64, f64)> {
+    let sorted = sorted_present(values);
+    let n = sorted.len() as f64;
+    let mut out: Vec<(f64, f64)> = vec![];
+    for (idx, &value) in sorted.iter().enumerate() {
+        let fraction = (idx + 1) as f64 / n;
+        match out.last_mut() {
+            // Repeated values are a single, taller step.
+            Some(last) if last.0 == value => last.1 = fraction,
+            _ => out.push((value, fraction)),
+        }
+    }
+    out
+}
+
+/// A step line showing the empirical CDF of `values` over `x_range`, with y from 0 to 1.
+///
+/// The line is flat at 0 from the start of `x_range` to the smallest value, and flat at 1 from
+/// the largest value to the end of `x_range`. If there are no (non-missing) values there is no
+/// line.
+pub fn ecdf_trace(values: &[f64], x_range: Interval) -> LineTrace {
+    ecdf_trace_from_steps(&ecdf(values), x_range)
+}
+
+fn ecdf_trace_from_steps(steps: &[(f64, f64)], x_range: Interval) -> LineTrace {
+    if steps.is_empty() {
+        return LineTrace::new(vec![], x_range, Interval::new(0., 1.));
+    }
+    let mut points = Vec::with_capacity(steps.len() + 2);
+    points.push((x_range.min(), 0.));
+    points.extend_from_slice(steps);
+    points.push((x_range.max(), 1.));
+    LineTrace::new(points, x_range, Interval::new(0., 1.))
+        .with_interpolation(Interpolation::StepAfter)
+}
+
+#[test]
+fn test_ecdf() {
+    assert_eq!(
+        ecdf(&[3., 1., f64::NAN, 3., 2.]),
+        vec![(1., 0.25), (2., 0.5), (3., 1.)]
+    );
+    assert!(ecdf(&[]).is_empty());
+}
+
+#[test]
+fn test_ecdf_chart_degenerate_data() {
+    use crate::chart::test_degenerate;
+    test_degenerate(ecdf_chart(Vec::<Vec<f64>>::new()), true);
+    test_degenerate(ecdf_chart([vec![]]), true);
+    let (x_range, _) = test_degenerate(ecdf_chart([vec![1.]]), false);
+    assert!(x_range.unwrap().contains(1.));
+    let (x_range, _) = test_degenerate(ecdf_chart([vec![2., 2.]]), false);
+    assert!(x_range.unwrap().contains(2.));
+}
@@ -0,0 +1,96 @@
+use crate::ColumnType;
+use piet_common::Error as PietError;
+use std::fmt;
+
+/// Errors that can occur when building, laying out or drawing a chart.
+#[derive(Debug)]
+pub enum Error {
+    /// An interval that doesn't satisfy `-∞ < min < max < ∞`.
+    InvalidInterval { min: f64, max: f64 },
+    /// A step size that doesn't satisfy `0 < step < ∞`.
+    InvalidStep(f64),
+    /// An aspect ratio that doesn't satisfy `0 < ratio < ∞`.
+    InvalidAspectRatio(f64),
+    /// There was no (non-missing) data where some was needed.
+    EmptyData,
+    /// Something was used before `layout` was called on it.
+    LayoutNotPerformed,
+    /// Two things that should be the same length were not.
+    MismatchedLengths { expected: usize, found: usize },
+    /// There is no column with this name in a table.
+    UnknownColumn(String),
+    /// A column of a table doesn't have the type of values that was needed.
+    WrongColumnType {
+        column: String,
+        expected: ColumnType,
+        found: ColumnType,
+    },
+    /// A chart spec that can't be built, e.g. because a trace is missing a column it needs.
+    InvalidSpec(String),
+    /// An error from the piet render context.
+    Piet(PietError),
+    /// An error reading CSV data.
+    #[cfg(feature = "csv")]
+    Csv(csv::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::InvalidInterval { min, max } => {
+                write!(
+                    f,
+                    "invalid interval: -∞ < {} < {} < ∞ is not true",
+                    min, max
+                )
+            }
+            Error::InvalidStep(step) => write!(f, "invalid step: 0 < {} < ∞ is not true", step),
+            Error::InvalidAspectRatio(ratio) => {
+                write!(f, "invalid aspect ratio: 0 < {} < ∞ is not true", ratio)
+            }
+            Error::EmptyData => f.write_str("no data"),
+            Error::LayoutNotPerformed => f.write_str("layout not called"),
+            Error::MismatchedLengths { expected, found } => {
+                write!(f, "expected {} values, found {}", expected, found)
+            }
+            Error::UnknownColumn(name) => write!(f, "no column called \"{}\"", name),
+            Error::WrongColumnType {
+                column,
+                expected,
+                found,
+            } => write!(
+                f,
+                "column \"{}\" should contain {} values, but contains {} values",
+                column, expected, found
+            ),
+            Error::InvalidSpec(msg) => write!(f, "invalid chart spec: {}", msg),
+            Error::Piet(e) => write!(f, "piet error: {}", e),
+            #[cfg(feature = "csv")]
+            Error::Csv(e) => write!(f, "csv error: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Piet(e) => Some(e),
+            #[cfg(feature = "csv")]
+            Error::Csv(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<PietError> for Error {
+    fn from(e: PietError) -> Self {
+        Error::Piet(e)
+    }
+}
+
+#[cfg(feature = "csv")]
+impl From<csv::Error> for Error {
+    fn from(e: csv::Error) -> Self {
+        Error::Csv(e)
+    }
+}
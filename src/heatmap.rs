@@ -0,0 +1,344 @@
+use crate::{
+    missing::is_missing, prelude::*, Chart, ColorMap, ColorScale, Colorbar, DataLabels, Error,
+    Interval, LabelPlacement, NoDataHatch, Sequence, Trace,
+};
+use piet_common::{
+    kurbo::{Point, Rect, Size},
+    Error as PietError, Piet, RenderContext, TextLayout,
+};
+use std::{any::Any, fmt, sync::Arc};
+
+/// Create a heatmap of `values`, a row-major matrix with a row for each item in `rows` and a
+/// column for each item in `columns`.
+///
+/// The first row is drawn at the top. A colorbar showing the color scale is included.
+///
+/// # Panics
+///
+/// Panics if `values.len() != rows.len() * columns.len()`.
+pub fn heatmap<R, C>(rows: R, columns: C, values: impl Into<Arc<[f64]>>) -> Chart
+where
+    R: Sequence + 'static,
+    R::Item: fmt::Display,
+    C: Sequence + 'static,
+    C::Item: fmt::Display,
+{
+    let values = values.into();
+    let range = Interval::from_iter(values.iter().copied());
+    heatmap_with_scale(
+        rows,
+        columns,
+        values,
+        ColorScale::new(ColorMap::viridis(), range.to_nonempty()),
+    )
+}
+
+/// Like `heatmap`, but with the color scale given explicitly.
+pub fn heatmap_with_scale<R, C>(
+    rows: R,
+    columns: C,
+    values: impl Into<Arc<[f64]>>,
+    scale: ColorScale,
+) -> Chart
+where
+    R: Sequence + 'static,
+    R::Item: fmt::Display,
+    C: Sequence + 'static,
+    C::Item: fmt::Display,
+{
+    let trace = HeatmapTrace::new(rows.len(), columns.len(), values, scale.clone());
+    Chart::new()
+        .with_left_axis(rows.space_around_ticker())
+        .with_bottom_axis(columns.space_around_ticker())
+        .with_colorbar(Colorbar::new(scale))
+        .with_trace(trace)
+}
+
+/// Create a heatmap of the correlations between the given columns of data.
+///
+/// Each column is a `(name, values)` pair. Cells are annotated with the correlation coefficient.
+pub fn correlation_heatmap<L>(columns: &[(L, &[f64])]) -> Chart
+where
+    L: fmt::Display + fmt::Debug + Clone + 'static,
+{
+    let names: Vec<L> = columns.iter().map(|(name, _)| name.clone()).collect();
+    let data: Vec<&[f64]> = columns.iter().map(|(_, values)| *values).collect();
+    let values = correlation_matrix(&data);
+    let scale = ColorScale::new(ColorMap::red_blue(), Interval::new(-1., 1.));
+    let trace = HeatmapTrace::new(names.len(), names.len(), values, scale.clone())
+        .with_labels(DataLabels::new().with_formatter(|v| format!("{:.2}", v)));
+    let names = crate::Categorical::new(names);
+    Chart::new()
+        .with_left_axis(names.clone().space_around_ticker())
+        .with_bottom_axis(names.space_around_ticker())
+        .with_colorbar(Colorbar::new(scale))
+        .with_trace(trace)
+}
+
+/// The row-major matrix of Pearson correlation coefficients between each pair of `columns`.
+///
+/// Rows where either value is NaN are skipped for that pair. If there isn't enough data, or one of
+/// the columns is constant, the coefficient is NaN.
+pub fn correlation_matrix(columns: &[&[f64]]) -> Vec<f64> {
+    let n = columns.len();
+    let mut out = vec![f64::NAN; n * n];
+    for i in 0..n {
+        for j in i..n {
+            let r = pearson(columns[i], columns[j]);
+            out[i * n + j] = r;
+            out[j * n + i] = r;
+        }
+    }
+    out
+}
+
+/// Pearson's correlation coefficient, skipping pairs containing NaN.
+fn pearson(xs: &[f64], ys: &[f64]) -> f64 {
+    let pairs = || {
+        xs.iter()
+            .copied()
+            .zip(ys.iter().copied())
+            .filter(|(x, y)| !x.is_nan() && !y.is_nan())
+    };
+    let count = pairs().count() as f64;
+    if count < 2. {
+        return f64::NAN;
+    }
+    let (sum_x, sum_y) = pairs().fold((0., 0.), |(sx, sy), (x, y)| (sx + x, sy + y));
+    let (mean_x, mean_y) = (sum_x / count, sum_y / count);
+    let (mut cov, mut var_x, mut var_y) = (0., 0., 0.);
+    for (x, y) in pairs() {
+        let (dx, dy) = (x - mean_x, y - mean_y);
+        cov += dx * dy;
+        var_x += dx * dx;
+        var_y += dy * dy;
+    }
+    if var_x == 0. || var_y == 0. {
+        return f64::NAN;
+    }
+    cov / (var_x * var_y).sqrt()
+}
+
+/// Draws a grid of cells colored by value.
+///
+/// NaN values are missing, and their cells are left blank.
+pub struct HeatmapTrace {
+    /// The values of the cells, row-major.
+    ///
+    /// Not public because we have retained state that depends on them.
+    values: Arc<[f64]>,
+    rows: usize,
+    columns: usize,
+    /// How to convert values into colors.
+    scale: ColorScale,
+    /// The gap between cells.
+    pub cell_gap: f64,
+    /// Optional text showing the value of each cell.
+    labels: Option<DataLabels>,
+    /// If set, cells with missing values are hatched rather than left blank.
+    pub no_data: Option<NoDataHatch>,
+
+    // Retained
+    /// The size of the chart area.
+    pub size: Option<Size>,
+}
+
+impl HeatmapTrace {
+    /// A heatmap trace.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `values.len() != rows * columns`.
+    pub fn new(
+        rows: usize,
+        columns: usize,
+        values: impl Into<Arc<[f64]>>,
+        scale: ColorScale,
+    ) -> Self {
+        match Self::try_new(rows, columns, values, scale) {
+            Ok(trace) => trace,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// A heatmap trace, or an error if `values.len() != rows * columns`.
+    pub fn try_new(
+        rows: usize,
+        columns: usize,
+        values: impl Into<Arc<[f64]>>,
+        scale: ColorScale,
+    ) -> Result<Self, Error> {
+        let values = values.into();
+        if values.len() != rows * columns {
+            return Err(Error::MismatchedLengths {
+                expected: rows * columns,
+                found: values.len(),
+            });
+        }
+        Ok(Self {
+            values,
+            rows,
+            columns,
+            scale,
+            cell_gap: 1.,
+            labels: None,
+            no_data: None,
+            size: None,
+        })
+    }
+
+    /// Write the value of each cell in the cell.
+    ///
+    /// Labels are always centered in their cell, and are left out if they don't fit.
+    pub fn with_labels(mut self, labels: DataLabels) -> Self {
+        self.labels = Some(labels.with_placement(LabelPlacement::Inside));
+        self
+    }
+
+    /// Hatch cells with missing values.
+    pub fn with_no_data(mut self, hatch: NoDataHatch) -> Self {
+        self.no_data = Some(hatch);
+        self
+    }
+
+    /// The values of the cells, row-major.
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    /// The value of the cell at `(row, column)`.
+    pub fn get(&self, row: usize, column: usize) -> Option<f64> {
+        if column >= self.columns {
+            return None;
+        }
+        self.values.get(row * self.columns + column).copied()
+    }
+
+    pub fn scale(&self) -> &ColorScale {
+        &self.scale
+    }
+
+    /// The area of the cell at `(row, column)`, before removing the gap.
+    fn cell_rect(&self, row: usize, column: usize, size: Size) -> Rect {
+        let cell_width = size.width / self.columns as f64;
+        let cell_height = size.height / self.rows as f64;
+        Rect::from_origin_size(
+            Point::new(column as f64 * cell_width, row as f64 * cell_height),
+            Size::new(cell_width, cell_height),
+        )
+    }
+
+    fn cells(&self) -> impl Iterator<Item = (usize, usize, f64)> + '_ {
+        let columns = self.columns;
+        self.values
+            .iter()
+            .enumerate()
+            .map(move |(idx, &value)| (idx / columns, idx % columns, value))
+    }
+}
+
+impl Trace for HeatmapTrace {
+    fn layout(&mut self, size: Size, rc: &mut Piet) -> Result<(), PietError> {
+        if self.size == Some(size) {
+            return Ok(());
+        }
+        self.size = Some(size);
+        // Missing values are left blank.
+        let cells: Vec<(Rect, f64)> = self
+            .cells()
+            .filter(|(_, _, value)| !is_missing(*value))
+            .map(|(row, column, value)| (self.cell_rect(row, column, size), value))
+            .collect();
+        let labels = match &mut self.labels {
+            Some(labels) => labels,
+            None => return Ok(()),
+        };
+        labels.clear();
+        for (cell, value) in cells {
+            let layout = labels.build_layout(labels.format(value), rc)?;
+            let label_size = layout.size();
+            let pos = Point::new(
+                cell.center().x - label_size.width * 0.5,
+                cell.center().y - label_size.height * 0.5,
+            );
+            labels.place(layout, [pos], cell);
+        }
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        self.size.unwrap()
+    }
+
+    fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
+    fn draw(&self, rc: &mut Piet) {
+        let size = self.size.unwrap();
+        let inset = self.cell_gap * 0.5;
+        for (row, column, value) in self.cells() {
+            let cell = self.cell_rect(row, column, size).inset(-inset);
+            if !is_missing(value) {
+                rc.fill(cell, &self.scale.color(value));
+            } else if let Some(hatch) = &self.no_data {
+                hatch.draw(cell, rc);
+            }
+        }
+        if let Some(labels) = &self.labels {
+            labels.draw(rc);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.values.iter().all(|&v| is_missing(v))
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[test]
+fn test_heatmap_missing_cells() {
+    let scale = ColorScale::new(ColorMap::viridis(), Interval::new(0., 1.));
+    let trace = HeatmapTrace::new(2, 2, vec![0., f64::NAN, 1., 0.5], scale);
+    let missing: Vec<_> = trace
+        .cells()
+        .filter(|(_, _, value)| is_missing(*value))
+        .map(|(row, column, _)| (row, column))
+        .collect();
+    assert_eq!(missing, vec![(0, 1)]);
+    assert_eq!(
+        trace.cell_rect(0, 1, Size::new(10., 10.)),
+        Rect::new(5., 0., 10., 5.)
+    );
+}
+
+#[test]
+fn test_correlation_matrix() {
+    let a = [1., 2., 3., 4.];
+    let b = [2., 4., 6., f64::NAN];
+    let c = [4., 3., 2., 1.];
+    let m = correlation_matrix(&[&a, &b, &c]);
+    assert!((m[0] - 1.).abs() < 1e-12);
+    assert!((m[1] - 1.).abs() < 1e-12);
+    assert!((m[2] + 1.).abs() < 1e-12);
+    assert_eq!(m[1], m[3]);
+}
+
+#[test]
+fn test_heatmap_degenerate_data() {
+    use crate::{chart::test_degenerate, Categorical};
+    let labels = |names: &[&'static str]| Categorical::new(names.to_vec());
+    test_degenerate(heatmap(labels(&[]), labels(&[]), vec![]), true);
+    test_degenerate(heatmap(labels(&["a"]), labels(&["x"]), vec![1.]), false);
+    test_degenerate(
+        heatmap(labels(&["a"]), labels(&["x", "y"]), vec![2., 2.]),
+        false,
+    );
+    test_degenerate(
+        heatmap(labels(&["a"]), labels(&["x"]), vec![f64::NAN]),
+        true,
+    );
+}
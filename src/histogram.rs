@@ -1,8 +1,11 @@
-use crate::{prelude::*, theme, Categorical, Chart, GridStyle, Interval, Trace};
+use crate::{
+    data_label::bar_label_candidates, missing::is_missing, prelude::*, theme, Categorical, Chart,
+    DataLabels, Error, GridStyle, Interval, NoDataHatch, Table, Trace,
+};
 use itertools::izip;
 use piet_common::{
     kurbo::{Rect, Size},
-    Color, Error as PietError, Piet, RenderContext,
+    Color, Error as PietError, Piet, RenderContext, TextLayout,
 };
 use std::{any::Any, f64::consts::FRAC_2_PI, fmt, sync::Arc};
 
@@ -22,6 +25,15 @@ where
         .with_bottom_axis(labels.space_around_ticker())
         .with_trace(bars_trace)
 }
+
+/// Create a histogram of how many times each value appears in column `column` of `table`.
+///
+/// See `Table::value_counts` for the order of the bars.
+pub fn histogram_from(table: &Table, column: &str) -> Result<Chart, Error> {
+    let (labels, counts) = table.value_counts(column)?;
+    Ok(histogram(labels, counts))
+}
+
 /// Create a histogram from `(label, frequency)` pairs.
 pub fn histogram_from_pairs<L>(data: impl IntoIterator<Item = (L, f64)>) -> Chart
 where
@@ -31,7 +43,18 @@ where
     histogram(labels, values)
 }
 
+/// Choose a bar width for bars with centers `bar_gap` apart.
+///
+/// Narrow bars take up most of the gap, wide bars proportionally less.
+pub(crate) fn auto_bar_width(bar_gap: f64) -> f64 {
+    const SCALE_F: f64 = 0.004;
+    let bar_factor = 1. - ((SCALE_F * bar_gap).atan() * FRAC_2_PI);
+    bar_factor * bar_gap
+}
+
 /// How to draw the bars of the histogram.
+///
+/// NaN values are missing: no bar is drawn for them.
 pub struct HistogramTrace {
     /// The values of the bars.
     ///
@@ -45,6 +68,10 @@ pub struct HistogramTrace {
     ///
     /// The maximum value in `values` would be a sensible choice.
     y_range: Option<Interval>,
+    /// Optional labels showing the value of each bar.
+    labels: Option<DataLabels>,
+    /// If set, the space for bars with missing values is hatched.
+    pub no_data: Option<NoDataHatch>,
 
     // Retained
     /// The size of the chart area.
@@ -68,6 +95,8 @@ impl HistogramTrace {
             bar_color: theme::BAR_COLOR,
             values,
             y_range: None,
+            labels: None,
+            no_data: None,
             size: None,
             positions: None,
         }
@@ -81,6 +110,27 @@ impl HistogramTrace {
         self
     }
 
+    /// Show the value of each bar in a label at the end of the bar.
+    pub fn with_labels(mut self, labels: DataLabels) -> Self {
+        self.labels = Some(labels);
+        self
+    }
+
+    /// Show the value of each bar in a label at the end of the bar.
+    ///
+    /// Call `layout` after changing this.
+    pub fn set_labels(&mut self, labels: Option<DataLabels>) -> &mut Self {
+        self.labels = labels;
+        self.size = None;
+        self
+    }
+
+    /// Hatch the space where bars with missing values would be.
+    pub fn with_no_data(mut self, hatch: NoDataHatch) -> Self {
+        self.no_data = Some(hatch);
+        self
+    }
+
     /// Get the numeric values of the bars in this histogram.
     pub fn values(&self) -> &[f64] {
         &self.values
@@ -89,22 +139,35 @@ impl HistogramTrace {
     /// Specify where you want the bars to be positioned.
     ///
     /// Might bin this method.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there isn't a position for each value.
     pub fn set_positions(&mut self, positions: impl Into<Arc<[f64]>>) {
+        if let Err(e) = self.try_set_positions(positions) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Specify where you want the bars to be positioned, returning an error if there isn't a
+    /// position for each value.
+    pub fn try_set_positions(&mut self, positions: impl Into<Arc<[f64]>>) -> Result<(), Error> {
         let positions = positions.into();
-        assert_eq!((&*positions).len(), (&*self.values).len());
+        if positions.len() != self.values.len() {
+            return Err(Error::MismatchedLengths {
+                expected: self.values.len(),
+                found: positions.len(),
+            });
+        }
         self.positions = Some(positions);
+        self.size = None;
+        Ok(())
     }
 }
 
-impl Trace for HistogramTrace {
-    fn size(&self) -> Size {
-        self.size.unwrap()
-    }
-
-    fn layout(&mut self, size: Size, _rc: &mut Piet) -> Result<(), PietError> {
-        if self.size == Some(size) {
-            return Ok(());
-        }
+impl HistogramTrace {
+    /// Calculate the bar positions and widths for the given chart area size.
+    fn layout_bars(&mut self, size: Size) {
         self.size = Some(size);
         if self.y_range.is_none() {
             self.y_range = Some(
@@ -116,10 +179,7 @@ impl Trace for HistogramTrace {
             );
         }
         if self.bar_width.is_none() {
-            const SCALE_F: f64 = 0.004;
-            let bar_gap = size.width / self.values.len() as f64;
-            let bar_factor = 1. - ((SCALE_F * bar_gap).atan() * FRAC_2_PI);
-            self.bar_width = Some(bar_factor * bar_gap);
+            self.bar_width = Some(auto_bar_width(size.width / self.values.len() as f64));
         }
         if self.positions.is_none() {
             let gap = size.width / (self.values.len() as f64);
@@ -129,30 +189,140 @@ impl Trace for HistogramTrace {
                     .collect(),
             );
         }
-        Ok(())
     }
 
-    fn draw(&self, rc: &mut Piet) {
+    /// The rectangle for each bar, or `Err` with the full-height slot the bar would occupy if
+    /// its value is missing.
+    fn bars(&self) -> impl Iterator<Item = Result<Rect, Rect>> + '_ {
         let size = self.size.unwrap();
         let y_range = self.y_range.unwrap();
-        let bar_width = self.bar_width.unwrap();
-        let bar_width_2 = bar_width * 0.5;
+        let bar_width_2 = self.bar_width.unwrap() * 0.5;
         let positions = self.positions.as_ref().unwrap().iter().copied();
 
         let zero = size.height * (1. - y_range.t(0.));
-        for (&val, pos) in izip!(&*self.values, positions) {
-            let bar = Rect {
-                x0: pos - bar_width_2,
-                y0: size.height * (1. - y_range.t(val)),
-                x1: pos + bar_width_2,
-                y1: zero,
-            };
-            rc.fill(bar, &self.bar_color.clone().with_alpha(0.8));
-            rc.stroke(bar, &self.bar_color, 2.);
+        izip!(&*self.values, positions).map(move |(&val, pos)| {
+            if is_missing(val) {
+                Err(Rect::new(
+                    pos - bar_width_2,
+                    0.,
+                    pos + bar_width_2,
+                    size.height,
+                ))
+            } else {
+                Ok(Rect {
+                    x0: pos - bar_width_2,
+                    y0: size.height * (1. - y_range.t(val)),
+                    x1: pos + bar_width_2,
+                    y1: zero,
+                })
+            }
+        })
+    }
+
+    /// Build and place the value labels, if there are any.
+    fn layout_labels(&mut self, rc: &mut Piet) -> Result<(), PietError> {
+        let labels = match &mut self.labels {
+            Some(labels) => labels,
+            None => return Ok(()),
+        };
+        labels.clear();
+        let size = self.size.unwrap();
+        let y_range = self.y_range.unwrap();
+        let bar_width = self.bar_width.unwrap();
+        let bounds = size.to_rect();
+        let zero = size.height * (1. - y_range.t(0.));
+        for (&val, &pos) in izip!(&*self.values, &**self.positions.as_ref().unwrap()) {
+            if is_missing(val) {
+                continue;
+            }
+            let layout = labels.build_layout(labels.format(val), rc)?;
+            let candidates = bar_label_candidates(
+                labels.placement,
+                layout.size(),
+                pos,
+                size.height * (1. - y_range.t(val)),
+                zero,
+                bar_width,
+            );
+            labels.place(layout, candidates, bounds);
         }
+        Ok(())
+    }
+}
+
+impl Trace for HistogramTrace {
+    fn size(&self) -> Size {
+        self.size.unwrap()
+    }
+
+    fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
+    fn layout(&mut self, size: Size, rc: &mut Piet) -> Result<(), PietError> {
+        if self.size == Some(size) {
+            return Ok(());
+        }
+        self.layout_bars(size);
+        self.layout_labels(rc)?;
+        Ok(())
+    }
+
+    fn draw(&self, rc: &mut Piet) {
+        for bar in self.bars() {
+            match (bar, &self.no_data) {
+                (Ok(bar), _) => {
+                    rc.fill(bar, &self.bar_color.clone().with_alpha(0.8));
+                    rc.stroke(bar, &self.bar_color, 2.);
+                }
+                (Err(slot), Some(hatch)) => hatch.draw(slot, rc),
+                (Err(_), None) => (),
+            }
+        }
+        if let Some(labels) = &self.labels {
+            labels.draw(rc);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.values.iter().all(|&v| is_missing(v))
     }
 
     fn as_any(&mut self) -> &mut dyn Any {
         self
     }
 }
+
+#[test]
+fn test_histogram_skips_missing_bars() {
+    let mut trace = HistogramTrace::new(vec![1., f64::NAN, 2.]);
+    trace.layout_bars(Size::new(300., 100.));
+    let bars: Vec<_> = trace.bars().collect();
+    assert!(bars[0].is_ok() && bars[2].is_ok());
+    // The slot for the missing bar is still reserved, so it can be hatched.
+    let slot = bars[1].unwrap_err();
+    assert!(slot.x0 > 100. && slot.x1 < 200.);
+    assert_eq!(slot.height(), 100.);
+}
+
+#[test]
+fn test_histogram_degenerate_data() {
+    use crate::chart::test_degenerate;
+    test_degenerate(histogram(Vec::<&str>::new(), vec![]), true);
+    let (_, y_range) = test_degenerate(histogram(vec!["a"], vec![3.]), false);
+    assert!(y_range.unwrap().contains(3.));
+    let (_, y_range) = test_degenerate(histogram(vec!["a", "b"], vec![0., 0.]), false);
+    assert!(y_range.unwrap().contains(0.));
+    test_degenerate(histogram(vec!["a", "b"], vec![f64::NAN, f64::NAN]), true);
+}
+
+#[test]
+fn test_histogram_from_table() {
+    use crate::chart::test_render;
+    let table = Table::new().with_column("edlevel", vec![1., 3., 3., f64::NAN]);
+    test_render(histogram_from(&table, "edlevel").unwrap());
+    assert!(matches!(
+        histogram_from(&table, "age"),
+        Err(Error::UnknownColumn(_))
+    ));
+}
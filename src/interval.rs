@@ -1,4 +1,8 @@
-use crate::ticker::{Tick, Ticker};
+use crate::{
+    missing::is_missing,
+    ticker::{Tick, Ticker},
+    Error,
+};
 use std::{any::Any, f64::consts::LOG10_2, fmt};
 
 /// An [interval](https://en.wikipedia.org/wiki/Interval_(mathematics)) of real numbers.
@@ -6,6 +10,9 @@ use std::{any::Any, f64::consts::LOG10_2, fmt};
 /// Maintains invariants: `-∞ < min < max < ∞`.
 ///
 /// Because this is for continuous data, we ignore whether the interval is closed or open.
+///
+/// Intervals calculated from data (`FromIterator`, `Extend` and `try_from_values`) skip missing
+/// values: NaN, and also ±∞, which can't be drawn.
 #[derive(Copy, Clone, PartialEq)]
 pub struct Interval {
     min: f64,
@@ -32,17 +39,36 @@ impl Interval {
     /// `-∞ < min < max < ∞`.
     #[inline]
     pub fn new(min: f64, max: f64) -> Self {
-        assert!(
-            min.is_finite() && max.is_finite() && min < max,
-            "-∞ < {} < {} < ∞",
-            min,
-            max
-        );
+        match Self::try_new(min, max) {
+            Ok(interval) => interval,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Construct a interval from its min and max, or return an error unless
+    /// `-∞ < min < max < ∞`.
+    #[inline]
+    pub fn try_new(min: f64, max: f64) -> Result<Self, Error> {
         let interval = Interval { min, max };
-        if !interval.is_valid() {
-            panic!("invalid interval: -∞ < {} < {} < ∞", min, max);
+        if interval.is_valid() {
+            Ok(interval)
+        } else {
+            Err(Error::InvalidInterval { min, max })
         }
-        interval
+    }
+
+    /// The smallest interval that contains all the values in `iter`, skipping missing (NaN or
+    /// infinite) values.
+    ///
+    /// Returns `Error::EmptyData` if there are no values, and `Error::InvalidInterval` if they
+    /// are all the same.
+    pub fn try_from_values(iter: impl IntoIterator<Item = f64>) -> Result<Self, Error> {
+        let mut ival = Self::default();
+        ival.extend(iter);
+        if ival == Self::INVALID {
+            return Err(Error::EmptyData);
+        }
+        Self::try_new(ival.min, ival.max)
     }
 
     /// Whether this interval is valid.
@@ -108,9 +134,11 @@ impl Interval {
         if !val.is_finite() {
             panic!("can only extend to a finite value");
         }
+        // Not `else if`, because both change when extending `Interval::default()`.
         if val < self.min {
             self.min = val;
-        } else if val > self.max {
+        }
+        if val > self.max {
             self.max = val;
         }
         self
@@ -136,13 +164,41 @@ impl Interval {
         Interval::new(min, max)
     }
 
+    /// Make sure the interval can be drawn, even if it came from empty or constant data.
+    ///
+    /// An invalid interval (e.g. from no values) becomes `0..1`, and a single value `v` becomes
+    /// `v ± 10%` (or `v ± 1` if `v` is 0, and at least `f64::MIN_POSITIVE`), clamped to the finite range of `f64`. Valid intervals
+    /// are returned unchanged.
+    pub fn to_nonempty(self) -> Self {
+        if self.is_valid() {
+            self
+        } else if self.min == self.max && self.min.is_finite() {
+            // `MIN_POSITIVE` keeps the padding from rounding to 0 for subnormal values.
+            let pad = if self.min == 0. {
+                1.
+            } else {
+                (self.min.abs() * 0.1).max(f64::MIN_POSITIVE)
+            };
+            Interval::new(
+                (self.min - pad).max(f64::MIN),
+                (self.max + pad).min(f64::MAX),
+            )
+        } else {
+            Interval::new(0., 1.)
+        }
+    }
+
     /// Extends the interval to nice round numbers.
+    ///
+    /// Empty and single-value intervals are first padded using `to_nonempty`. If rounding would
+    /// go past the finite range of `f64`, the padded interval is returned unrounded.
     pub fn to_rounded(self) -> Self {
+        let this = self.to_nonempty();
         // log_10(2) (from libstd)
         // log_10(5)
         const LOG10_5: f64 = 0.6989700043360189;
 
-        let log10size = self.size().log10() - 1.;
+        let log10size = this.size().log10() - 1.;
         let mut scale = log10size.floor();
         let rem = log10size - scale;
         if rem < LOG10_2 {
@@ -155,9 +211,14 @@ impl Interval {
             scale += 1.
         }
         let scale = 10.0f64.powf(scale);
-        let min = self.min.div_euclid(scale) * scale;
-        let max = (self.max + scale).div_euclid(scale) * scale;
-        Interval::new(min, max)
+        let min = this.min.div_euclid(scale) * scale;
+        let max = (this.max + scale).div_euclid(scale) * scale;
+        Interval::try_new(min, max).unwrap_or(this)
+    }
+
+    /// Whether `value` is between min and max (inclusive).
+    pub fn contains(&self, value: f64) -> bool {
+        self.min <= value && value <= self.max
     }
 
     /// Get the position between min and max of the given value (0. = min, 1. = max).
@@ -171,12 +232,11 @@ impl Interval {
 }
 
 impl FromIterator<f64> for Interval {
-    /// Returns the smallest interval that contains all the values in `iter`.
-    ///
-    /// # Panics
+    /// Returns the smallest interval that contains all the values in `iter`, skipping missing
+    /// (NaN or infinite) values.
     ///
-    /// This function will panic if the iterator is empty, or if any of the values are +∞ or -∞.
-    /// NaN values are skipped.
+    /// The interval is invalid if there are no values, and empty if they are all the same. Use
+    /// `to_nonempty` or `to_rounded` to get an interval that can be drawn.
     fn from_iter<I>(iter: I) -> Self
     where
         I: IntoIterator<Item = f64>,
@@ -187,12 +247,14 @@ impl FromIterator<f64> for Interval {
     }
 }
 
+/// Extends the interval to include the values in `iter`, skipping missing (NaN or infinite)
+/// values.
 impl Extend<f64> for Interval {
     fn extend<I>(&mut self, iter: I)
     where
         I: IntoIterator<Item = f64>,
     {
-        for i in iter {
+        for i in iter.into_iter().filter(|i| !is_missing(*i)) {
             *self = self.extend_to(i);
         }
     }
@@ -258,7 +320,7 @@ impl Ticker for IntervalTicker {
         let start = calc_next_tick(self.interval.min(), step);
         // Rely on truncating behavior of `as usize`. TODO check the +1 is correct - I think it is
         // as we count fences but we want fence posts.
-        let count = ((self.interval.max() - start) / step) as usize + 1;
+        let count = ((self.interval.max() - start) / step + STEP_TOLERANCE) as usize + 1;
         self.step_start_count = Some((step, start, count));
 
         let scale = axis_len / self.interval.size();
@@ -282,10 +344,20 @@ impl Ticker for IntervalTicker {
         let val = idx as f64 * step + start;
         Some(Tick {
             pos: val * scale + translate,
-            label: val.to_string().into(),
+            label: format_value(val).into(),
         })
     }
 
+    fn interval(&self) -> Option<Interval> {
+        Some(self.interval)
+    }
+
+    fn set_interval(&mut self, interval: Interval) {
+        self.interval = interval;
+        self.step_start_count = None;
+        self.transform = None;
+    }
+
     fn as_any(&self) -> &dyn Any {
         self
     }
@@ -293,6 +365,18 @@ impl Ticker for IntervalTicker {
 
 // helpers
 
+/// The fraction of a step that tick counts allow for floating point error, so that a tick that
+/// lands (almost) exactly on the end of an interval is counted.
+const STEP_TOLERANCE: f64 = 1e-9;
+
+/// Format a value for display as a label.
+///
+/// This is what `IntervalTicker` uses for its tick labels, so other labels (e.g. data labels)
+/// can use it to match the axis.
+pub fn format_value(val: f64) -> String {
+    val.to_string()
+}
+
 /// Returns gap between each scale tick, in terms of the y variable, that gives closest to the
 /// requested `target_count` and is either 1, 2 or 5 ×10<sup>n</sup> for some n (hardcoded for now).
 pub fn calc_tick_spacing(interval: Interval, target_count: usize) -> f64 {
@@ -385,7 +469,7 @@ pub fn calc_prev_tick(v: f64, spacing: f64) -> f64 {
 fn count_ticks(interval: Interval, tick_step: f64) -> usize {
     let start = calc_next_tick(interval.min(), tick_step);
     let end = calc_prev_tick(interval.max(), tick_step);
-    ((end - start) / tick_step).floor() as usize + 1 // fence/fencepost
+    ((end - start) / tick_step + STEP_TOLERANCE).floor() as usize + 1 // fence/fencepost
 }
 
 /// An alternate way to calculate the number of ticks. Used for testing.
@@ -394,7 +478,7 @@ fn count_ticks_slow(interval: Interval, tick_step: f64) -> usize {
     let mut start = calc_next_tick(interval.min(), tick_step);
     let end = calc_prev_tick(interval.max(), tick_step);
     let mut tick_count = 1;
-    while start <= end {
+    while start <= end + tick_step * STEP_TOLERANCE {
         tick_count += 1;
         start += tick_step;
     }
@@ -408,3 +492,57 @@ fn test_interval_extend() {
     ival.extend([1., 2., 3.]);
     assert_eq!(ival, Interval::new(1., 3.));
 }
+
+#[test]
+fn test_interval_try_new() {
+    assert!(matches!(
+        Interval::try_new(1., 1.),
+        Err(Error::InvalidInterval { .. })
+    ));
+    assert!(matches!(
+        Interval::try_from_values([f64::NAN]),
+        Err(Error::EmptyData)
+    ));
+    assert!(matches!(
+        Interval::try_from_values([1., f64::INFINITY]),
+        Err(Error::InvalidInterval { .. })
+    ));
+    assert_eq!(
+        Interval::try_from_values([1., f64::NEG_INFINITY, 3.]).unwrap(),
+        Interval::new(1., 3.)
+    );
+    assert_eq!(
+        Interval::from_iter([2., f64::INFINITY, 1.]),
+        Interval::new(1., 2.)
+    );
+    assert_eq!(
+        Interval::try_from_values([2., f64::NAN, 1.]).unwrap(),
+        Interval::new(1., 2.)
+    );
+}
+
+#[test]
+fn test_interval_to_nonempty() {
+    let empty = Interval::from_iter(std::iter::empty());
+    assert_eq!(empty.to_nonempty(), Interval::new(0., 1.));
+    assert!(empty.to_rounded().is_valid());
+    let constant = Interval::from_iter([5., 5.]);
+    assert_eq!(constant.to_nonempty(), Interval::new(4.5, 5.5));
+    assert!(constant.to_rounded().is_valid());
+    let zero = Interval::from_iter([0.]).include_zero();
+    assert_eq!(zero.to_nonempty(), Interval::new(-1., 1.));
+    assert_eq!(Interval::new(1., 2.).to_nonempty(), Interval::new(1., 2.));
+
+    // Padding and rounding don't overflow near the largest finite values.
+    let max = Interval::from_iter([f64::MAX]);
+    assert_eq!(max.to_nonempty(), Interval::new(f64::MAX * 0.9, f64::MAX));
+    assert!(max.to_rounded().is_valid());
+    let min = Interval::from_iter([f64::MIN]);
+    assert_eq!(min.to_nonempty(), Interval::new(f64::MIN, f64::MIN * 0.9));
+    assert!(min.to_rounded().is_valid());
+    assert!(Interval::new(f64::MIN, f64::MAX).to_rounded().is_valid());
+    let subnormal = Interval::from_iter([5e-324]);
+    let padded = subnormal.to_nonempty();
+    assert!(padded.min() < 5e-324 && padded.max() > 5e-324);
+    assert!(subnormal.to_rounded().contains(5e-324));
+}
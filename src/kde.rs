@@ -0,0 +1,350 @@
+//! Kernel density estimation.
+use crate::{
+    prelude::*,
+    stats::{mean_sd, quantile, sorted_present},
+    theme, Chart, Error, GridStyle, Interval, Trace,
+};
+use piet_common::{
+    kurbo::{BezPath, Point, Size},
+    Color, Error as PietError, Piet, RenderContext,
+};
+use std::{
+    any::Any,
+    f64::consts::{FRAC_1_SQRT_2, PI},
+    sync::Arc,
+};
+
+/// The number of points the density curve is evaluated at by default.
+const DEFAULT_SAMPLES: usize = 200;
+
+/// Create a chart showing the estimated density of some values.
+///
+/// Uses a Gaussian kernel with Silverman's rule of thumb for the bandwidth. NaN values are
+/// skipped.
+pub fn kde_chart(values: impl Into<Arc<[f64]>>) -> Chart {
+    let kde = Kde::new(&values.into());
+    let x_range = kde.support().unwrap_or_default().to_rounded();
+    let max_density = kde
+        .sample(x_range, DEFAULT_SAMPLES)
+        .into_iter()
+        .map(|(_, d)| d);
+    let y_range = Interval::from_iter(max_density).include_zero().to_rounded();
+    Chart::new()
+        .with_left_axis(y_range.ticker().reverse())
+        .with_left_grid(GridStyle::default())
+        .with_bottom_axis(x_range.ticker())
+        .with_trace(KdeTrace::new(kde, x_range, y_range))
+}
+
+/// The function used to spread each value out into a density.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Kernel {
+    /// The normal distribution.
+    Gaussian,
+    /// A parabola, which is 0 more than one bandwidth away from the value.
+    Epanechnikov,
+}
+
+impl Kernel {
+    /// The value of the kernel at `u` bandwidths from the center.
+    pub fn eval(self, u: f64) -> f64 {
+        match self {
+            Kernel::Gaussian => (-0.5 * u * u).exp() * FRAC_1_SQRT_2 / PI.sqrt(),
+            Kernel::Epanechnikov if u.abs() <= 1. => 0.75 * (1. - u * u),
+            Kernel::Epanechnikov => 0.,
+        }
+    }
+
+    /// How many bandwidths from the center the kernel reaches.
+    ///
+    /// The Gaussian kernel reaches forever, but is negligible after this point.
+    fn reach(self) -> f64 {
+        match self {
+            Kernel::Gaussian => 4.,
+            Kernel::Epanechnikov => 1.,
+        }
+    }
+}
+
+/// How to choose the bandwidth (the amount of smoothing) of a kernel density estimate.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Bandwidth {
+    /// Silverman's rule of thumb, `0.9 min(σ, IQR / 1.34) n^(-1/5)`.
+    Silverman,
+    /// Scott's rule, `1.06 σ n^(-1/5)`.
+    Scott,
+    /// A fixed bandwidth.
+    Fixed(f64),
+}
+
+impl Bandwidth {
+    /// The bandwidth to use for some sorted values.
+    ///
+    /// Falls back to 1 if the rule gives a bandwidth that isn't positive (e.g. if all the values
+    /// are the same).
+    fn resolve(self, sorted: &[f64]) -> f64 {
+        let n_factor = (sorted.len() as f64).powf(-0.2);
+        let bandwidth = match self {
+            Bandwidth::Silverman => {
+                let (_, sd) = mean_sd(sorted);
+                let iqr = quantile(sorted, 0.75) - quantile(sorted, 0.25);
+                let spread = if iqr > 0. { sd.min(iqr / 1.34) } else { sd };
+                0.9 * spread * n_factor
+            }
+            Bandwidth::Scott => 1.06 * mean_sd(sorted).1 * n_factor,
+            Bandwidth::Fixed(bandwidth) => bandwidth,
+        };
+        if bandwidth.is_finite() && bandwidth > 0. {
+            bandwidth
+        } else {
+            // Fall back to 10% of the size of the values (or 1 if they are 0), like
+            // `Interval::to_nonempty`, so the bandwidth isn't lost next to large values.
+            let scale = sorted
+                .first()
+                .zip(sorted.last())
+                .map_or(0., |(first, last)| first.abs().max(last.abs()));
+            if scale > 0. {
+                (scale * 0.1).max(f64::MIN_POSITIVE)
+            } else {
+                1.
+            }
+        }
+    }
+}
+
+/// A kernel density estimate of some values.
+#[derive(Debug, Clone)]
+pub struct Kde {
+    /// The values, sorted and without missing values.
+    values: Arc<[f64]>,
+    kernel: Kernel,
+    bandwidth: f64,
+}
+
+impl Kde {
+    /// Estimate the density of `values` with a Gaussian kernel and Silverman's rule for the
+    /// bandwidth. NaN values are skipped.
+    pub fn new(values: &[f64]) -> Self {
+        let values = sorted_present(values);
+        Self {
+            bandwidth: Bandwidth::Silverman.resolve(&values),
+            values: values.into(),
+            kernel: Kernel::Gaussian,
+        }
+    }
+
+    pub fn with_kernel(mut self, kernel: Kernel) -> Self {
+        self.kernel = kernel;
+        self
+    }
+
+    pub fn with_bandwidth(mut self, bandwidth: Bandwidth) -> Self {
+        self.bandwidth = bandwidth.resolve(&self.values);
+        self
+    }
+
+    /// The values being estimated, sorted and without missing values.
+    pub fn values(&self) -> &[f64] {
+        &self.values
+    }
+
+    pub fn kernel(&self) -> Kernel {
+        self.kernel
+    }
+
+    pub fn bandwidth(&self) -> f64 {
+        self.bandwidth
+    }
+
+    /// The estimated density at `x`.
+    pub fn density(&self, x: f64) -> f64 {
+        if self.values.is_empty() {
+            return 0.;
+        }
+        // Only values within the kernel's reach contribute.
+        let reach = self.kernel.reach() * self.bandwidth;
+        let start = self.values.partition_point(|v| *v < x - reach);
+        let end = self.values.partition_point(|v| *v <= x + reach);
+        let sum: f64 = self.values[start..end]
+            .iter()
+            .map(|v| self.kernel.eval((x - v) / self.bandwidth))
+            .sum();
+        sum / (self.values.len() as f64 * self.bandwidth)
+    }
+
+    /// The range of x values where the density is (effectively) non-zero, or `None` if there are
+    /// no values.
+    pub fn support(&self) -> Option<Interval> {
+        let (first, last) = (self.values.first()?, self.values.last()?);
+        let reach = self.kernel.reach() * self.bandwidth;
+        let mut support = Interval::default();
+        support.extend([first - reach, last + reach]);
+        Some(support.to_nonempty())
+    }
+
+    /// The `(x, density)` at `samples` evenly spaced points from the start to the end of `range`.
+    pub fn sample(&self, range: Interval, samples: usize) -> Vec<(f64, f64)> {
+        let step = range.size() / (samples.max(2) - 1) as f64;
+        (0..samples.max(2))
+            .map(|idx| {
+                let x = range.min() + step * idx as f64;
+                (x, self.density(x))
+            })
+            .collect()
+    }
+}
+
+/// Draws the estimated density of some values as a filled curve.
+pub struct KdeTrace {
+    /// The density estimate.
+    ///
+    /// Not public because we have retained state that depends on it.
+    kde: Kde,
+    /// The range that x values should be shown over
+    x_range: Interval,
+    /// The range that densities should be shown over
+    y_range: Interval,
+    /// The color of the curve and the area under it.
+    pub color: Color,
+    /// The opacity of the area under the curve. Set to 0 to not fill it.
+    pub fill_opacity: f64,
+    /// The width of the curve.
+    pub stroke_width: f64,
+    /// The number of points to evaluate the density at.
+    pub samples: usize,
+
+    // Retained
+    /// The size of the chart area.
+    pub size: Option<Size>,
+    /// The points of the curve, in chart area coordinates.
+    curve: Vec<Point>,
+}
+
+impl KdeTrace {
+    pub fn new(kde: Kde, x_range: Interval, y_range: Interval) -> Self {
+        Self {
+            kde,
+            x_range,
+            y_range,
+            color: theme::series_color(0),
+            fill_opacity: theme::AREA_FILL_OPACITY,
+            stroke_width: 1.5,
+            samples: DEFAULT_SAMPLES,
+            size: None,
+            curve: vec![],
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_fill_opacity(mut self, fill_opacity: f64) -> Self {
+        self.fill_opacity = fill_opacity;
+        self
+    }
+
+    pub fn kde(&self) -> &Kde {
+        &self.kde
+    }
+
+    pub fn set_kde(&mut self, kde: Kde) {
+        self.kde = kde;
+        self.size = None;
+    }
+}
+
+impl Trace for KdeTrace {
+    fn layout(&mut self, size: Size, _rc: &mut Piet) -> Result<(), PietError> {
+        if self.size == Some(size) {
+            return Ok(());
+        }
+        self.size = Some(size);
+        let (x_range, y_range) = (self.x_range, self.y_range);
+        self.curve = self
+            .kde
+            .sample(x_range, self.samples)
+            .into_iter()
+            .map(|(x, density)| {
+                Point::new(
+                    x_range.t(x) * size.width,
+                    (1. - y_range.t(density)) * size.height,
+                )
+            })
+            .collect();
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        self.size.unwrap()
+    }
+
+    fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
+    fn draw(&self, rc: &mut Piet) {
+        let size = self.size.unwrap();
+        let (first, last) = match (self.curve.first(), self.curve.last()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return,
+        };
+        let mut line = BezPath::new();
+        line.move_to(first);
+        for point in &self.curve[1..] {
+            line.line_to(*point);
+        }
+        if self.fill_opacity > 0. {
+            let zero = (1. - self.y_range.t(0.)) * size.height;
+            let mut area = line.clone();
+            area.line_to((last.x, zero));
+            area.line_to((first.x, zero));
+            area.close_path();
+            rc.fill(area, &self.color.clone().with_alpha(self.fill_opacity));
+        }
+        rc.stroke(line, &self.color, self.stroke_width);
+    }
+
+    fn set_intervals(&mut self, x_range: Interval, y_range: Interval) {
+        self.x_range = x_range;
+        self.y_range = y_range;
+        self.size = None;
+    }
+
+    fn has_intervals(&self) -> bool {
+        true
+    }
+
+    fn is_empty(&self) -> bool {
+        self.kde.values().is_empty()
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[test]
+fn test_kde_integrates_to_one() {
+    for kernel in [Kernel::Gaussian, Kernel::Epanechnikov] {
+        let kde = Kde::new(&[1., 2., 2.5, f64::NAN, 4.]).with_kernel(kernel);
+        let samples = kde.sample(kde.support().unwrap(), 1000);
+        let step = samples[1].0 - samples[0].0;
+        let total: f64 = samples.iter().map(|(_, d)| d * step).sum();
+        assert!((total - 1.).abs() < 0.01, "{:?}: {}", kernel, total);
+    }
+}
+
+#[test]
+fn test_kde_chart_degenerate_data() {
+    use crate::chart::test_degenerate;
+    assert_eq!(Kde::new(&[]).support(), None);
+    test_degenerate(kde_chart(vec![]), true);
+    let (x_range, _) = test_degenerate(kde_chart(vec![1.]), false);
+    assert!(x_range.unwrap().contains(1.));
+    let (x_range, _) = test_degenerate(kde_chart(vec![2., 2., 2.]), false);
+    assert!(x_range.unwrap().contains(2.));
+    let (x_range, _) = test_degenerate(kde_chart(vec![1e17, 1e17]), false);
+    assert!(x_range.unwrap().contains(1e17));
+}
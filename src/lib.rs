@@ -6,24 +6,66 @@
     Color,
 };*/
 
+/// Log a warning with the `log` crate if the `log` feature is enabled, otherwise do nothing.
+macro_rules! log_warn {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "log")]
+        log::warn!($($arg)+);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)+);
+    }};
+}
+
+mod aggregate;
+mod annotation;
+pub mod area;
 pub mod axis;
-pub mod prelude;
-mod ticker;
-//mod box_plot;
+pub mod box_plot;
+pub mod heatmap;
 pub mod histogram;
+pub mod prelude;
 pub mod scatter;
+mod ticker;
 //mod line_chart;
 //mod pie_chart;
 mod chart;
+mod color_map;
+mod colorbar;
+mod data_label;
+pub mod density;
+pub mod ecdf;
+mod error;
 mod interval;
+pub mod kde;
+pub mod line;
+mod marker;
+mod missing;
+pub mod ohlc;
+pub mod polar;
+pub mod qq;
+pub mod regression;
 mod sequence;
+pub mod sparkline;
+pub mod spec;
+mod stats;
+mod table;
 pub mod theme;
 mod trace;
+pub mod violin;
 
 pub use crate::{
-    chart::{Chart, GridStyle},
-    interval::{Interval, IntervalTicker},
+    aggregate::{Aggregate, GroupBy},
+    annotation::{Annotation, Annotations, Layer, Position},
+    chart::{AxisReport, Chart, EqualScale, GridStyle, LayoutReport},
+    color_map::{ColorMap, ColorScale},
+    colorbar::Colorbar,
+    data_label::{DataLabels, LabelPlacement},
+    error::Error,
+    interval::{format_value, Interval, IntervalTicker},
+    marker::{Marker, MarkerShape},
+    missing::NoDataHatch,
     sequence::{Categorical, Numeric, Sequence, SequenceExt},
+    table::{Column, ColumnType, Table},
     ticker::{Tick, Ticker},
     trace::Trace,
 };
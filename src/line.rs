@@ -0,0 +1,519 @@
+use crate::{
+    marker::overflow_marker,
+    missing::{is_missing, present_runs},
+    prelude::*,
+    theme, Chart, Error, GridStyle, Interval, Marker, NoDataHatch, Table, Trace,
+};
+use piet_common::{
+    kurbo::{BezPath, Circle, Point, Rect, Size},
+    Color, Error as PietError, Piet, RenderContext, StrokeStyle,
+};
+use std::{any::Any, sync::Arc};
+
+/// Create a line chart with a line for each series of `(x, y)` values.
+///
+/// Each series gets the next color from the theme.
+pub fn line_chart<S>(series: impl IntoIterator<Item = S>) -> Chart
+where
+    S: Into<Arc<[(f64, f64)]>>,
+{
+    let series: Vec<Arc<[(f64, f64)]>> = series.into_iter().map(Into::into).collect();
+    let (x_range, y_range): (Interval, Interval) = series
+        .iter()
+        .flat_map(|values| values.iter().copied())
+        .unzip();
+    let (x_range, y_range) = (x_range.to_rounded(), y_range.to_rounded());
+    let mut chart = Chart::new()
+        .with_left_axis(y_range.ticker().reverse())
+        .with_left_grid(GridStyle::default())
+        .with_bottom_axis(x_range.ticker());
+    for (idx, values) in series.into_iter().enumerate() {
+        chart = chart.with_trace(
+            LineTrace::new(values, x_range, y_range).with_color(theme::series_color(idx)),
+        );
+    }
+    chart
+}
+
+/// Create a line chart of the numeric columns `x` and `y` of `table`, sorted by `x`.
+pub fn line_from(table: &Table, x: &str, y: &str) -> Result<Chart, Error> {
+    let mut values = table.pairs(x, y)?.to_vec();
+    values.sort_by(|a, b| a.0.total_cmp(&b.0));
+    Ok(line_chart([values]))
+}
+
+/// How to join up the points of a line.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Interpolation {
+    /// Straight lines between points.
+    Linear,
+    /// Change to the next value halfway between points.
+    StepMid,
+    /// Change to the next value at the point before (the line is horizontal leading up to each
+    /// point).
+    StepBefore,
+    /// Change to the next value at the next point (the line is horizontal after each point).
+    StepAfter,
+    /// A smooth curve that preserves monotonicity, so it never overshoots the data
+    /// (Fritsch–Carlson).
+    Monotone,
+    /// A Catmull–Rom spline, with control points clamped so the curve doesn't overshoot the data.
+    CatmullRom,
+}
+
+/// A line joining a series of points.
+///
+/// Points with a NaN coordinate are missing: the line is broken either side of them.
+pub struct LineTrace {
+    /// The points of the line, sorted by x.
+    ///
+    /// Not public because we have retained state that depends on them.
+    values: Arc<[(f64, f64)]>,
+    /// The range that x values should be shown over
+    x_range: Interval,
+    /// The range that y values should be shown over
+    y_range: Interval,
+    /// The line color.
+    pub color: Color,
+    /// The line width.
+    pub stroke_width: f64,
+    /// The line style, including any dash pattern.
+    pub stroke_style: StrokeStyle,
+    /// An optional marker to draw at each point.
+    pub marker: Option<Marker>,
+    /// How to join up the points.
+    pub interpolation: Interpolation,
+    /// If set, gaps in the line from missing data are hatched.
+    pub no_data: Option<NoDataHatch>,
+    /// Whether to mark points outside the intervals with arrows at the edge of the chart area.
+    overflow_markers: bool,
+
+    // Retained
+    /// The size of the chart area.
+    pub size: Option<Size>,
+}
+
+impl LineTrace {
+    /// A line trace.
+    ///
+    /// `values` should be sorted by x.
+    pub fn new(values: impl Into<Arc<[(f64, f64)]>>, x_range: Interval, y_range: Interval) -> Self {
+        Self {
+            values: values.into(),
+            x_range,
+            y_range,
+            color: theme::series_color(0),
+            stroke_width: 2.,
+            stroke_style: StrokeStyle::new(),
+            marker: None,
+            interpolation: Interpolation::Linear,
+            no_data: None,
+            overflow_markers: false,
+            size: None,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_stroke_width(mut self, stroke_width: f64) -> Self {
+        self.stroke_width = stroke_width;
+        self
+    }
+
+    pub fn with_stroke_style(mut self, stroke_style: StrokeStyle) -> Self {
+        self.stroke_style = stroke_style;
+        self
+    }
+
+    /// Draw a dashed line, alternating between drawn and skipped lengths from `pattern`.
+    pub fn with_dash(mut self, pattern: &'static [f64]) -> Self {
+        self.stroke_style = self.stroke_style.dash_pattern(pattern);
+        self
+    }
+
+    pub fn with_marker(mut self, marker: Marker) -> Self {
+        self.marker = Some(marker);
+        self
+    }
+
+    /// Draw an arrow at the edge of the chart area for each point outside the x or y interval.
+    pub fn with_overflow_markers(mut self) -> Self {
+        self.overflow_markers = true;
+        self
+    }
+
+    pub fn set_overflow_markers(&mut self, overflow_markers: bool) -> &mut Self {
+        self.overflow_markers = overflow_markers;
+        self.size = None;
+        self
+    }
+
+    pub fn with_interpolation(mut self, interpolation: Interpolation) -> Self {
+        self.interpolation = interpolation;
+        self
+    }
+
+    /// Hatch the gaps in the line caused by missing data.
+    pub fn with_no_data(mut self, hatch: NoDataHatch) -> Self {
+        self.no_data = Some(hatch);
+        self
+    }
+
+    pub fn values(&self) -> &[(f64, f64)] {
+        &self.values
+    }
+
+    pub fn set_values(&mut self, new_values: impl Into<Arc<[(f64, f64)]>>) {
+        self.values = new_values.into();
+        self.size = None;
+    }
+
+    pub fn set_x_interval(&mut self, new_range: Interval) {
+        self.x_range = new_range;
+        self.size = None;
+    }
+
+    pub fn set_y_interval(&mut self, new_range: Interval) {
+        self.y_range = new_range;
+        self.size = None;
+    }
+
+    /// Convert a value to its position in the chart area.
+    fn to_point(&self, (x, y): (f64, f64), size: Size) -> Point {
+        // The y position is reversed (because we want 0 at the bottom, not the top)
+        Point::new(
+            self.x_range.t(x) * size.width,
+            (1. - self.y_range.t(y)) * size.height,
+        )
+    }
+
+    /// An arrow on the edge of the chart area for each point outside it, if overflow markers are
+    /// on.
+    fn overflow_markers(&self, size: Size) -> Vec<BezPath> {
+        if !self.overflow_markers {
+            return vec![];
+        }
+        self.runs(size)
+            .into_iter()
+            .flatten()
+            .filter_map(|point| overflow_marker(point, size.to_rect()))
+            .collect()
+    }
+
+    /// The runs of points between missing values, in chart coordinates.
+    fn runs(&self, size: Size) -> Vec<Vec<Point>> {
+        present_runs(&self.values, |&(x, y)| is_missing(x) || is_missing(y))
+            .into_iter()
+            .map(|run| {
+                self.values[run]
+                    .iter()
+                    .map(|&value| self.to_point(value, size))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// The path of the line, broken where there is missing data.
+    fn path(&self, size: Size) -> BezPath {
+        let mut path = BezPath::new();
+        for run in self.runs(size) {
+            path.extend(line_path(&run, self.interpolation));
+        }
+        path
+    }
+
+    /// The areas between the ends of the line either side of missing data.
+    ///
+    /// Missing data at the start or end of the line extends the gap to the edge of the chart.
+    fn gaps(&self, size: Size) -> Vec<Rect> {
+        let runs = self.runs(size);
+        if runs.is_empty() {
+            return if self.values.is_empty() {
+                vec![]
+            } else {
+                vec![size.to_rect()]
+            };
+        }
+        let missing = |&(x, y): &(f64, f64)| is_missing(x) || is_missing(y);
+        let mut gaps = vec![];
+        if self.values.first().map(missing).unwrap_or(false) {
+            gaps.push(Rect::new(0., 0., runs[0][0].x, size.height));
+        }
+        for pair in runs.windows(2) {
+            let (end, start) = (pair[0][pair[0].len() - 1], pair[1][0]);
+            gaps.push(Rect::new(end.x, 0., start.x, size.height));
+        }
+        if self.values.last().map(missing).unwrap_or(false) {
+            let last = &runs[runs.len() - 1];
+            gaps.push(Rect::new(
+                last[last.len() - 1].x,
+                0.,
+                size.width,
+                size.height,
+            ));
+        }
+        gaps
+    }
+}
+
+impl Trace for LineTrace {
+    fn layout(&mut self, size: Size, _rc: &mut Piet) -> Result<(), PietError> {
+        self.size = Some(size);
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        self.size.unwrap()
+    }
+
+    fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
+    fn draw(&self, rc: &mut Piet) {
+        let size = self.size.unwrap();
+        if let Some(hatch) = &self.no_data {
+            for gap in self.gaps(size) {
+                hatch.draw(gap, rc);
+            }
+        }
+        rc.stroke_styled(
+            self.path(size),
+            &self.color,
+            self.stroke_width,
+            &self.stroke_style,
+        );
+        if let Some(marker) = &self.marker {
+            for point in self.runs(size).into_iter().flatten() {
+                marker.draw(point, &self.color, rc);
+            }
+        } else {
+            // A point on its own has no line, so draw a dot so it isn't lost.
+            for run in self.runs(size) {
+                if let [point] = run[..] {
+                    rc.fill(Circle::new(point, self.stroke_width), &self.color);
+                }
+            }
+        }
+        for arrow in self.overflow_markers(size) {
+            rc.fill(arrow, &self.color);
+        }
+    }
+
+    fn set_intervals(&mut self, x_range: Interval, y_range: Interval) {
+        self.x_range = x_range;
+        self.y_range = y_range;
+    }
+
+    fn has_intervals(&self) -> bool {
+        true
+    }
+
+    fn is_empty(&self) -> bool {
+        self.values
+            .iter()
+            .all(|&(x, y)| is_missing(x) || is_missing(y))
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Build a path through `points` (which should be sorted by x) using the given interpolation.
+pub(crate) fn line_path(points: &[Point], interpolation: Interpolation) -> BezPath {
+    let mut path = BezPath::new();
+    let first = match points.first() {
+        Some(&first) => first,
+        None => return path,
+    };
+    path.move_to(first);
+    match interpolation {
+        Interpolation::Linear => {
+            for &p in &points[1..] {
+                path.line_to(p);
+            }
+        }
+        Interpolation::StepMid => {
+            for w in points.windows(2) {
+                let mid = (w[0].x + w[1].x) * 0.5;
+                path.line_to((mid, w[0].y));
+                path.line_to((mid, w[1].y));
+                path.line_to(w[1]);
+            }
+        }
+        Interpolation::StepBefore => {
+            for w in points.windows(2) {
+                path.line_to((w[0].x, w[1].y));
+                path.line_to(w[1]);
+            }
+        }
+        Interpolation::StepAfter => {
+            for w in points.windows(2) {
+                path.line_to((w[1].x, w[0].y));
+                path.line_to(w[1]);
+            }
+        }
+        Interpolation::Monotone => {
+            let tangents = monotone_tangents(points);
+            for (w, t) in points.windows(2).zip(tangents.windows(2)) {
+                let (p0, p1) = (w[0], w[1]);
+                let dx = (p1.x - p0.x) / 3.;
+                path.curve_to(
+                    (p0.x + dx, p0.y + dx * t[0]),
+                    (p1.x - dx, p1.y - dx * t[1]),
+                    (p1.x, p1.y),
+                );
+            }
+        }
+        Interpolation::CatmullRom => {
+            for idx in 0..points.len() - 1 {
+                let p0 = points[idx.saturating_sub(1)];
+                let (p1, p2) = (points[idx], points[idx + 1]);
+                let p3 = points[(idx + 2).min(points.len() - 1)];
+                // Convert the uniform Catmull–Rom segment to a cubic bezier, then clamp the
+                // control points between the ends so the curve can't overshoot.
+                let (y_min, y_max) = (p1.y.min(p2.y), p1.y.max(p2.y));
+                let c1 = Point::new(
+                    p1.x + (p2.x - p0.x) / 6.,
+                    (p1.y + (p2.y - p0.y) / 6.).clamp(y_min, y_max),
+                );
+                let c2 = Point::new(
+                    p2.x - (p3.x - p1.x) / 6.,
+                    (p2.y - (p3.y - p1.y) / 6.).clamp(y_min, y_max),
+                );
+                path.curve_to(c1, c2, p2);
+            }
+        }
+    }
+    path
+}
+
+/// The gradient of a monotone cubic interpolation at each point (the same method as d3's
+/// `curveMonotoneX`).
+fn monotone_tangents(points: &[Point]) -> Vec<f64> {
+    let n = points.len();
+    if n < 2 {
+        return vec![0.; n];
+    }
+    let slope = |a: Point, b: Point| {
+        let h = b.x - a.x;
+        if h == 0. {
+            0.
+        } else {
+            (b.y - a.y) / h
+        }
+    };
+    let mut tangents = vec![0.; n];
+    for idx in 1..n - 1 {
+        let (p0, p1, p2) = (points[idx - 1], points[idx], points[idx + 1]);
+        let (h0, h1) = (p1.x - p0.x, p2.x - p1.x);
+        let (s0, s1) = (slope(p0, p1), slope(p1, p2));
+        if s0 * s1 <= 0. || h0 + h1 == 0. {
+            // local extremum: flat so we don't overshoot
+            continue;
+        }
+        let p = (s0 * h1 + s1 * h0) / (h0 + h1);
+        tangents[idx] = s0.signum() * s0.abs().min(s1.abs()).min(0.5 * p.abs()) * 2.;
+    }
+    // The ends use a one-sided estimate.
+    let end_tangent = |s: f64, inner: f64| {
+        let t = (3. * s - inner) * 0.5;
+        if t * s <= 0. {
+            0.
+        } else if t.abs() > 3. * s.abs() {
+            3. * s
+        } else {
+            t
+        }
+    };
+    tangents[0] = end_tangent(slope(points[0], points[1]), tangents[1]);
+    tangents[n - 1] = end_tangent(slope(points[n - 2], points[n - 1]), tangents[n - 2]);
+    if n == 2 {
+        let s = slope(points[0], points[1]);
+        tangents[0] = s;
+        tangents[1] = s;
+    }
+    tangents
+}
+
+#[test]
+fn test_line_breaks_at_missing_data() {
+    use piet_common::kurbo::PathEl;
+    let size = Size::new(100., 100.);
+    let trace = LineTrace::new(
+        vec![(0., 0.), (1., f64::NAN), (2., 1.), (3., 0.), (f64::NAN, 1.)],
+        Interval::new(0., 4.),
+        Interval::new(0., 1.),
+    );
+    let move_tos = trace
+        .path(size)
+        .elements()
+        .iter()
+        .filter(|el| matches!(el, PathEl::MoveTo(_)))
+        .count();
+    assert_eq!(move_tos, 2);
+    assert_eq!(
+        trace.gaps(size),
+        vec![Rect::new(0., 0., 50., 100.), Rect::new(75., 0., 100., 100.)]
+    );
+}
+
+#[test]
+fn test_line_overflow_markers() {
+    let size = Size::new(100., 100.);
+    let mut trace = LineTrace::new(
+        vec![(0., 0.5), (1., 2.), (2., f64::NAN), (3., 0.5)],
+        Interval::new(0., 3.),
+        Interval::new(0., 1.),
+    );
+    assert!(trace.overflow_markers(size).is_empty());
+    trace.set_overflow_markers(true);
+    let markers = trace.overflow_markers(size);
+    assert_eq!(markers.len(), 1);
+    // The arrow points up from the top edge, above x = 1.
+    let bounds = piet_common::kurbo::Shape::bounding_box(&markers[0]);
+    assert!(bounds.y0.abs() < 1e-9 && bounds.center().x > 30. && bounds.center().x < 37.);
+}
+
+#[test]
+fn test_monotone_does_not_overshoot() {
+    use piet_common::kurbo::{ParamCurve, PathSeg};
+    let points = [
+        Point::new(0., 0.),
+        Point::new(1., 10.),
+        Point::new(2., 10.),
+        Point::new(3., 0.),
+        Point::new(4., 1.),
+    ];
+    for interpolation in [Interpolation::Monotone, Interpolation::CatmullRom] {
+        let path = line_path(&points, interpolation);
+        for (seg, w) in path.segments().zip(points.windows(2)) {
+            let (y_min, y_max) = (w[0].y.min(w[1].y), w[0].y.max(w[1].y));
+            if let PathSeg::Cubic(cubic) = seg {
+                for step in 0..=20 {
+                    let y = cubic.eval(step as f64 / 20.).y;
+                    assert!(
+                        y >= y_min - 1e-9 && y <= y_max + 1e-9,
+                        "{:?}",
+                        interpolation
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_line_chart_degenerate_data() {
+    use crate::chart::test_degenerate;
+    test_degenerate(line_chart(Vec::<Vec<(f64, f64)>>::new()), true);
+    test_degenerate(line_chart([vec![]]), true);
+    let (x_range, y_range) = test_degenerate(line_chart([vec![(1., 2.)]]), false);
+    assert!(x_range.unwrap().contains(1.) && y_range.unwrap().contains(2.));
+    let (_, y_range) = test_degenerate(line_chart([vec![(0., 2.), (1., 2.), (2., 2.)]]), false);
+    assert!(y_range.unwrap().contains(2.));
+}
@@ -0,0 +1,172 @@
+use piet_common::{
+    kurbo::{BezPath, Circle, Point, Rect, Shape, Vec2},
+    Color, Piet, RenderContext,
+};
+
+/// The shape of a marker drawn at a data point.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum MarkerShape {
+    Circle,
+    Square,
+    Triangle,
+    Diamond,
+    /// A diagonal cross (×). Always stroked.
+    Cross,
+    /// An upright cross (+). Always stroked.
+    Plus,
+}
+
+impl MarkerShape {
+    /// Whether this shape encloses an area that can be filled.
+    pub fn is_fillable(self) -> bool {
+        !matches!(self, MarkerShape::Cross | MarkerShape::Plus)
+    }
+
+    /// The outline of this shape, centered on `center`, fitting in a circle of radius `radius`.
+    pub fn path(self, center: Point, radius: f64) -> BezPath {
+        let Point { x, y } = center;
+        match self {
+            MarkerShape::Circle => Circle::new(center, radius).to_path(0.1),
+            MarkerShape::Square => {
+                // Use the same area as the circle would have, so sizes look consistent.
+                let half = radius * 0.886;
+                Rect::new(x - half, y - half, x + half, y + half).to_path(0.1)
+            }
+            MarkerShape::Triangle => {
+                let dx = radius * 0.866;
+                polygon(&[
+                    (x, y - radius),
+                    (x + dx, y + radius * 0.5),
+                    (x - dx, y + radius * 0.5),
+                ])
+            }
+            MarkerShape::Diamond => polygon(&[
+                (x, y - radius),
+                (x + radius, y),
+                (x, y + radius),
+                (x - radius, y),
+            ]),
+            MarkerShape::Cross => {
+                let d = radius * 0.707;
+                let mut path = BezPath::new();
+                path.move_to((x - d, y - d));
+                path.line_to((x + d, y + d));
+                path.move_to((x - d, y + d));
+                path.line_to((x + d, y - d));
+                path
+            }
+            MarkerShape::Plus => {
+                let mut path = BezPath::new();
+                path.move_to((x - radius, y));
+                path.line_to((x + radius, y));
+                path.move_to((x, y - radius));
+                path.line_to((x, y + radius));
+                path
+            }
+        }
+    }
+}
+
+/// The length of the arrows made by `overflow_marker`.
+const OVERFLOW_MARKER_SIZE: f64 = 7.;
+
+/// If `point` is outside `area`, an arrow on the edge of `area` pointing towards it.
+///
+/// Used to show that there are points outside the intervals a trace is drawn over.
+pub(crate) fn overflow_marker(point: Point, area: Rect) -> Option<BezPath> {
+    let tip = Point::new(
+        point.x.clamp(area.x0, area.x1),
+        point.y.clamp(area.y0, area.y1),
+    );
+    let direction = point - tip;
+    let len = direction.hypot();
+    if len == 0. {
+        return None;
+    }
+    let direction = direction / len;
+    let normal = Vec2::new(-direction.y, direction.x);
+    let base = tip - direction * OVERFLOW_MARKER_SIZE;
+    Some(polygon(&[
+        tip.into(),
+        (base + normal * OVERFLOW_MARKER_SIZE * 0.5).into(),
+        (base - normal * OVERFLOW_MARKER_SIZE * 0.5).into(),
+    ]))
+}
+
+/// How to draw a marker at each data point.
+#[derive(Debug, Clone)]
+pub struct Marker {
+    pub shape: MarkerShape,
+    /// The radius of the circle the marker fits in.
+    pub size: f64,
+    /// Whether to fill the marker (`true`) or stroke its outline (`false`).
+    ///
+    /// Shapes that can't be filled are always stroked.
+    pub fill: bool,
+    /// The line width used when stroking.
+    pub stroke_width: f64,
+}
+
+impl Marker {
+    pub fn new(shape: MarkerShape) -> Self {
+        Self {
+            shape,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_size(mut self, size: f64) -> Self {
+        self.size = size;
+        self
+    }
+
+    pub fn with_fill(mut self, fill: bool) -> Self {
+        self.fill = fill;
+        self
+    }
+
+    pub fn with_stroke_width(mut self, stroke_width: f64) -> Self {
+        self.stroke_width = stroke_width;
+        self
+    }
+
+    /// Draw the marker at `center`, using `size` rather than `self.size`.
+    pub fn draw_sized(&self, center: Point, size: f64, color: &Color, rc: &mut Piet) {
+        let path = self.shape.path(center, size);
+        if self.fill && self.shape.is_fillable() {
+            rc.fill(path, color);
+        } else {
+            rc.stroke(path, color, self.stroke_width);
+        }
+    }
+
+    /// Draw the marker at `center`.
+    pub fn draw(&self, center: Point, color: &Color, rc: &mut Piet) {
+        self.draw_sized(center, self.size, color, rc)
+    }
+}
+
+impl Default for Marker {
+    fn default() -> Self {
+        Self {
+            shape: MarkerShape::Circle,
+            size: 2.,
+            fill: true,
+            stroke_width: 1.,
+        }
+    }
+}
+
+/// A closed path through the given points.
+fn polygon(points: &[(f64, f64)]) -> BezPath {
+    let mut path = BezPath::new();
+    for (idx, &point) in points.iter().enumerate() {
+        if idx == 0 {
+            path.move_to(point);
+        } else {
+            path.line_to(point);
+        }
+    }
+    path.close_path();
+    path
+}
@@ -0,0 +1,108 @@
+//! Support for missing data.
+//!
+//! Missing values are represented by NaN. Infinite values can't be drawn either, so they are
+//! treated as missing too. Traces skip missing values: lines are broken, and points, bars and
+//! cells are not drawn. Intervals calculated from data skip them as well. Some traces can
+//! optionally mark where data is missing with a hatched area using `NoDataHatch`.
+use crate::theme;
+use piet_common::{
+    kurbo::{Line, Rect},
+    Color, Piet, RenderContext,
+};
+use std::ops::Range;
+
+/// A pattern of diagonal lines used to show where data is missing (NaN or infinite).
+#[derive(Debug, Clone)]
+pub struct NoDataHatch {
+    /// The color of the hatch lines.
+    pub color: Color,
+    /// The horizontal distance between hatch lines.
+    pub spacing: f64,
+    /// The width of the hatch lines.
+    pub stroke_width: f64,
+}
+
+impl NoDataHatch {
+    pub fn new() -> Self {
+        Self {
+            color: theme::NO_DATA_COLOR,
+            spacing: 6.,
+            stroke_width: 1.,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_spacing(mut self, spacing: f64) -> Self {
+        self.spacing = spacing;
+        self
+    }
+
+    /// Fill `rect` with the hatch pattern.
+    pub fn draw(&self, rect: Rect, rc: &mut Piet) {
+        let rect = rect.abs();
+        if rect.area() <= 0. || self.spacing <= 0. {
+            return;
+        }
+        rc.with_save(|rc| {
+            rc.clip(rect);
+            // 45° lines, starting far enough left that the whole rect is covered.
+            let mut x = rect.x0 - rect.height();
+            while x < rect.x1 {
+                rc.stroke(
+                    Line::new((x, rect.y1), (x + rect.height(), rect.y0)),
+                    &self.color,
+                    self.stroke_width,
+                );
+                x += self.spacing;
+            }
+            Ok(())
+        })
+        .unwrap();
+    }
+}
+
+impl Default for NoDataHatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Whether a value should be treated as missing: NaN or ±∞.
+#[inline]
+pub(crate) fn is_missing(value: f64) -> bool {
+    !value.is_finite()
+}
+
+/// The ranges of indices of `values` that are not missing (as defined by `missing`), in order.
+pub(crate) fn present_runs<T>(values: &[T], missing: impl Fn(&T) -> bool) -> Vec<Range<usize>> {
+    let mut runs = vec![];
+    let mut start = None;
+    for (idx, value) in values.iter().enumerate() {
+        match (missing(value), start) {
+            (false, None) => start = Some(idx),
+            (true, Some(s)) => {
+                runs.push(s..idx);
+                start = None;
+            }
+            _ => (),
+        }
+    }
+    if let Some(s) = start {
+        runs.push(s..values.len());
+    }
+    runs
+}
+
+#[test]
+fn test_present_runs() {
+    let values = [f64::NAN, 1., 2., f64::NAN, f64::NAN, 3., f64::NAN, 4.];
+    assert_eq!(
+        present_runs(&values, |v| is_missing(*v)),
+        vec![1..3, 5..6, 7..8]
+    );
+    assert!(present_runs(&[f64::NAN], |v| is_missing(*v)).is_empty());
+}
@@ -0,0 +1,460 @@
+use crate::{
+    histogram::auto_bar_width, missing::is_missing, prelude::*, theme, Chart, Error, GridStyle,
+    Interval, Trace,
+};
+use piet_common::{
+    kurbo::{Line, Rect, Size},
+    Color, Error as PietError, Piet, RenderContext,
+};
+use std::{any::Any, sync::Arc};
+
+/// The fraction of the chart height used for volume bars in `candlestick_with_volume`.
+const VOLUME_HEIGHT: f64 = 0.2;
+
+/// Open, high, low and close values for a period starting at `x`.
+///
+/// For time series, use a numeric timestamp (e.g. seconds since the epoch) for `x`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ohlc {
+    pub x: f64,
+    pub open: f64,
+    pub high: f64,
+    pub low: f64,
+    pub close: f64,
+}
+
+impl Ohlc {
+    pub fn new(x: f64, open: f64, high: f64, low: f64, close: f64) -> Self {
+        Self {
+            x,
+            open,
+            high,
+            low,
+            close,
+        }
+    }
+
+    /// Whether the value went up (or stayed the same) over the period.
+    pub fn is_up(&self) -> bool {
+        self.close >= self.open
+    }
+
+    /// Whether any of the values are missing.
+    fn is_missing(&self) -> bool {
+        [self.x, self.open, self.high, self.low, self.close]
+            .into_iter()
+            .any(is_missing)
+    }
+}
+
+/// Create a candlestick chart.
+pub fn candlestick(values: impl Into<Arc<[Ohlc]>>) -> Chart {
+    let values = values.into();
+    let (x_range, y_range) = ohlc_intervals(&values);
+    let y_range = y_range.to_rounded();
+    Chart::new()
+        .with_left_axis(y_range.ticker().reverse())
+        .with_left_grid(GridStyle::default())
+        .with_bottom_axis(x_range.ticker())
+        .with_trace(OhlcTrace::new(values, x_range, y_range))
+}
+
+/// Create a candlestick chart with a bar for the volume of each period along the bottom.
+///
+/// `volumes` are paired with `values` by index.
+///
+/// # Panics
+///
+/// Panics if there isn't a volume for each value.
+pub fn candlestick_with_volume(
+    values: impl Into<Arc<[Ohlc]>>,
+    volumes: impl Into<Arc<[f64]>>,
+) -> Chart {
+    match try_candlestick_with_volume(values, volumes) {
+        Ok(chart) => chart,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Like `candlestick_with_volume`, but returns an error if there isn't a volume for each value.
+pub fn try_candlestick_with_volume(
+    values: impl Into<Arc<[Ohlc]>>,
+    volumes: impl Into<Arc<[f64]>>,
+) -> Result<Chart, Error> {
+    let (values, volumes) = (values.into(), volumes.into());
+    if volumes.len() != values.len() {
+        return Err(Error::MismatchedLengths {
+            expected: values.len(),
+            found: volumes.len(),
+        });
+    }
+    let (x_range, y_range) = ohlc_intervals(&values);
+    // Make space at the bottom for the volume bars.
+    let y_range = y_range.to_rounded();
+    let y_range = Interval::new(
+        y_range.min() - y_range.size() * VOLUME_HEIGHT / (1. - VOLUME_HEIGHT),
+        y_range.max(),
+    );
+    let volume = VolumeTrace::new(values.clone(), volumes, x_range);
+    Ok(Chart::new()
+        .with_left_axis(y_range.ticker().reverse())
+        .with_left_grid(GridStyle::default())
+        .with_bottom_axis(x_range.ticker())
+        .with_trace(volume)
+        .with_trace(OhlcTrace::new(values, x_range, y_range)))
+}
+
+/// The x interval (padded by half a period at each end) and the y interval of some OHLC values.
+fn ohlc_intervals(values: &[Ohlc]) -> (Interval, Interval) {
+    let values: Vec<&Ohlc> = values.iter().filter(|v| !v.is_missing()).collect();
+    let x_range: Interval = values.iter().map(|v| v.x).collect();
+    let y_range: Interval = values.iter().flat_map(|v| [v.low, v.high]).collect();
+    if values.is_empty() {
+        return (x_range.to_nonempty(), y_range);
+    }
+    let pad = min_gap(values.iter().map(|v| v.x), 1.) * 0.5;
+    (
+        Interval::new(x_range.min() - pad, x_range.max() + pad),
+        y_range,
+    )
+}
+
+/// How to draw each period.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum OhlcStyle {
+    /// A box from open to close, with a line (wick) from low to high.
+    Candlestick,
+    /// A line from low to high, with ticks to the left for open and to the right for close.
+    Bars,
+}
+
+/// Draws open-high-low-close values as candlesticks or OHLC bars.
+///
+/// Periods with any NaN values are missing, and are not drawn.
+pub struct OhlcTrace {
+    /// The values for each period, sorted by x.
+    ///
+    /// Not public because we have retained state that depends on them.
+    values: Arc<[Ohlc]>,
+    /// The range that x values should be shown over
+    x_range: Interval,
+    /// The range that y values should be shown over
+    y_range: Interval,
+    /// How to draw each period.
+    pub style: OhlcStyle,
+    /// The color for periods where the value went up.
+    pub up_color: Color,
+    /// The color for periods where the value went down.
+    pub down_color: Color,
+    /// The width of the candle bodies (or bar ticks). Chosen from the spacing of the periods if
+    /// `None`.
+    pub body_width: Option<f64>,
+
+    // Retained
+    /// The size of the chart area.
+    pub size: Option<Size>,
+    /// The body width actually used.
+    resolved_body_width: f64,
+}
+
+impl OhlcTrace {
+    pub fn new(values: impl Into<Arc<[Ohlc]>>, x_range: Interval, y_range: Interval) -> Self {
+        Self {
+            values: values.into(),
+            x_range,
+            y_range,
+            style: OhlcStyle::Candlestick,
+            up_color: theme::UP_COLOR,
+            down_color: theme::DOWN_COLOR,
+            body_width: None,
+            size: None,
+            resolved_body_width: 0.,
+        }
+    }
+
+    pub fn with_style(mut self, style: OhlcStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn with_colors(mut self, up_color: Color, down_color: Color) -> Self {
+        self.up_color = up_color;
+        self.down_color = down_color;
+        self
+    }
+
+    pub fn with_body_width(mut self, body_width: f64) -> Self {
+        self.body_width = Some(body_width);
+        self
+    }
+
+    pub fn values(&self) -> &[Ohlc] {
+        &self.values
+    }
+
+    pub fn set_values(&mut self, new_values: impl Into<Arc<[Ohlc]>>) {
+        self.values = new_values.into();
+        self.size = None;
+    }
+
+    fn x_pos(&self, x: f64, size: Size) -> f64 {
+        self.x_range.t(x) * size.width
+    }
+
+    fn y_pos(&self, y: f64, size: Size) -> f64 {
+        (1. - self.y_range.t(y)) * size.height
+    }
+}
+
+/// The smallest non-zero distance between consecutive x positions, or `default` if there isn't
+/// one.
+fn min_gap(xs: impl Iterator<Item = f64>, default: f64) -> f64 {
+    let mut prev: Option<f64> = None;
+    let mut gap = f64::INFINITY;
+    for x in xs {
+        if let Some(prev) = prev {
+            let d = (x - prev).abs();
+            if d > 0. && d < gap {
+                gap = d;
+            }
+        }
+        prev = Some(x);
+    }
+    if gap.is_finite() {
+        gap
+    } else {
+        default
+    }
+}
+
+impl Trace for OhlcTrace {
+    fn layout(&mut self, size: Size, _rc: &mut Piet) -> Result<(), PietError> {
+        self.size = Some(size);
+        self.resolved_body_width = match self.body_width {
+            Some(width) => width,
+            None => {
+                let xs = self
+                    .values
+                    .iter()
+                    .filter(|v| !v.is_missing())
+                    .map(|v| self.x_pos(v.x, size));
+                auto_bar_width(min_gap(xs, size.width))
+            }
+        };
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        self.size.unwrap()
+    }
+
+    fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
+    fn draw(&self, rc: &mut Piet) {
+        let size = self.size.unwrap();
+        let half = self.resolved_body_width * 0.5;
+        for value in self.values.iter().filter(|v| !v.is_missing()) {
+            let color = if value.is_up() {
+                &self.up_color
+            } else {
+                &self.down_color
+            };
+            let x = self.x_pos(value.x, size);
+            let (open, close) = (self.y_pos(value.open, size), self.y_pos(value.close, size));
+            let wick = Line::new(
+                (x, self.y_pos(value.high, size)),
+                (x, self.y_pos(value.low, size)),
+            );
+            match self.style {
+                OhlcStyle::Candlestick => {
+                    rc.stroke(wick, color, 1.);
+                    let body = Rect::new(x - half, open, x + half, close).abs();
+                    // make sure flat candles are still visible.
+                    let body = if body.height() < 1. {
+                        body.inflate(0., 0.5)
+                    } else {
+                        body
+                    };
+                    rc.fill(body, color);
+                }
+                OhlcStyle::Bars => {
+                    rc.stroke(wick, color, 1.5);
+                    rc.stroke(Line::new((x - half, open), (x, open)), color, 1.5);
+                    rc.stroke(Line::new((x, close), (x + half, close)), color, 1.5);
+                }
+            }
+        }
+    }
+
+    fn set_intervals(&mut self, x_range: Interval, y_range: Interval) {
+        self.x_range = x_range;
+        self.y_range = y_range;
+        self.size = None;
+    }
+
+    fn has_intervals(&self) -> bool {
+        true
+    }
+
+    fn is_empty(&self) -> bool {
+        self.values.iter().all(Ohlc::is_missing)
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Draws a bar for the volume of each period along the bottom of the chart area, colored by
+/// whether the period went up or down.
+pub struct VolumeTrace {
+    /// The periods, used for x positions and colors.
+    ///
+    /// Not public because we have retained state that depends on them.
+    values: Arc<[Ohlc]>,
+    /// The volume for each period, paired with `values` by index.
+    volumes: Arc<[f64]>,
+    /// The range that x values should be shown over
+    x_range: Interval,
+    /// The fraction of the chart height that the largest volume bar takes.
+    pub height: f64,
+    pub up_color: Color,
+    pub down_color: Color,
+
+    // Retained
+    /// The size of the chart area.
+    pub size: Option<Size>,
+    bar_width: f64,
+}
+
+impl VolumeTrace {
+    pub fn new(
+        values: impl Into<Arc<[Ohlc]>>,
+        volumes: impl Into<Arc<[f64]>>,
+        x_range: Interval,
+    ) -> Self {
+        Self {
+            values: values.into(),
+            volumes: volumes.into(),
+            x_range,
+            height: VOLUME_HEIGHT,
+            up_color: theme::UP_COLOR.with_alpha(0.4),
+            down_color: theme::DOWN_COLOR.with_alpha(0.4),
+            size: None,
+            bar_width: 0.,
+        }
+    }
+}
+
+impl Trace for VolumeTrace {
+    fn layout(&mut self, size: Size, _rc: &mut Piet) -> Result<(), PietError> {
+        self.size = Some(size);
+        let xs = self
+            .values
+            .iter()
+            .filter(|v| !v.is_missing())
+            .map(|v| self.x_range.t(v.x) * size.width);
+        self.bar_width = auto_bar_width(min_gap(xs, size.width));
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        self.size.unwrap()
+    }
+
+    fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
+    fn draw(&self, rc: &mut Piet) {
+        let size = self.size.unwrap();
+        let max = self
+            .volumes
+            .iter()
+            .copied()
+            .filter(|v| !is_missing(*v))
+            .fold(0., f64::max);
+        if max <= 0. {
+            return;
+        }
+        let half = self.bar_width * 0.5;
+        for (value, &volume) in self.values.iter().zip(self.volumes.iter()) {
+            if value.is_missing() || is_missing(volume) {
+                continue;
+            }
+            let x = self.x_range.t(value.x) * size.width;
+            let top = size.height * (1. - self.height * volume / max);
+            let color = if value.is_up() {
+                &self.up_color
+            } else {
+                &self.down_color
+            };
+            rc.fill(Rect::new(x - half, top, x + half, size.height), color);
+        }
+    }
+
+    /// The bar heights are a fraction of the chart height, so only the x interval changes.
+    fn set_intervals(&mut self, x_range: Interval, _y_range: Interval) {
+        self.x_range = x_range;
+        self.size = None;
+    }
+
+    fn has_intervals(&self) -> bool {
+        true
+    }
+
+    fn is_empty(&self) -> bool {
+        self.volumes.iter().all(|&v| is_missing(v))
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[test]
+fn test_candlestick_degenerate_data() {
+    use crate::chart::test_degenerate;
+    let flat = Ohlc::new(1., 5., 5., 5., 5.);
+    test_degenerate(candlestick(vec![]), true);
+    let (x_range, y_range) = test_degenerate(candlestick(vec![flat]), false);
+    assert!(x_range.unwrap().contains(1.) && y_range.unwrap().contains(5.));
+    test_degenerate(
+        candlestick(vec![flat, Ohlc::new(2., 5., 5., 5., 5.)]),
+        false,
+    );
+    test_degenerate(candlestick_with_volume(vec![], vec![]), true);
+    test_degenerate(candlestick_with_volume(vec![flat], vec![0.]), false);
+    for volumes in [vec![], vec![1., 2.]] {
+        assert!(matches!(
+            try_candlestick_with_volume(vec![flat], volumes.clone()),
+            Err(Error::MismatchedLengths {
+                expected: 1,
+                found,
+            }) if found == volumes.len()
+        ));
+    }
+}
+
+#[test]
+fn test_volume_bars_match_candles() {
+    use crate::chart::with_test_context;
+    // The period at x = 2 is missing, so the narrowest gap between candles is 2, not 1.
+    let values = vec![
+        Ohlc::new(1., 1., 2., 0., 1.5),
+        Ohlc::new(2., f64::NAN, 2., 0., 1.5),
+        Ohlc::new(3., 1., 2., 0., 1.5),
+        Ohlc::new(5., 1., 2., 0., 1.5),
+    ];
+    let (x_range, y_range) = (Interval::new(0., 6.), Interval::new(0., 2.));
+    let mut candles = OhlcTrace::new(values.clone(), x_range, y_range);
+    let mut volumes = VolumeTrace::new(values, vec![1., 2., 3., 4.], x_range);
+    with_test_context(|rc| {
+        candles.layout(Size::new(600., 300.), rc)?;
+        volumes.layout(Size::new(600., 300.), rc)?;
+        Ok(())
+    });
+    assert_eq!(volumes.bar_width, candles.resolved_body_width);
+}
@@ -0,0 +1,682 @@
+//! Charts in polar coordinates, where values are drawn at an angle and distance from a center.
+//!
+//! Angles start at the top and go clockwise (like a compass).
+use crate::{
+    area::{stack, StackOffset},
+    missing::is_missing,
+    theme, Categorical, Error, GridStyle, Interval, IntervalTicker, Marker, Ticker, Trace,
+};
+use piet_common::{
+    kurbo::{Affine, BezPath, Circle, Line, Point, Size, Vec2},
+    Color, Error as PietError, Piet, PietTextLayout, RenderContext, Text, TextAttribute,
+    TextLayout, TextLayoutBuilder,
+};
+use std::{any::Any, f64::consts::TAU, fmt, sync::Arc};
+
+const LABEL_FONT_SIZE: f64 = 14.;
+/// The number of line segments used for a full circle.
+const CIRCLE_SEGMENTS: usize = 128;
+
+/// Create a radar (spider) chart, with a closed, filled line for each series.
+///
+/// Each series has a value for each category, paired by index. The radial axis starts at 0. Each
+/// series gets the next color from the theme.
+pub fn radar_chart<L, S>(
+    categories: impl Into<Categorical<L>>,
+    series: impl IntoIterator<Item = S>,
+) -> PolarChart
+where
+    L: fmt::Display,
+    S: Into<Arc<[f64]>>,
+{
+    let angular = AngularAxis::categorical(categories);
+    let series: Vec<Arc<[f64]>> = series.into_iter().map(Into::into).collect();
+    let radial_range = radial_range(series.iter().flat_map(|values| values.iter().copied()));
+    let scale = PolarScale::new(angular.range(), radial_range);
+    let mut chart = PolarChart::new(angular, scale.radial_range);
+    for (idx, values) in series.into_iter().enumerate() {
+        let points: Vec<(f64, f64)> = values
+            .iter()
+            .enumerate()
+            .map(|(category, &value)| (category as f64, value))
+            .collect();
+        chart = chart.with_trace(
+            PolarLineTrace::new(points, scale)
+                .with_color(theme::series_color(idx))
+                .with_closed(true)
+                .with_fill_opacity(theme::AREA_FILL_OPACITY),
+        );
+    }
+    chart
+}
+
+/// Create a wind rose: a stacked bar for each direction, with a layer for each series (e.g.
+/// ranges of wind speed).
+///
+/// Each series has a value for each direction, paired by index. Missing (NaN) values count as 0.
+/// Each series gets the next color from the theme.
+pub fn wind_rose<L, S>(
+    directions: impl Into<Categorical<L>>,
+    series: impl IntoIterator<Item = S>,
+) -> PolarChart
+where
+    L: fmt::Display,
+    S: Into<Arc<[f64]>>,
+{
+    let angular = AngularAxis::categorical(directions);
+    let series: Vec<Arc<[f64]>> = series
+        .into_iter()
+        .map(|values| {
+            let values: Arc<[f64]> = values.into();
+            values
+                .iter()
+                .map(|&v| if is_missing(v) { 0. } else { v })
+                .collect()
+        })
+        .collect();
+    let stacked = stack(&series, StackOffset::Zero);
+    let radial_range = radial_range(
+        stacked
+            .iter()
+            .flat_map(|layer| layer.iter().map(|(_, upper)| *upper)),
+    );
+    let scale = PolarScale::new(angular.range(), radial_range);
+    let mut chart = PolarChart::new(angular, radial_range);
+    for (idx, layer) in stacked.into_iter().enumerate() {
+        chart =
+            chart.with_trace(PolarBarTrace::new(layer, scale).with_color(theme::series_color(idx)));
+    }
+    chart
+}
+
+/// A rounded radial range from 0 that covers `values`.
+///
+/// Falls back to `0..1` if there are no positive values.
+fn radial_range(values: impl Iterator<Item = f64>) -> Interval {
+    let rounded = values.collect::<Interval>().include_zero().to_rounded();
+    Interval::try_new(0., rounded.max()).unwrap_or_else(|_| Interval::new(0., 1.))
+}
+
+/// The angular axis of a polar chart.
+#[derive(Debug, Clone)]
+pub enum AngularAxis {
+    /// A numeric range mapped to one full turn (e.g. `0..360` for compass degrees).
+    Numeric(Interval),
+    /// Categories evenly spaced around the circle, starting at the top. Category `i` has angular
+    /// value `i`.
+    Categorical(Vec<String>),
+}
+
+impl AngularAxis {
+    /// An angular axis with the given categories.
+    pub fn categorical<L: fmt::Display>(categories: impl Into<Categorical<L>>) -> Self {
+        let categories = categories.into();
+        AngularAxis::Categorical(
+            categories
+                .categories()
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+        )
+    }
+
+    /// The range of angular values that make up one full turn.
+    pub fn range(&self) -> Interval {
+        match self {
+            AngularAxis::Numeric(range) => *range,
+            AngularAxis::Categorical(categories) => {
+                Interval::new(0., categories.len().max(1) as f64)
+            }
+        }
+    }
+}
+
+/// Converts between `(angle, radius)` data values and positions in the chart area.
+///
+/// The plot is a circle filling as much of the chart area as possible.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct PolarScale {
+    /// The angular values that make up one full turn.
+    pub angular_range: Interval,
+    /// The radial values from the center to the edge.
+    pub radial_range: Interval,
+}
+
+impl PolarScale {
+    pub fn new(angular_range: Interval, radial_range: Interval) -> Self {
+        Self {
+            angular_range,
+            radial_range,
+        }
+    }
+
+    /// The center of the plot in a chart area of `size`.
+    pub fn center(&self, size: Size) -> Point {
+        (size.to_vec2() * 0.5).to_point()
+    }
+
+    /// The radius of the plot in a chart area of `size`.
+    pub fn radius(&self, size: Size) -> f64 {
+        size.min_side() * 0.5
+    }
+
+    /// The angle in radians, clockwise from the top, of an angular value.
+    pub fn angle(&self, angle: f64) -> f64 {
+        self.angular_range.t(angle) * TAU
+    }
+
+    /// The position of a point at angular value `angle` and radial value `r`.
+    pub fn to_point(&self, angle: f64, r: f64, size: Size) -> Point {
+        let distance = self.radial_range.t(r) * self.radius(size);
+        self.center(size) + direction(self.angle(angle)) * distance
+    }
+}
+
+/// The unit vector at `angle` radians clockwise from the top.
+fn direction(angle: f64) -> Vec2 {
+    Vec2::new(angle.sin(), -angle.cos())
+}
+
+fn build_label(text: String, rc: &mut Piet) -> Result<PietTextLayout, PietError> {
+    rc.text()
+        .new_text_layout(text)
+        .default_attribute(TextAttribute::FontSize(LABEL_FONT_SIZE))
+        .default_attribute(TextAttribute::TextColor(theme::AXES_COLOR))
+        .build()
+}
+
+/// A label drawn outside or inside the plot.
+struct PolarLabel {
+    pos: Point,
+    layout: PietTextLayout,
+}
+
+/// A chart in polar coordinates, with an angular axis around the edge and a radial axis from the
+/// center.
+///
+/// Traces are laid out with a square chart area the size of the plot, and should use a
+/// `PolarScale` to position their points.
+pub struct PolarChart {
+    angular: AngularAxis,
+    radial_range: Interval,
+    /// Chooses the radial gridlines and labels.
+    radial_ticker: IntervalTicker,
+    /// The style of the circular gridlines and spokes.
+    pub grid: GridStyle,
+    traces: Vec<Box<dyn Trace>>,
+
+    // Retained
+    /// The size that everything should fit in (inc. labels).
+    size: Option<Size>,
+    /// The square the plot is drawn in.
+    plot_origin: Point,
+    plot_size: Size,
+    /// The angular values of the spokes, and their labels.
+    spokes: Vec<f64>,
+    labels: Vec<PolarLabel>,
+}
+
+impl PolarChart {
+    pub fn new(angular: AngularAxis, radial_range: Interval) -> Self {
+        Self {
+            angular,
+            radial_range,
+            radial_ticker: radial_range.ticker(),
+            grid: GridStyle::default(),
+            traces: vec![],
+            size: None,
+            plot_origin: Point::ZERO,
+            plot_size: Size::ZERO,
+            spokes: vec![],
+            labels: vec![],
+        }
+    }
+
+    pub fn with_grid(mut self, grid: GridStyle) -> Self {
+        self.grid = grid;
+        self
+    }
+
+    pub fn with_trace(mut self, trace: impl Trace + 'static) -> Self {
+        self.traces.push(Box::new(trace));
+        self
+    }
+
+    pub fn traces_mut<T: Trace>(&mut self) -> impl Iterator<Item = &mut T> {
+        self.traces
+            .iter_mut()
+            .filter_map(|trace| trace.as_any().downcast_mut())
+    }
+
+    /// The scale traces on this chart should use.
+    pub fn scale(&self) -> PolarScale {
+        PolarScale::new(self.angular.range(), self.radial_range)
+    }
+
+    /// # Panics
+    ///
+    /// Will panic if `layout` has not been called.
+    pub fn size(&self) -> Size {
+        match self.try_size() {
+            Ok(size) => size,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Like `size`, but returns an error rather than panicking if `layout` hasn't been called.
+    pub fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
+    /// The angular values and labels of the spokes, for a circle with circumference
+    /// `circumference`.
+    fn angular_ticks(&self, circumference: f64) -> Vec<(f64, String)> {
+        match &self.angular {
+            AngularAxis::Numeric(range) => {
+                let mut ticker = range.ticker();
+                ticker.layout(circumference);
+                ticker
+                    .ticks()
+                    .map(|tick| {
+                        (
+                            range.min() + tick.pos / circumference * range.size(),
+                            tick.label,
+                        )
+                    })
+                    // The end of the range is the same angle as the start.
+                    .filter(|(value, _)| *value < range.max())
+                    .map(|(value, label)| (value, label.to_string()))
+                    .collect()
+            }
+            AngularAxis::Categorical(categories) => categories
+                .iter()
+                .enumerate()
+                .map(|(idx, label)| (idx as f64, label.clone()))
+                .collect(),
+        }
+    }
+
+    /// Lay out the labels and calculate the plot area available.
+    ///
+    /// Once the plot area has been calculated, each trace will have its `layout` method called.
+    pub fn layout(&mut self, size: Size, rc: &mut Piet) -> Result<(), PietError> {
+        self.size = Some(size);
+        let scale = self.scale();
+
+        // Make room for the largest angular label outside the circle. The spokes depend on the
+        // circumference, so start with a guess and refine it once.
+        let mut radius = size.min_side() * 0.5;
+        let mut ticks = vec![];
+        let mut layouts = vec![];
+        for _ in 0..2 {
+            ticks = self.angular_ticks(TAU * radius);
+            layouts = ticks
+                .iter()
+                .map(|(_, label)| build_label(label.clone(), rc))
+                .collect::<Result<Vec<_>, _>>()?;
+            let largest = layouts
+                .iter()
+                .map(|layout| layout.size().width.max(layout.size().height))
+                .fold(0., f64::max);
+            radius = (size.min_side() * 0.5 - largest - theme::MARGIN).max(0.);
+        }
+        let center = (size.to_vec2() * 0.5).to_point();
+        self.plot_size = Size::new(radius * 2., radius * 2.);
+        self.plot_origin = center - self.plot_size.to_vec2() * 0.5;
+
+        self.labels.clear();
+        self.spokes.clear();
+        for ((value, _), layout) in ticks.into_iter().zip(layouts) {
+            let dir = direction(scale.angle(value));
+            let anchor = center + dir * (radius + theme::MARGIN * 0.5);
+            // Push the label out so its nearest edge touches the anchor.
+            let half = layout.size().to_vec2() * 0.5;
+            let pos = anchor + Vec2::new(dir.x * half.x, dir.y * half.y) - half;
+            self.labels.push(PolarLabel { pos, layout });
+            self.spokes.push(value);
+        }
+
+        // Radial labels, along the first spoke.
+        self.radial_ticker.layout(radius);
+        for tick in self.radial_ticker.ticks().collect::<Vec<_>>() {
+            let layout = build_label(tick.label.to_string(), rc)?;
+            let pos = center + Vec2::new(4., -tick.pos - layout.size().height);
+            self.labels.push(PolarLabel { pos, layout });
+        }
+
+        for trace in &mut self.traces {
+            trace.layout(self.plot_size, rc)?;
+        }
+        Ok(())
+    }
+
+    /// Draw the chart at (0,0).
+    ///
+    /// # Panics
+    ///
+    /// Panics if the chart hasn't been laid out since it last changed, or if the render context
+    /// returns an error.
+    pub fn draw(&self, rc: &mut Piet) {
+        if let Err(e) = self.try_draw(rc) {
+            panic!("{}", e);
+        }
+    }
+
+    /// Draw the chart at (0,0), returning an error if the render context does.
+    ///
+    /// Returns `Error::LayoutNotPerformed` (without drawing anything) if the chart or any of its
+    /// traces hasn't been laid out since it last changed.
+    pub fn try_draw(&self, rc: &mut Piet) -> Result<(), Error> {
+        self.try_size()?;
+        for trace in &self.traces {
+            trace.try_size()?;
+        }
+        let scale = self.scale();
+        let size = self.plot_size;
+        rc.with_save(|rc| {
+            rc.transform(Affine::translate(self.plot_origin.to_vec2()));
+            // circular gridlines
+            let center = scale.center(size);
+            for tick in self.radial_ticker.ticks() {
+                rc.stroke(
+                    Circle::new(center, tick.pos),
+                    &self.grid.color,
+                    self.grid.stroke_width,
+                );
+            }
+            // spokes
+            for &value in &self.spokes {
+                rc.stroke(
+                    Line::new(center, scale.to_point(value, self.radial_range.max(), size)),
+                    &self.grid.color,
+                    self.grid.stroke_width,
+                );
+            }
+            rc.stroke(
+                Circle::new(center, scale.radius(size)),
+                &theme::AXES_COLOR,
+                1.,
+            );
+            for trace in &self.traces {
+                trace.draw(rc);
+            }
+            Ok(())
+        })?;
+        for label in &self.labels {
+            rc.draw_text(&label.layout, label.pos);
+        }
+        Ok(())
+    }
+}
+
+/// A line through `(angle, radius)` points, optionally closed and filled (a radar chart).
+///
+/// Points with a NaN coordinate are missing, and are skipped.
+pub struct PolarLineTrace {
+    /// The `(angle, radius)` points of the line.
+    ///
+    /// Not public because we have retained state that depends on them.
+    values: Arc<[(f64, f64)]>,
+    scale: PolarScale,
+    /// The line color.
+    pub color: Color,
+    /// The line width.
+    pub stroke_width: f64,
+    /// Whether to join the last point back to the first.
+    pub closed: bool,
+    /// The opacity of the fill inside a closed line. Set to 0 to not fill it.
+    pub fill_opacity: f64,
+    /// An optional marker to draw at each point.
+    pub marker: Option<Marker>,
+
+    // Retained
+    /// The size of the chart area.
+    pub size: Option<Size>,
+    path: BezPath,
+}
+
+impl PolarLineTrace {
+    pub fn new(values: impl Into<Arc<[(f64, f64)]>>, scale: PolarScale) -> Self {
+        Self {
+            values: values.into(),
+            scale,
+            color: theme::series_color(0),
+            stroke_width: 2.,
+            closed: false,
+            fill_opacity: 0.,
+            marker: None,
+            size: None,
+            path: BezPath::new(),
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_closed(mut self, closed: bool) -> Self {
+        self.closed = closed;
+        self
+    }
+
+    pub fn with_fill_opacity(mut self, fill_opacity: f64) -> Self {
+        self.fill_opacity = fill_opacity;
+        self
+    }
+
+    pub fn with_marker(mut self, marker: Marker) -> Self {
+        self.marker = Some(marker);
+        self
+    }
+
+    pub fn values(&self) -> &[(f64, f64)] {
+        &self.values
+    }
+
+    pub fn set_values(&mut self, new_values: impl Into<Arc<[(f64, f64)]>>) {
+        self.values = new_values.into();
+        self.size = None;
+    }
+
+    fn points(&self, size: Size) -> impl Iterator<Item = Point> + '_ {
+        self.values
+            .iter()
+            .filter(|(angle, r)| !is_missing(*angle) && !is_missing(*r))
+            .map(move |&(angle, r)| self.scale.to_point(angle, r, size))
+    }
+}
+
+impl Trace for PolarLineTrace {
+    fn layout(&mut self, size: Size, _rc: &mut Piet) -> Result<(), PietError> {
+        self.size = Some(size);
+        let mut path = BezPath::new();
+        for (idx, point) in self.points(size).enumerate() {
+            if idx == 0 {
+                path.move_to(point);
+            } else {
+                path.line_to(point);
+            }
+        }
+        if self.closed {
+            path.close_path();
+        }
+        self.path = path;
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        self.size.unwrap()
+    }
+
+    fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
+    fn draw(&self, rc: &mut Piet) {
+        if self.closed && self.fill_opacity > 0. {
+            rc.fill(
+                &self.path,
+                &self.color.clone().with_alpha(self.fill_opacity),
+            );
+        }
+        rc.stroke(&self.path, &self.color, self.stroke_width);
+        if let Some(marker) = &self.marker {
+            for point in self.points(self.size.unwrap()) {
+                marker.draw(point, &self.color, rc);
+            }
+        }
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// Bars radiating from the center, one per category (e.g. one layer of a wind rose).
+///
+/// Bar `i` is centered on angular value `i`, to match a categorical `AngularAxis`. Each bar spans
+/// `(lower, upper)` radial values, so layers can be stacked.
+pub struct PolarBarTrace {
+    /// The `(lower, upper)` radial values of the bar for each category.
+    ///
+    /// Not public because we have retained state that depends on them.
+    values: Arc<[(f64, f64)]>,
+    scale: PolarScale,
+    /// The bar color.
+    pub color: Color,
+    /// The fraction of each category's angle that the bar covers.
+    pub bar_fraction: f64,
+
+    // Retained
+    /// The size of the chart area.
+    pub size: Option<Size>,
+    bars: Vec<BezPath>,
+}
+
+impl PolarBarTrace {
+    pub fn new(values: impl Into<Arc<[(f64, f64)]>>, scale: PolarScale) -> Self {
+        Self {
+            values: values.into(),
+            scale,
+            color: theme::series_color(0),
+            bar_fraction: 0.8,
+            size: None,
+            bars: vec![],
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn values(&self) -> &[(f64, f64)] {
+        &self.values
+    }
+
+    pub fn set_values(&mut self, new_values: impl Into<Arc<[(f64, f64)]>>) {
+        self.values = new_values.into();
+        self.size = None;
+    }
+
+    /// The outline of an annular sector.
+    fn sector(
+        &self,
+        center: f64,
+        half_width: f64,
+        (lower, upper): (f64, f64),
+        size: Size,
+    ) -> BezPath {
+        let turn = half_width * 2. / self.scale.angular_range.size();
+        let segments = ((turn * CIRCLE_SEGMENTS as f64).ceil() as usize).max(2);
+        let step = half_width * 2. / segments as f64;
+        let mut path = BezPath::new();
+        for idx in 0..=segments {
+            let angle = center - half_width + step * idx as f64;
+            let point = self.scale.to_point(angle, upper, size);
+            if idx == 0 {
+                path.move_to(point);
+            } else {
+                path.line_to(point);
+            }
+        }
+        for idx in (0..=segments).rev() {
+            let angle = center - half_width + step * idx as f64;
+            path.line_to(self.scale.to_point(angle, lower, size));
+        }
+        path.close_path();
+        path
+    }
+}
+
+impl Trace for PolarBarTrace {
+    fn layout(&mut self, size: Size, _rc: &mut Piet) -> Result<(), PietError> {
+        self.size = Some(size);
+        let half_width = self.bar_fraction * 0.5;
+        self.bars = self
+            .values
+            .iter()
+            .enumerate()
+            .filter(|(_, (lower, upper))| {
+                !is_missing(*lower) && !is_missing(*upper) && upper > lower
+            })
+            .map(|(idx, &range)| self.sector(idx as f64, half_width, range, size))
+            .collect();
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        self.size.unwrap()
+    }
+
+    fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
+    fn draw(&self, rc: &mut Piet) {
+        for bar in &self.bars {
+            rc.fill(bar, &self.color.clone().with_alpha(0.8));
+            rc.stroke(bar, &self.color, 1.);
+        }
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[test]
+fn test_polar_scale() {
+    let scale = PolarScale::new(Interval::new(0., 360.), Interval::new(0., 10.));
+    let size = Size::new(200., 100.);
+    let top = scale.to_point(0., 10., size);
+    assert!((top - Point::new(100., 0.)).hypot() < 1e-9);
+    let right = scale.to_point(90., 5., size);
+    assert!((right - Point::new(125., 50.)).hypot() < 1e-9);
+}
+
+#[test]
+fn test_polar_degenerate_data() {
+    use crate::chart::with_test_context;
+    let no_series: Vec<Vec<f64>> = vec![];
+    let charts = [
+        (radar_chart(Vec::<&str>::new(), no_series.clone()), true),
+        (radar_chart(vec!["a"], [vec![1.]]), false),
+        (radar_chart(vec!["a", "b"], [vec![0., 0.]]), false),
+        (radar_chart(vec!["a"], [vec![-1.]]), false),
+        (wind_rose(vec!["N"], [vec![-1.]]), false),
+        (wind_rose(Vec::<&str>::new(), no_series), true),
+        (wind_rose(vec!["N", "S"], [vec![2., 2.]]), false),
+    ];
+    for (mut chart, empty) in charts {
+        with_test_context(|rc| {
+            assert!(matches!(chart.try_draw(rc), Err(Error::LayoutNotPerformed)));
+            chart.layout(Size::new(400., 300.), rc)?;
+            chart.draw(rc);
+            Ok(())
+        });
+        assert!(chart.radial_range.is_valid() && chart.radial_range.min() == 0.);
+        assert_eq!(chart.traces.iter().all(|trace| trace.is_empty()), empty);
+    }
+}
@@ -0,0 +1,137 @@
+//! Quantile–quantile plots.
+use crate::{
+    line::LineTrace,
+    prelude::*,
+    scatter::ScatterTrace,
+    stats::{normal_quantile, quantile, sorted_present},
+    theme, Chart, GridStyle, Interval,
+};
+use std::sync::Arc;
+
+/// Create a Q–Q plot of `sample` against `reference`, with a reference line.
+///
+/// Points close to the line mean the sample has the same shape of distribution as the reference.
+/// NaN values are skipped.
+pub fn qq_plot(sample: &[f64], reference: QqReference) -> Chart {
+    let points = qq_points(sample, &reference);
+    let (x_range, y_range): (Interval, Interval) = points.iter().copied().unzip();
+    let (x_range, y_range) = (x_range.to_rounded(), y_range.to_rounded());
+    let line = reference_line(&points, &reference, x_range, y_range);
+    Chart::new()
+        .with_left_axis(y_range.ticker().reverse())
+        .with_left_grid(GridStyle::default())
+        .with_bottom_axis(x_range.ticker())
+        .with_bottom_grid(GridStyle::default())
+        .with_trace(
+            LineTrace::new(line, x_range, y_range)
+                .with_color(theme::AXES_COLOR)
+                .with_stroke_width(1.)
+                .with_dash(&[4., 4.]),
+        )
+        .with_trace(ScatterTrace::new(points, x_range, y_range))
+}
+
+/// The distribution a sample is compared against in a Q–Q plot.
+#[derive(Debug, Clone)]
+pub enum QqReference {
+    /// The standard normal distribution.
+    ///
+    /// The reference line passes through the first and third quartiles, so it shows how close the
+    /// sample is to *some* normal distribution.
+    Normal,
+    /// Another sample. NaN values are skipped.
+    ///
+    /// The reference line is `y = x`, so it shows whether the samples have the same distribution.
+    Sample(Arc<[f64]>),
+}
+
+/// The `(reference quantile, sample quantile)` points of a Q–Q plot.
+///
+/// Against a normal distribution there is a point for each value in `sample`, plotted at
+/// probability `(i + 0.5) / n`. Against another sample there is a point for each value in the
+/// smaller sample, with quantiles of the larger sample interpolated to match.
+pub fn qq_points(sample: &[f64], reference: &QqReference) -> Vec<(f64, f64)> {
+    let sample = sorted_present(sample);
+    match reference {
+        QqReference::Normal => {
+            let n = sample.len() as f64;
+            sample
+                .iter()
+                .enumerate()
+                .map(|(idx, &value)| (normal_quantile((idx as f64 + 0.5) / n), value))
+                .collect()
+        }
+        QqReference::Sample(other) => {
+            let other = sorted_present(other);
+            let len = sample.len().min(other.len());
+            if len < 2 {
+                return (0..len).map(|idx| (other[idx], sample[idx])).collect();
+            }
+            (0..len)
+                .map(|idx| {
+                    // With equal sized samples this pairs up the sorted values exactly.
+                    let p = idx as f64 / (len - 1) as f64;
+                    (quantile(&other, p), quantile(&sample, p))
+                })
+                .collect()
+        }
+    }
+}
+
+/// The ends of the reference line for some Q–Q points, across the chart.
+fn reference_line(
+    points: &[(f64, f64)],
+    reference: &QqReference,
+    x_range: Interval,
+    y_range: Interval,
+) -> Vec<(f64, f64)> {
+    let (x0, x1) = x_range.as_tuple();
+    match reference {
+        QqReference::Normal => {
+            let samples: Vec<f64> = points.iter().map(|(_, y)| *y).collect();
+            let (q1, q3) = (quantile(&samples, 0.25), quantile(&samples, 0.75));
+            let (z1, z3) = (normal_quantile(0.25), normal_quantile(0.75));
+            let slope = (q3 - q1) / (z3 - z1);
+            let intercept = q1 - slope * z1;
+            vec![(x0, intercept + slope * x0), (x1, intercept + slope * x1)]
+        }
+        QqReference::Sample(_) => {
+            // Only draw `y = x` where it is inside both ranges.
+            let start = x0.max(y_range.min());
+            let end = x1.min(y_range.max());
+            if start < end {
+                vec![(start, start), (end, end)]
+            } else {
+                vec![]
+            }
+        }
+    }
+}
+
+#[test]
+fn test_qq_points() {
+    let points = qq_points(
+        &[3., 1., 2.],
+        &QqReference::Sample(vec![10., 30., 20.].into()),
+    );
+    assert_eq!(points, vec![(10., 1.), (20., 2.), (30., 3.)]);
+
+    let points = qq_points(&[1., f64::NAN, 2.], &QqReference::Normal);
+    assert_eq!(points.len(), 2);
+    assert!(points[0].0 < 0. && points[1].0 > 0.);
+}
+
+#[test]
+fn test_qq_plot_degenerate_data() {
+    use crate::chart::test_degenerate;
+    test_degenerate(qq_plot(&[], QqReference::Normal), true);
+    let (_, y_range) = test_degenerate(qq_plot(&[1.], QqReference::Normal), false);
+    assert!(y_range.unwrap().contains(1.));
+    let (_, y_range) = test_degenerate(qq_plot(&[2., 2.], QqReference::Normal), false);
+    assert!(y_range.unwrap().contains(2.));
+    let (x_range, _) = test_degenerate(
+        qq_plot(&[2., 2.], QqReference::Sample(vec![2.].into())),
+        false,
+    );
+    assert!(x_range.unwrap().contains(2.));
+}
@@ -0,0 +1,679 @@
+//! Fitted trend lines for `(x, y)` data.
+use crate::{missing::is_missing, stats::t_quantile, theme, Error, Interval, Trace};
+use piet_common::{
+    kurbo::{BezPath, Point, Size},
+    Color, Error as PietError, Piet, RenderContext,
+};
+use std::any::Any;
+
+/// The number of points a fitted curve is evaluated at.
+const FIT_SAMPLES: usize = 100;
+
+/// How to fit a curve to some points.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum FitMethod {
+    /// An ordinary least squares straight line.
+    Linear,
+    /// An ordinary least squares polynomial of the given degree.
+    Polynomial(usize),
+    /// Locally weighted linear regression (LOESS), using the nearest `span` fraction of the
+    /// points with tricube weights for each fitted value. A span of 0.75 is a good default.
+    ///
+    /// The fit is evaluated at 100 evenly spaced x values, and interpolated between them.
+    Loess { span: f64 },
+}
+
+/// The fitted model.
+#[derive(Debug, Clone)]
+enum Model {
+    /// A polynomial in `(x - center) / scale` (which keeps the least squares problem well
+    /// conditioned), with the inverse of its normal matrix for standard errors.
+    Polynomial {
+        center: f64,
+        scale: f64,
+        coefficients: Vec<f64>,
+        inverse: Vec<Vec<f64>>,
+    },
+    /// LOESS is evaluated at `FIT_SAMPLES` evenly spaced x values across the domain, and
+    /// linearly interpolated between them.
+    Loess(Vec<LoessSample>),
+}
+
+/// The LOESS fit at one x value.
+#[derive(Debug, Clone, Copy)]
+struct LoessSample {
+    x: f64,
+    fitted: f64,
+    /// The sum of the squared weights of the y values, for the standard error.
+    leverage: f64,
+}
+
+/// A curve fitted to some points.
+#[derive(Debug, Clone)]
+pub struct Fit {
+    method: FitMethod,
+    model: Model,
+    /// The range of the x values that were fitted.
+    domain: Interval,
+    r_squared: f64,
+    /// The residual standard error.
+    sigma: f64,
+    /// The residual degrees of freedom.
+    dof: f64,
+}
+
+impl Fit {
+    /// Fit a curve to `values` using `method`. Points with a NaN coordinate are skipped.
+    ///
+    /// Returns `None` if there aren't enough distinct points for the method.
+    pub fn new(values: &[(f64, f64)], method: FitMethod) -> Option<Self> {
+        let points: Vec<(f64, f64)> = values
+            .iter()
+            .copied()
+            .filter(|(x, y)| !is_missing(*x) && !is_missing(*y))
+            .collect();
+        let domain: Interval = points.iter().map(|(x, _)| *x).collect();
+        if points.len() < 2 || !domain.is_valid() {
+            return None;
+        }
+        let (model, dof) = match method {
+            FitMethod::Linear => fit_polynomial(&points, 1)?,
+            FitMethod::Polynomial(degree) => fit_polynomial(&points, degree)?,
+            FitMethod::Loess { span } => fit_loess(&points, span, domain),
+        };
+        let mut fit = Fit {
+            method,
+            model,
+            domain,
+            r_squared: f64::NAN,
+            sigma: f64::NAN,
+            dof,
+        };
+        let n = points.len() as f64;
+        let mean = points.iter().map(|(_, y)| y).sum::<f64>() / n;
+        let (mut rss, mut tss) = (0., 0.);
+        for &(x, y) in &points {
+            rss += (y - fit.predict(x)).powi(2);
+            tss += (y - mean).powi(2);
+        }
+        fit.r_squared = if tss > 0. { 1. - rss / tss } else { 1. };
+        if dof > 0. {
+            fit.sigma = (rss / dof).sqrt();
+        }
+        Some(fit)
+    }
+
+    pub fn method(&self) -> FitMethod {
+        self.method
+    }
+
+    /// The range of x values that were fitted.
+    pub fn domain(&self) -> Interval {
+        self.domain
+    }
+
+    /// The coefficient of determination.
+    pub fn r_squared(&self) -> f64 {
+        self.r_squared
+    }
+
+    /// The fitted value at `x`.
+    pub fn predict(&self, x: f64) -> f64 {
+        match &self.model {
+            Model::Polynomial {
+                center,
+                scale,
+                coefficients,
+                ..
+            } => {
+                let t = (x - center) / scale;
+                coefficients.iter().rev().fold(0., |acc, c| acc * t + c)
+            }
+            Model::Loess(samples) => interpolate(samples, x, |sample| sample.fitted),
+        }
+    }
+
+    /// The standard error of the fitted value at `x`, or NaN if there are too few points to
+    /// estimate it.
+    fn std_error(&self, x: f64) -> f64 {
+        let leverage = match &self.model {
+            Model::Polynomial {
+                center,
+                scale,
+                inverse,
+                ..
+            } => {
+                let t = (x - center) / scale;
+                let powers: Vec<f64> = (0..inverse.len()).map(|k| t.powi(k as i32)).collect();
+                let mut sum = 0.;
+                for (row, pi) in inverse.iter().zip(&powers) {
+                    for (value, pj) in row.iter().zip(&powers) {
+                        sum += pi * value * pj;
+                    }
+                }
+                sum
+            }
+            Model::Loess(samples) => interpolate(samples, x, |sample| sample.leverage),
+        };
+        self.sigma * leverage.max(0.).sqrt()
+    }
+
+    /// The confidence interval for the fitted value at `x`, at confidence `level` (e.g. 0.95).
+    ///
+    /// Returns `None` if there are too few points to estimate it (fewer than 1 degree of freedom
+    /// left after fitting).
+    pub fn confidence(&self, x: f64, level: f64) -> Option<(f64, f64)> {
+        let margin = t_quantile(0.5 + level * 0.5, self.dof) * self.std_error(x);
+        if is_missing(margin) {
+            return None;
+        }
+        let y = self.predict(x);
+        Some((y - margin, y + margin))
+    }
+
+    /// The coefficients of the fitted polynomial, from the constant term up, or `None` for LOESS.
+    pub fn coefficients(&self) -> Option<Vec<f64>> {
+        match &self.model {
+            Model::Polynomial {
+                center,
+                scale,
+                coefficients,
+                ..
+            } => Some(expand_polynomial(coefficients, *center, *scale)),
+            Model::Loess(_) => None,
+        }
+    }
+
+    /// The fitted equation, e.g. `y = 2.5x - 1`, or `None` for LOESS.
+    pub fn equation(&self) -> Option<String> {
+        let coefficients = self.coefficients()?;
+        // Treat coefficients that are rounding error compared to the others as 0.
+        let largest = coefficients.iter().map(|c| c.abs()).fold(0., f64::max);
+        let mut out = String::from("y =");
+        let mut first = true;
+        for (power, &c) in coefficients.iter().enumerate().rev() {
+            let c = if c.abs() <= largest * 1e-10 { 0. } else { c };
+            let text = format_coefficient(c.abs());
+            if text == "0" && !(first && power == 0) {
+                continue;
+            }
+            let sign = match (first, c < 0.) {
+                (true, true) => " -",
+                (true, false) => " ",
+                (false, true) => " - ",
+                (false, false) => " + ",
+            };
+            let term = match power {
+                0 => text,
+                _ if text == "1" => x_power(power),
+                _ => format!("{}{}", text, x_power(power)),
+            };
+            out.push_str(sign);
+            out.push_str(&term);
+            first = false;
+        }
+        Some(out)
+    }
+
+    /// A description of the fit suitable for a legend, e.g. `y = 2.5x - 1, R² = 0.93`.
+    pub fn label(&self) -> String {
+        let description = match self.method {
+            FitMethod::Loess { span } => format!("LOESS (span {})", span),
+            _ => self.equation().unwrap_or_default(),
+        };
+        format!("{}, R² = {:.3}", description, self.r_squared)
+    }
+}
+
+/// Least squares fit of a polynomial of `degree`, returning the model and residual degrees of
+/// freedom.
+fn fit_polynomial(points: &[(f64, f64)], degree: usize) -> Option<(Model, f64)> {
+    let terms = degree + 1;
+    if points.len() < terms {
+        return None;
+    }
+    let n = points.len() as f64;
+    let center = points.iter().map(|(x, _)| x).sum::<f64>() / n;
+    let scale = points
+        .iter()
+        .map(|(x, _)| (x - center).abs())
+        .fold(0., f64::max);
+    let mut normal = vec![vec![0.; terms]; terms];
+    let mut rhs = vec![0.; terms];
+    for &(x, y) in points {
+        let t = (x - center) / scale;
+        let powers: Vec<f64> = (0..terms).map(|k| t.powi(k as i32)).collect();
+        for i in 0..terms {
+            rhs[i] += powers[i] * y;
+            for j in 0..terms {
+                normal[i][j] += powers[i] * powers[j];
+            }
+        }
+    }
+    let inverse = invert(normal)?;
+    let coefficients = inverse
+        .iter()
+        .map(|row| row.iter().zip(&rhs).map(|(a, b)| a * b).sum())
+        .collect();
+    let model = Model::Polynomial {
+        center,
+        scale,
+        coefficients,
+        inverse,
+    };
+    Some((model, n - terms as f64))
+}
+
+/// Fit LOESS across `domain`, returning the model and (approximate) residual degrees of freedom.
+///
+/// Each fitted value takes `O(n)` time, so the fit is only evaluated at `FIT_SAMPLES` points,
+/// keeping the whole fit `O(n)`.
+fn fit_loess(points: &[(f64, f64)], span: f64, domain: Interval) -> (Model, f64) {
+    let step = domain.size() / (FIT_SAMPLES - 1) as f64;
+    let samples = (0..FIT_SAMPLES)
+        .map(|idx| {
+            let x = domain.min() + step * idx as f64;
+            let (mut fitted, mut leverage) = (0., 0.);
+            for (idx, weight) in loess_weights(points, span, x) {
+                fitted += weight * points[idx].1;
+                leverage += weight * weight;
+            }
+            LoessSample {
+                x,
+                fitted,
+                leverage,
+            }
+        })
+        .collect();
+
+    // The equivalent number of parameters is the trace of the smoother matrix: the sum of the
+    // weight of each point in its own fitted value. This is estimated from (at most)
+    // `FIT_SAMPLES` of the points.
+    let stride = points.len().div_ceil(FIT_SAMPLES);
+    let (mut total, mut count) = (0., 0.);
+    for (i, &(x, _)) in points.iter().enumerate().step_by(stride) {
+        total += loess_weights(points, span, x)
+            .find(|(idx, _)| *idx == i)
+            .map(|(_, weight)| weight)
+            .unwrap_or(0.);
+        count += 1.;
+    }
+    let parameters = total / count * points.len() as f64;
+    (Model::Loess(samples), points.len() as f64 - parameters)
+}
+
+/// Linearly interpolate `value` of the `samples` (sorted by x) at `x`, clamping to the first and
+/// last samples.
+fn interpolate(samples: &[LoessSample], x: f64, value: impl Fn(&LoessSample) -> f64) -> f64 {
+    if x.is_nan() {
+        return f64::NAN;
+    }
+    let idx = samples.partition_point(|sample| sample.x < x);
+    if idx == 0 {
+        return value(&samples[0]);
+    }
+    if idx == samples.len() {
+        return value(&samples[idx - 1]);
+    }
+    let (before, after) = (&samples[idx - 1], &samples[idx]);
+    let t = (x - before.x) / (after.x - before.x);
+    value(before) + (value(after) - value(before)) * t
+}
+
+/// The weight of each point in the LOESS fitted value at `x`, such that the fitted value is the
+/// weighted sum of the y values.
+///
+/// This is a local linear regression with tricube weights over the nearest `span` fraction of the
+/// points.
+fn loess_weights(
+    points: &[(f64, f64)],
+    span: f64,
+    x: f64,
+) -> impl Iterator<Item = (usize, f64)> + '_ {
+    let n = points.len();
+    let neighbours = ((span * n as f64).ceil() as usize).clamp(2, n);
+    let mut distances: Vec<f64> = points.iter().map(|(px, _)| (px - x).abs()).collect();
+    let (_, max_distance, _) = distances.select_nth_unstable_by(neighbours - 1, f64::total_cmp);
+    let mut max_distance = *max_distance;
+    if span > 1. {
+        max_distance *= span;
+    }
+
+    let tricube: Vec<f64> = points
+        .iter()
+        .map(|(px, _)| {
+            let d = (px - x).abs();
+            if max_distance <= 0. {
+                // All the neighbours are at `x`.
+                if d == 0. {
+                    1.
+                } else {
+                    0.
+                }
+            } else if d < max_distance {
+                (1. - (d / max_distance).powi(3)).powi(3)
+            } else {
+                0.
+            }
+        })
+        .collect();
+    let total: f64 = tricube.iter().sum();
+    let mean_x = tricube
+        .iter()
+        .zip(points)
+        .map(|(w, p)| w * p.0)
+        .sum::<f64>()
+        / total;
+    let sxx: f64 = tricube
+        .iter()
+        .zip(points)
+        .map(|(w, p)| w * (p.0 - mean_x).powi(2))
+        .sum();
+    tricube
+        .into_iter()
+        .enumerate()
+        .filter(|(_, w)| *w > 0.)
+        .map(move |(idx, w)| {
+            let slope_term = if sxx > 0. {
+                (x - mean_x) * (points[idx].0 - mean_x) / sxx
+            } else {
+                0.
+            };
+            (idx, w * (1. / total + slope_term))
+        })
+}
+
+/// Invert a square matrix using Gauss–Jordan elimination with partial pivoting.
+///
+/// Returns `None` if the matrix is singular.
+fn invert(mut matrix: Vec<Vec<f64>>) -> Option<Vec<Vec<f64>>> {
+    let n = matrix.len();
+    let mut inverse: Vec<Vec<f64>> = (0..n)
+        .map(|i| (0..n).map(|j| if i == j { 1. } else { 0. }).collect())
+        .collect();
+    for col in 0..n {
+        let pivot =
+            (col..n).max_by(|&a, &b| matrix[a][col].abs().total_cmp(&matrix[b][col].abs()))?;
+        if matrix[pivot][col].abs() < 1e-12 {
+            return None;
+        }
+        matrix.swap(col, pivot);
+        inverse.swap(col, pivot);
+        let factor = matrix[col][col];
+        for j in 0..n {
+            matrix[col][j] /= factor;
+            inverse[col][j] /= factor;
+        }
+        for row in 0..n {
+            if row != col {
+                let factor = matrix[row][col];
+                for j in 0..n {
+                    matrix[row][j] -= factor * matrix[col][j];
+                    inverse[row][j] -= factor * inverse[col][j];
+                }
+            }
+        }
+    }
+    Some(inverse)
+}
+
+/// Convert the coefficients of a polynomial in `(x - center) / scale` to coefficients of a
+/// polynomial in `x`.
+fn expand_polynomial(coefficients: &[f64], center: f64, scale: f64) -> Vec<f64> {
+    let mut out = vec![0.; coefficients.len()];
+    // The coefficients of `((x - center) / scale)^k`, updated for each k.
+    let mut term = vec![1.];
+    for &c in coefficients {
+        for (o, t) in out.iter_mut().zip(&term) {
+            *o += c * t;
+        }
+        // multiply `term` by `(x - center) / scale`
+        let mut next = vec![0.; term.len() + 1];
+        for (power, t) in term.iter().enumerate() {
+            next[power + 1] += t / scale;
+            next[power] -= t * center / scale;
+        }
+        term = next;
+    }
+    out
+}
+
+/// Format a coefficient to 4 significant figures, without trailing zeros.
+fn format_coefficient(value: f64) -> String {
+    if value == 0. {
+        return "0".into();
+    }
+    let decimals = (3 - value.abs().log10().floor() as i32).max(0) as usize;
+    let text = format!("{:.*}", decimals, value);
+    if text.contains('.') {
+        text.trim_end_matches('0').trim_end_matches('.').to_string()
+    } else {
+        text
+    }
+}
+
+fn x_power(power: usize) -> String {
+    match power {
+        1 => "x".into(),
+        2 => "x²".into(),
+        3 => "x³".into(),
+        _ => format!("x^{}", power),
+    }
+}
+
+/// Draws a fitted curve, with an optional confidence band.
+pub struct FitTrace {
+    /// How to fit the curve when the values change.
+    method: FitMethod,
+    /// The fitted curve, or `None` if there were too few points to fit.
+    ///
+    /// Not public because we have retained state that depends on it.
+    fit: Option<Fit>,
+    /// The range that x values should be shown over
+    x_range: Interval,
+    /// The range that y values should be shown over
+    y_range: Interval,
+    /// The color of the curve and band.
+    pub color: Color,
+    /// The width of the curve.
+    pub stroke_width: f64,
+    /// The confidence level of the band (e.g. 0.95), if one should be drawn.
+    band: Option<f64>,
+
+    // Retained
+    /// The size of the chart area.
+    pub size: Option<Size>,
+    curve: BezPath,
+    band_path: Option<BezPath>,
+}
+
+impl FitTrace {
+    /// A trace drawing `fit` across the x values that were fitted.
+    pub fn new(fit: Fit, x_range: Interval, y_range: Interval) -> Self {
+        Self::from_fit(fit.method(), Some(fit), x_range, y_range)
+    }
+
+    /// A trace drawing a curve fitted to `values` using `method`.
+    ///
+    /// Nothing is drawn if there are too few points to fit.
+    pub fn from_values(
+        values: &[(f64, f64)],
+        method: FitMethod,
+        x_range: Interval,
+        y_range: Interval,
+    ) -> Self {
+        Self::from_fit(method, Fit::new(values, method), x_range, y_range)
+    }
+
+    fn from_fit(method: FitMethod, fit: Option<Fit>, x_range: Interval, y_range: Interval) -> Self {
+        Self {
+            method,
+            fit,
+            x_range,
+            y_range,
+            color: theme::series_color(1),
+            stroke_width: 2.,
+            band: None,
+            size: None,
+            curve: BezPath::new(),
+            band_path: None,
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Draw a confidence band around the curve at confidence `level` (e.g. 0.95).
+    pub fn with_band(mut self, level: f64) -> Self {
+        self.band = Some(level);
+        self
+    }
+
+    /// The fitted curve, or `None` if there were too few points to fit.
+    pub fn fit(&self) -> Option<&Fit> {
+        self.fit.as_ref()
+    }
+
+    /// Refit the curve to `values`, using the same method.
+    pub fn set_values(&mut self, values: &[(f64, f64)]) {
+        self.fit = Fit::new(values, self.method);
+        self.size = None;
+    }
+
+    fn to_point(&self, (x, y): (f64, f64), size: Size) -> Point {
+        Point::new(
+            self.x_range.t(x) * size.width,
+            (1. - self.y_range.t(y)) * size.height,
+        )
+    }
+}
+
+impl Trace for FitTrace {
+    fn layout(&mut self, size: Size, _rc: &mut Piet) -> Result<(), PietError> {
+        if self.size == Some(size) {
+            return Ok(());
+        }
+        self.size = Some(size);
+        self.curve = BezPath::new();
+        self.band_path = None;
+        let fit = match &self.fit {
+            Some(fit) => fit,
+            None => return Ok(()),
+        };
+        let domain = fit.domain();
+        let step = domain.size() / (FIT_SAMPLES - 1) as f64;
+        let xs: Vec<f64> = (0..FIT_SAMPLES)
+            .map(|idx| domain.min() + step * idx as f64)
+            .collect();
+
+        for (idx, &x) in xs.iter().enumerate() {
+            let point = self.to_point((x, fit.predict(x)), size);
+            if idx == 0 {
+                self.curve.move_to(point);
+            } else {
+                self.curve.line_to(point);
+            }
+        }
+
+        self.band_path = self.band.and_then(|level| {
+            let bounds: Vec<(f64, (f64, f64))> = xs
+                .iter()
+                .map(|&x| Some((x, fit.confidence(x, level)?)))
+                .collect::<Option<_>>()?;
+            let mut path = BezPath::new();
+            path.move_to(self.to_point((bounds[0].0, bounds[0].1 .1), size));
+            for &(x, (_, upper)) in &bounds[1..] {
+                path.line_to(self.to_point((x, upper), size));
+            }
+            for &(x, (lower, _)) in bounds.iter().rev() {
+                path.line_to(self.to_point((x, lower), size));
+            }
+            path.close_path();
+            Some(path)
+        });
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        self.size.unwrap()
+    }
+
+    fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
+    fn draw(&self, rc: &mut Piet) {
+        if let Some(band) = &self.band_path {
+            rc.fill(band, &self.color.clone().with_alpha(0.2));
+        }
+        rc.stroke(&self.curve, &self.color, self.stroke_width);
+    }
+
+    fn set_intervals(&mut self, x_range: Interval, y_range: Interval) {
+        self.x_range = x_range;
+        self.y_range = y_range;
+        self.size = None;
+    }
+
+    fn has_intervals(&self) -> bool {
+        true
+    }
+
+    fn is_empty(&self) -> bool {
+        self.fit.is_none()
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[test]
+fn test_fit() {
+    let values: Vec<(f64, f64)> = (0..10).map(|x| (x as f64, 2. * x as f64 + 1.)).collect();
+    let fit = Fit::new(&values, FitMethod::Linear).unwrap();
+    let coefficients = fit.coefficients().unwrap();
+    assert!((coefficients[0] - 1.).abs() < 1e-9 && (coefficients[1] - 2.).abs() < 1e-9);
+    assert_eq!(fit.equation().unwrap(), "y = 2x + 1");
+    assert!((fit.r_squared() - 1.).abs() < 1e-9);
+
+    let values: Vec<(f64, f64)> = (-5..=5).map(|x| (x as f64, (x * x) as f64 - 3.)).collect();
+    let fit = Fit::new(&values, FitMethod::Polynomial(2)).unwrap();
+    assert_eq!(fit.equation().unwrap(), "y = x² - 3");
+    let values: Vec<(f64, f64)> = values.iter().map(|&(x, y)| (x, -y)).collect();
+    let fit = Fit::new(&values, FitMethod::Polynomial(2)).unwrap();
+    assert_eq!(fit.equation().unwrap(), "y = -x² + 3");
+
+    // LOESS reproduces a straight line exactly.
+    let values: Vec<(f64, f64)> = (0..20).map(|x| (x as f64, 0.5 * x as f64)).collect();
+    let fit = Fit::new(&values, FitMethod::Loess { span: 0.5 }).unwrap();
+    assert!((fit.predict(7.5) - 3.75).abs() < 1e-9);
+
+    assert!(Fit::new(&[(1., 1.), (1., 2.)], FitMethod::Linear).is_none());
+}
+
+#[test]
+fn test_fit_confidence_few_points() {
+    // A straight line through 3 points leaves 1 degree of freedom.
+    let fit = Fit::new(&[(0., 0.), (1., 2.), (2., 1.)], FitMethod::Linear).unwrap();
+    let (low, high) = fit.confidence(1., 0.95).unwrap();
+    let margin = (high - low) * 0.5;
+    assert!((margin / fit.std_error(1.) - 12.706).abs() < 0.001);
+    // A straight line through 2 points has no error to estimate.
+    let exact = Fit::new(&[(0., 0.), (1., 2.)], FitMethod::Linear).unwrap();
+    assert_eq!(exact.confidence(0.5, 0.95), None);
+}
+
+#[test]
+fn test_fit_loess_many_points() {
+    let values: Vec<(f64, f64)> = (0..20_000)
+        .map(|x| (x as f64, 0.5 * x as f64 + (x % 2) as f64))
+        .collect();
+    let fit = Fit::new(&values, FitMethod::Loess { span: 0.1 }).unwrap();
+    assert!((fit.predict(10_000.25) - 5_000.625).abs() < 1e-6);
+    assert!(fit.dof > 0. && fit.dof < values.len() as f64);
+    let (low, high) = fit.confidence(5_000., 0.95).unwrap();
+    assert!(low < fit.predict(5_000.) && fit.predict(5_000.) < high);
+}
@@ -1,10 +1,22 @@
-use crate::{prelude::*, Chart, Interval, Trace};
+use crate::{
+    data_label::point_label_candidates,
+    marker::overflow_marker,
+    missing::is_missing,
+    prelude::*,
+    regression::{Fit, FitMethod, FitTrace},
+    Chart, ColorMap, ColorScale, DataLabels, Error, Interval, Marker, Table, Trace,
+};
 use piet_common::{
-    kurbo::{Circle, Size},
-    Color, Error as PietError, Piet, RenderContext,
+    kurbo::{BezPath, Circle, Point, Size},
+    Color, Error as PietError, Piet, RenderContext, TextLayout,
 };
 use std::{any::Any, sync::Arc};
 
+/// Create a scatter plot of the numeric columns `x` and `y` of `table`.
+pub fn scatter_from(table: &Table, x: &str, y: &str) -> Result<Scatter, Error> {
+    Ok(Scatter::new(table.pairs(x, y)?))
+}
+
 pub struct Scatter {
     inner: Chart,
 }
@@ -33,13 +45,34 @@ impl Scatter {
         self.inner.draw(rc)
     }
 
+    /// Replace the points, refitting any fitted curve to them.
     pub fn set_values(&mut self, new_values: impl Into<Arc<[(f64, f64)]>>) {
+        let new_values = new_values.into();
+        for fit in self.inner.traces_mut::<FitTrace>() {
+            fit.set_values(&new_values);
+        }
+        let trace: &mut ScatterTrace = self.inner.traces_mut().next().unwrap();
+        trace.set_values(new_values);
+    }
+
+    /// Draw a curve fitted to the points over them, with a 95% confidence band if `band` is
+    /// true.
+    ///
+    /// Nothing is drawn while there are too few points to fit.
+    pub fn with_fit(mut self, method: FitMethod, band: bool) -> Self {
         let trace: &mut ScatterTrace = self.inner.traces_mut().next().unwrap();
-        trace.set_values(new_values.into());
+        let mut fit = FitTrace::from_values(&trace.values, method, trace.x_range, trace.y_range);
+        if band {
+            fit = fit.with_band(0.95);
+        }
+        self.inner = self.inner.with_trace(fit);
+        self
     }
 }
 
 /// How to draw the bars of the scatter.
+///
+/// Points with a NaN coordinate are missing, and are not drawn.
 pub struct ScatterTrace {
     /// The values of the bars.
     ///
@@ -49,8 +82,20 @@ pub struct ScatterTrace {
     x_range: Interval,
     /// The range that y values should be shown over
     y_range: Interval,
-    /// Point color TODO make this more customizable (e.g. custom renderer)
+    /// Point color, used when there is no color channel.
     point_color: Color,
+    /// How to draw each point.
+    marker: Marker,
+    /// Optional per-point sizes.
+    size_channel: Option<SizeChannel>,
+    /// Optional per-point colors.
+    color_channel: Option<ColorChannel>,
+    /// The indices of points that should be highlighted.
+    highlighted: Vec<usize>,
+    /// Optional labels showing the values of the highlighted points.
+    labels: Option<DataLabels>,
+    /// Whether to mark points outside the intervals with arrows at the edge of the chart area.
+    overflow_markers: bool,
 
     // Retained
     /// The size of the chart area.
@@ -64,6 +109,12 @@ impl ScatterTrace {
             x_range,
             y_range,
             point_color: Color::BLUE.with_alpha(0.4),
+            marker: Marker::default(),
+            size_channel: None,
+            color_channel: None,
+            highlighted: vec![],
+            labels: None,
+            overflow_markers: false,
 
             size: None,
             values: values.into(),
@@ -71,6 +122,106 @@ impl ScatterTrace {
         }
     }
 
+    pub fn with_point_color(mut self, color: Color) -> Self {
+        self.point_color = color;
+        self
+    }
+
+    pub fn set_point_color(&mut self, color: Color) -> &mut Self {
+        self.point_color = color;
+        self.size = None;
+        self
+    }
+
+    /// Draw an arrow at the edge of the chart area for each point outside the x or y interval.
+    pub fn with_overflow_markers(mut self) -> Self {
+        self.overflow_markers = true;
+        self
+    }
+
+    pub fn set_overflow_markers(&mut self, overflow_markers: bool) -> &mut Self {
+        self.overflow_markers = overflow_markers;
+        self.size = None;
+        self
+    }
+
+    /// Set the shape, size and style of the points.
+    pub fn with_marker(mut self, marker: Marker) -> Self {
+        self.marker = marker;
+        self
+    }
+
+    /// Set the shape, size and style of the points.
+    pub fn set_marker(&mut self, marker: Marker) -> &mut Self {
+        self.marker = marker;
+        self.size = None;
+        self
+    }
+
+    /// Size each point by the value at the same index in `sizes` (a bubble chart).
+    ///
+    /// The smallest value gets marker size `radii.0` and the largest `radii.1`, with the marker
+    /// area proportional to the value in between. If all the values are the same, they get the
+    /// size halfway between. Points without a (non-NaN) size use the marker's size.
+    pub fn with_sizes(mut self, sizes: impl Into<Arc<[f64]>>, radii: (f64, f64)) -> Self {
+        self.set_sizes(sizes, radii);
+        self
+    }
+
+    /// Size each point by the value at the same index in `sizes` (a bubble chart).
+    ///
+    /// See `with_sizes` for details.
+    pub fn set_sizes(&mut self, sizes: impl Into<Arc<[f64]>>, radii: (f64, f64)) -> &mut Self {
+        let values = sizes.into();
+        let domain = values.iter().copied().collect::<Interval>().to_nonempty();
+        self.size_channel = Some(SizeChannel {
+            values,
+            domain,
+            radii,
+        });
+        self.size = None;
+        self
+    }
+
+    /// Color each point by mapping the value at the same index in `colors` through `map`.
+    ///
+    /// The color map is applied to the range of `colors` (padded using `Interval::to_nonempty`
+    /// if they are all the same). Points without a (non-NaN) color value use the point color.
+    pub fn with_colors(mut self, colors: impl Into<Arc<[f64]>>, map: ColorMap) -> Self {
+        self.set_colors(colors, map);
+        self
+    }
+
+    /// Color each point by mapping the value at the same index in `colors` through `map`.
+    ///
+    /// See `with_colors` for details.
+    pub fn set_colors(&mut self, colors: impl Into<Arc<[f64]>>, map: ColorMap) -> &mut Self {
+        let values = colors.into();
+        let range = values.iter().copied().collect::<Interval>().to_nonempty();
+        self.color_channel = Some(ColorChannel {
+            values,
+            scale: ColorScale::new(map, range),
+        });
+        self.size = None;
+        self
+    }
+
+    /// The color scale used for per-point colors, if there is one.
+    ///
+    /// This can be used to draw a matching color bar.
+    pub fn color_scale(&self) -> Option<&ColorScale> {
+        self.color_channel.as_ref().map(|channel| &channel.scale)
+    }
+
+    /// A trace drawing a curve fitted to the points using `method`, on the same scale.
+    ///
+    /// Returns `None` if there are too few points to fit. The fitted equation and R² are
+    /// available from `FitTrace::fit`.
+    pub fn fit_trace(&self, method: FitMethod) -> Option<FitTrace> {
+        let fit = Fit::new(&self.values, method)?;
+        Some(FitTrace::new(fit, self.x_range, self.y_range))
+    }
+
     /// Get the numeric values of the bars in this scatter.
     pub fn values(&self) -> &[(f64, f64)] {
         &self.values
@@ -78,42 +229,298 @@ impl ScatterTrace {
 
     pub fn set_x_interval(&mut self, new_range: Interval) {
         self.x_range = new_range;
+        self.size = None;
     }
 
     pub fn set_y_interval(&mut self, new_range: Interval) {
         self.y_range = new_range;
+        self.size = None;
     }
 
     pub fn set_values(&mut self, new_values: Arc<[(f64, f64)]>) {
         self.values = new_values;
+        self.size = None;
+    }
+
+    /// Highlight the points at the given indices.
+    ///
+    /// Highlighted points are drawn with a ring around them, and are labelled if labels are set.
+    pub fn set_highlighted(&mut self, indices: impl Into<Vec<usize>>) {
+        self.highlighted = indices.into();
+        self.size = None;
+    }
+
+    /// Label the highlighted points with their values.
+    pub fn with_labels(mut self, labels: DataLabels) -> Self {
+        self.labels = Some(labels);
+        self
+    }
+
+    /// Label the highlighted points with their values.
+    pub fn set_labels(&mut self, labels: Option<DataLabels>) {
+        self.labels = labels;
+        self.size = None;
+    }
+
+    /// The marker size for the point at `idx`.
+    fn point_size(&self, idx: usize) -> f64 {
+        self.size_channel
+            .as_ref()
+            .and_then(|channel| channel.size(idx))
+            .unwrap_or(self.marker.size)
+    }
+
+    /// The color for the point at `idx`.
+    fn point_color(&self, idx: usize) -> Color {
+        self.color_channel
+            .as_ref()
+            .and_then(|channel| channel.color(idx))
+            .unwrap_or_else(|| self.point_color.clone())
+    }
+
+    /// Convert a value to its position in the chart area.
+    fn to_point(&self, (x, y): (f64, f64), size: Size) -> Point {
+        // The y position is reversed (because we want 0 at the bottom, not the top)
+        Point::new(
+            self.x_range.t(x) * size.width,
+            (1. - self.y_range.t(y)) * size.height,
+        )
+    }
+
+    /// The index and position in the chart area of every point that isn't missing.
+    fn visible_points(&self, size: Size) -> impl Iterator<Item = (usize, Point)> + '_ {
+        self.values
+            .iter()
+            .copied()
+            .enumerate()
+            .filter(|(_, (x, y))| !is_missing(*x) && !is_missing(*y))
+            .map(move |(idx, value)| (idx, self.to_point(value, size)))
+    }
+
+    /// An arrow on the edge of the chart area for each point outside it, if overflow markers are
+    /// on.
+    fn overflow_markers(&self, size: Size) -> Vec<(usize, BezPath)> {
+        if !self.overflow_markers {
+            return vec![];
+        }
+        self.visible_points(size)
+            .filter_map(|(idx, point)| Some((idx, overflow_marker(point, size.to_rect())?)))
+            .collect()
+    }
+
+    /// Build and place the labels for the highlighted points.
+    fn layout_labels(&mut self, rc: &mut Piet) -> Result<(), PietError> {
+        let size = self.size.unwrap();
+        let points: Vec<((f64, f64), Point, f64)> = self
+            .highlighted
+            .iter()
+            .filter_map(|&idx| {
+                let value = *self.values.get(idx)?;
+                if is_missing(value.0) || is_missing(value.1) {
+                    return None;
+                }
+                Some((value, self.to_point(value, size), self.point_size(idx)))
+            })
+            .collect();
+        let labels = match &mut self.labels {
+            Some(labels) => labels,
+            None => return Ok(()),
+        };
+        labels.clear();
+        for ((x, y), center, radius) in points {
+            let text = format!("({}, {})", labels.format(x), labels.format(y));
+            let layout = labels.build_layout(text, rc)?;
+            let candidates =
+                point_label_candidates(labels.placement, layout.size(), center, radius);
+            labels.place(layout, candidates, size.to_rect());
+        }
+        Ok(())
     }
 }
 
-impl Trace for ScatterTrace {
-    fn layout(&mut self, size: Size, _rc: &mut Piet) -> Result<(), PietError> {
-        if self.size == Some(size) {
-            return Ok(());
+/// Maps values to marker sizes.
+struct SizeChannel {
+    values: Arc<[f64]>,
+    /// The range of `values`, padded if they are all the same.
+    domain: Interval,
+    /// The marker sizes for the smallest and largest value.
+    radii: (f64, f64),
+}
+
+impl SizeChannel {
+    fn size(&self, idx: usize) -> Option<f64> {
+        let value = *self.values.get(idx)?;
+        if is_missing(value) {
+            return None;
         }
+        let t = self.domain.t(value);
+        // Interpolate area rather than radius, so the size looks proportional to the value.
+        let (r0, r1) = self.radii;
+        Some((r0 * r0 + (r1 * r1 - r0 * r0) * t).sqrt())
+    }
+}
+
+/// Maps values to marker colors.
+struct ColorChannel {
+    values: Arc<[f64]>,
+    scale: ColorScale,
+}
+
+impl ColorChannel {
+    fn color(&self, idx: usize) -> Option<Color> {
+        let value = *self.values.get(idx)?;
+        if is_missing(value) {
+            return None;
+        }
+        Some(self.scale.color(value))
+    }
+}
+
+impl Trace for ScatterTrace {
+    fn layout(&mut self, size: Size, rc: &mut Piet) -> Result<(), PietError> {
         self.size = Some(size);
-        Ok(())
+        // The labels depend on the values, which can change without the size changing, so we
+        // always rebuild them.
+        self.layout_labels(rc)
     }
 
     fn size(&self) -> Size {
         self.size.unwrap()
     }
 
+    fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
     fn draw(&self, rc: &mut Piet) {
         let size = self.size.unwrap();
-        for (x, y) in self.values.iter().copied() {
-            let pos_x = self.x_range.t(x) * size.width;
-            // The y position is reversed (because we want 0 at the bottom, not the top)
-            let pos_y = (1. - self.y_range.t(y)) * size.height;
-            let dot = Circle::new((pos_x, pos_y), 2.);
-            rc.fill(dot, &self.point_color);
+        for (idx, point) in self.visible_points(size) {
+            self.marker
+                .draw_sized(point, self.point_size(idx), &self.point_color(idx), rc);
+        }
+        for (idx, point) in self.visible_points(size) {
+            if self.highlighted.contains(&idx) {
+                let ring = Circle::new(point, self.point_size(idx) * 2.);
+                rc.stroke(ring, &self.point_color(idx).with_alpha(1.), 1.);
+            }
         }
+        for (idx, arrow) in self.overflow_markers(size) {
+            rc.fill(arrow, &self.point_color(idx).with_alpha(1.));
+        }
+        if let Some(labels) = &self.labels {
+            labels.draw(rc);
+        }
+    }
+
+    fn set_intervals(&mut self, x_range: Interval, y_range: Interval) {
+        self.x_range = x_range;
+        self.y_range = y_range;
+    }
+
+    fn has_intervals(&self) -> bool {
+        true
+    }
+
+    fn is_empty(&self) -> bool {
+        self.values
+            .iter()
+            .all(|&(x, y)| is_missing(x) || is_missing(y))
     }
 
     fn as_any(&mut self) -> &mut dyn Any {
         self
     }
 }
+
+#[test]
+fn test_scatter_skips_missing_points() {
+    let trace = ScatterTrace::new(
+        vec![(0., 0.), (f64::NAN, 1.), (1., f64::NAN), (1., 1.)],
+        Interval::new(0., 1.),
+        Interval::new(0., 1.),
+    );
+    let points: Vec<_> = trace.visible_points(Size::new(10., 10.)).collect();
+    assert_eq!(
+        points,
+        vec![(0, Point::new(0., 10.)), (3, Point::new(10., 0.))]
+    );
+}
+
+#[test]
+fn test_scatter_degenerate_data() {
+    use crate::chart::test_degenerate;
+    test_degenerate(Scatter::new(vec![]).inner, true);
+    for values in [vec![(1., 2.)], vec![(1., 2.), (1., 2.)]] {
+        let (x_range, y_range) = test_degenerate(Scatter::new(values).inner, false);
+        assert!(x_range.unwrap().contains(1.) && y_range.unwrap().contains(2.));
+    }
+}
+
+#[test]
+fn test_scatter_overflow_markers() {
+    use crate::chart::test_render;
+    use piet_common::kurbo::Shape;
+    let (x_range, y_range) = (Interval::new(0., 1.), Interval::new(0., 1.));
+    let trace = ScatterTrace::new(vec![(0.5, 0.5), (2., 0.5), (-1., -1.)], x_range, y_range)
+        .with_overflow_markers();
+    let mut chart = test_render(
+        Chart::new()
+            .with_bottom_axis(x_range.ticker())
+            .with_trace(trace),
+    );
+    let trace = chart.traces_mut::<ScatterTrace>().next().unwrap();
+    let size = trace.size();
+    let markers = trace.overflow_markers(size);
+    assert_eq!(
+        markers.iter().map(|(idx, _)| *idx).collect::<Vec<_>>(),
+        [1, 2]
+    );
+    // The arrows are on the edge of the chart area, pointing out of it.
+    let right = markers[0].1.bounding_box();
+    assert!((right.x1 - size.width).abs() < 1e-9 && right.x0 < size.width);
+    let bottom_left = markers[1].1.bounding_box();
+    assert!(bottom_left.x0.abs() < 1e-9 && (bottom_left.y1 - size.height).abs() < 1e-9);
+
+    trace.set_overflow_markers(false);
+    assert!(trace.overflow_markers(size).is_empty());
+}
+
+#[test]
+fn test_scatter_constant_channels() {
+    let range = Interval::new(0., 1.);
+    let trace = ScatterTrace::new(vec![(0., 0.), (1., 1.)], range, range)
+        .with_colors(vec![5., 5.], ColorMap::viridis())
+        .with_sizes(vec![f64::NAN, f64::NAN], (2., 10.));
+    let scale = trace.color_scale().unwrap();
+    assert_eq!(scale.range, Interval::new(4.5, 5.5));
+    assert_eq!(
+        trace.color_channel.as_ref().unwrap().color(0),
+        Some(scale.map.color(0.5))
+    );
+    let sizes = trace.size_channel.as_ref().unwrap();
+    assert_eq!(sizes.domain, Interval::new(0., 1.));
+    assert_eq!(sizes.size(0), None);
+
+    // A colorbar for a constant channel can be laid out and drawn.
+    let chart = crate::chart::test_render(
+        crate::Chart::new().with_colorbar(crate::Colorbar::new(scale.clone())),
+    );
+    assert!(chart.layout_report().unwrap().fits);
+}
+
+#[test]
+fn test_scatter_refit() {
+    let line =
+        |slope: f64| -> Vec<(f64, f64)> { (0..10).map(|x| (x as f64, slope * x as f64)).collect() };
+    let mut scatter = Scatter::new(vec![(1., 1.)]).with_fit(FitMethod::Linear, true);
+    let fit = |scatter: &mut Scatter| {
+        let trace = scatter.inner.traces_mut::<FitTrace>().next().unwrap();
+        trace.fit().map(|fit| fit.coefficients().unwrap()[1])
+    };
+    assert_eq!(fit(&mut scatter), None);
+    scatter.set_values(line(1.));
+    assert!((fit(&mut scatter).unwrap() - 1.).abs() < 1e-9);
+    scatter.set_values(line(2.));
+    assert!((fit(&mut scatter).unwrap() - 2.).abs() < 1e-9);
+    crate::chart::test_render(scatter.inner);
+}
@@ -1,5 +1,5 @@
 use crate::ticker::{Tick, Ticker};
-use crate::Interval;
+use crate::{Error, Interval};
 use std::{any::Any, fmt, sync::Arc};
 
 // /// Because we layout all labels, we should have some cap for when there are so many it will affect
@@ -37,8 +37,19 @@ impl Numeric {
     ///
     /// Panics if `0 < step < ∞` is not true.
     pub fn from_interval_step(interval: Interval, step: f64) -> Self {
-        assert!(step.is_finite() && step > 0., "0 < {} < ∞", step);
-        Self { interval, step }
+        match Self::try_from_interval_step(interval, step) {
+            Ok(seq) => seq,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// Construct a numeric sequence, or return an error unless `0 < step < ∞`.
+    pub fn try_from_interval_step(interval: Interval, step: f64) -> Result<Self, Error> {
+        if step.is_finite() && step > 0. {
+            Ok(Self { interval, step })
+        } else {
+            Err(Error::InvalidStep(step))
+        }
     }
 
     /// Construct a numeric sequence.
@@ -50,6 +61,12 @@ impl Numeric {
         Self::from_interval_step(Interval::new(min, max), step)
     }
 
+    /// Construct a numeric sequence, or return an error unless `0 < step < ∞` and
+    /// `-∞ < min < max < ∞`.
+    pub fn try_new(min: f64, max: f64, step: f64) -> Result<Self, Error> {
+        Self::try_from_interval_step(Interval::try_new(min, max)?, step)
+    }
+
     /// Get the interval for this sequence.
     pub fn interval(&self) -> Interval {
         self.interval
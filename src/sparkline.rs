@@ -0,0 +1,178 @@
+//! Tiny charts without axes, for showing the shape of a series inline (e.g. in a table).
+use crate::{
+    histogram::HistogramTrace, line::LineTrace, scatter::ScatterTrace, theme, Annotation,
+    Annotations, Chart, Interval, Marker,
+};
+use piet_common::Color;
+use std::sync::Arc;
+
+/// How much of the chart height is left empty above and below the values, so markers and thick
+/// lines aren't cut off.
+const SPARKLINE_PADDING: f64 = 1.2;
+
+/// Create a sparkline: a line chart of `values` (against their index) with no axes or grid.
+///
+/// See `Sparkline` for more options.
+pub fn sparkline(values: impl Into<Arc<[f64]>>) -> Chart {
+    Sparkline::new(values).into_chart()
+}
+
+/// How to draw the values of a sparkline.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SparklineStyle {
+    Line,
+    Bars,
+}
+
+/// Options for a sparkline chart.
+pub struct Sparkline {
+    values: Arc<[f64]>,
+    /// How to draw the values.
+    pub style: SparklineStyle,
+    /// The color of the line or bars.
+    pub color: Color,
+    /// Whether to mark the smallest and largest values.
+    pub min_max_markers: bool,
+    /// Whether to mark the last value.
+    pub last_marker: bool,
+    /// An optional shaded band of y values (e.g. the normal range).
+    pub band: Option<Interval>,
+}
+
+impl Sparkline {
+    pub fn new(values: impl Into<Arc<[f64]>>) -> Self {
+        Self {
+            values: values.into(),
+            style: SparklineStyle::Line,
+            color: theme::series_color(0),
+            min_max_markers: false,
+            last_marker: false,
+            band: None,
+        }
+    }
+
+    pub fn with_style(mut self, style: SparklineStyle) -> Self {
+        self.style = style;
+        self
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Mark the smallest and largest values (with `theme::DOWN_COLOR` and `theme::UP_COLOR`).
+    pub fn with_min_max_markers(mut self) -> Self {
+        self.min_max_markers = true;
+        self
+    }
+
+    /// Mark the last value.
+    pub fn with_last_marker(mut self) -> Self {
+        self.last_marker = true;
+        self
+    }
+
+    /// Shade the band between `band.min()` and `band.max()`.
+    pub fn with_band(mut self, band: Interval) -> Self {
+        self.band = Some(band);
+        self
+    }
+
+    /// Build the chart.
+    pub fn into_chart(self) -> Chart {
+        let mut y_range: Interval = self.values.iter().copied().collect();
+        if let Some(band) = self.band {
+            y_range = y_range.extend_to(band.min()).extend_to(band.max());
+        }
+        if self.style == SparklineStyle::Bars {
+            y_range = y_range.include_zero();
+        }
+        // If all the values are the same, `to_nonempty` puts them in the middle.
+        let y_range = y_range.to_nonempty().scale_center(SPARKLINE_PADDING);
+        // Leave half a step either side, so the line and bars line up.
+        let x_range = Interval::new(-0.5, self.values.len().max(1) as f64 - 0.5);
+
+        let mut chart = Chart::new();
+        if let Some(band) = self.band {
+            chart = chart.with_annotations(
+                Annotations::new(x_range, y_range).with(
+                    Annotation::y_span(band.min(), band.max())
+                        .with_color(theme::GRID_COLOR)
+                        .with_fill_opacity(0.3),
+                ),
+            );
+        }
+        chart = match self.style {
+            SparklineStyle::Line => {
+                let points: Vec<(f64, f64)> = self
+                    .values
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, &value)| (idx as f64, value))
+                    .collect();
+                chart.with_trace(
+                    LineTrace::new(points, x_range, y_range)
+                        .with_color(self.color.clone())
+                        .with_stroke_width(1.5),
+                )
+            }
+            SparklineStyle::Bars => {
+                let mut bars = HistogramTrace::new(self.values.clone()).with_y_range(y_range);
+                bars.bar_color = self.color.clone();
+                chart.with_trace(bars)
+            }
+        };
+
+        let mut markers = vec![];
+        if self.min_max_markers {
+            let present = || {
+                self.values
+                    .iter()
+                    .copied()
+                    .enumerate()
+                    .filter(|(_, v)| v.is_finite())
+            };
+            if let Some(min) = present().min_by(|a, b| a.1.total_cmp(&b.1)) {
+                markers.push((min, theme::DOWN_COLOR));
+            }
+            if let Some(max) = present().max_by(|a, b| a.1.total_cmp(&b.1)) {
+                markers.push((max, theme::UP_COLOR));
+            }
+        }
+        if self.last_marker {
+            if let Some(&last) = self.values.last().filter(|v| v.is_finite()) {
+                markers.push(((self.values.len() - 1, last), theme::AXES_COLOR));
+            }
+        }
+        for ((idx, value), color) in markers {
+            chart = chart.with_trace(
+                ScatterTrace::new(vec![(idx as f64, value)], x_range, y_range)
+                    .with_point_color(color)
+                    .with_marker(Marker::default().with_size(2.5)),
+            );
+        }
+        chart
+    }
+}
+
+#[test]
+fn test_sparkline_degenerate_data() {
+    use crate::chart::test_degenerate;
+    for (values, no_data) in [
+        (vec![], true),
+        (vec![1.], false),
+        (vec![2., 2.], false),
+        (vec![f64::NAN], true),
+    ] {
+        test_degenerate(sparkline(values.clone()), no_data);
+        test_degenerate(
+            Sparkline::new(values)
+                .with_style(SparklineStyle::Bars)
+                .with_min_max_markers()
+                .with_last_marker()
+                .into_chart(),
+            no_data,
+        );
+    }
+}
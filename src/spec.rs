@@ -0,0 +1,652 @@
+//! Charts described as data, so they can be generated from config and versioned alongside code.
+//!
+//! With the `serde` feature the spec types can be (de)serialized with any serde format, e.g.
+//!
+//! ```ignore
+//! let spec: ChartSpec = serde_json::from_str(r#"{
+//!     "traces": [{ "mark": "bar", "encoding": { "x": "edlevel", "y": "systm" }, "aggregate": "mean" }]
+//! }"#)?;
+//! let chart = spec.to_chart(&Table::from_csv_path("monica.csv")?)?;
+//! ```
+//!
+//! or from TOML
+//!
+//! ```toml
+//! [[traces]]
+//! mark = "scatter"
+//! encoding = { x = "age", y = "systm", color = "sex" }
+//!
+//! [y_axis]
+//! include_zero = true
+//! ```
+use crate::{
+    area::{AreaTrace, Baseline},
+    box_plot::BoxPlotTrace,
+    histogram::HistogramTrace,
+    line::LineTrace,
+    prelude::*,
+    scatter::ScatterTrace,
+    theme, Aggregate, Categorical, Chart, ColorMap, Colorbar, Column, Error, GridStyle, GroupBy,
+    Interval, Table, Ticker,
+};
+use piet_common::{kurbo::Insets, Color};
+use std::sync::Arc;
+
+/// A chart: some traces drawn from the columns of a table, with axes.
+///
+/// Traces whose `mark` is `Bar` or `Box` have a categorical x axis, and must be the only trace in
+/// the chart. All other traces share numeric x and y axes.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ChartSpec {
+    /// The traces, drawn in order.
+    pub traces: Vec<TraceSpec>,
+    pub x_axis: AxisSpec,
+    pub y_axis: AxisSpec,
+    pub theme: ThemeSpec,
+    /// Empty space around the chart, on all sides. Must be finite and not negative.
+    pub padding: f64,
+    /// The ratio of the width of the chart area to its height. Must be positive and finite.
+    pub aspect_ratio: Option<f64>,
+}
+
+/// One trace of a chart.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct TraceSpec {
+    pub mark: Mark,
+    /// Which columns of the table are shown how.
+    pub encoding: Encoding,
+    /// How to summarize the `y` values of each category of a `Bar` trace.
+    ///
+    /// Defaults to `Sum`, or to counting the rows of each category if there is no `y`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub aggregate: Option<Aggregate>,
+    /// The color of the trace, as a hex string like `"#0d6d5b"`. Defaults to the next color of
+    /// the theme's palette.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub color: Option<String>,
+    /// The smallest and largest marker size when sizing points by a column. Must be finite and
+    /// not negative.
+    #[cfg_attr(feature = "serde", serde(default = "default_size_range"))]
+    pub size_range: (f64, f64),
+}
+
+/// How a trace draws its values.
+#[derive(Debug, Copy, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Mark {
+    Scatter,
+    Line,
+    Area,
+    /// A bar for each category of `x`.
+    Bar,
+    /// A box plot of the `y` values of each category of `x`.
+    Box,
+}
+
+impl Mark {
+    /// Whether the mark has a categorical x axis.
+    fn is_categorical(self) -> bool {
+        matches!(self, Mark::Bar | Mark::Box)
+    }
+}
+
+/// The columns shown by a trace.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Encoding {
+    pub x: String,
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub y: Option<String>,
+    /// A categorical column splits the trace into a series per category, each with its own
+    /// color. A numeric column colors each point of a `Scatter` trace using the theme's color map.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub color: Option<String>,
+    /// A numeric column sizing each point of a `Scatter` trace.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub size: Option<String>,
+}
+
+/// Options for an axis.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct AxisSpec {
+    /// Whether to draw the axis.
+    pub show: bool,
+    /// Whether to draw grid lines at the ticks of the axis.
+    pub grid: bool,
+    /// The range of values shown. Defaults to the range of the data, rounded outwards.
+    ///
+    /// Ignored for categorical axes.
+    pub range: Option<(f64, f64)>,
+    /// Whether to extend the range of the data to include zero.
+    pub include_zero: bool,
+}
+
+/// Colors used by a chart.
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct ThemeSpec {
+    /// The colors given to traces and series in turn, as hex strings. Defaults to
+    /// `theme::series_color`.
+    pub palette: Vec<String>,
+    /// The color of grid lines, as a hex string.
+    pub grid_color: Option<String>,
+    /// The color map used for numeric color encodings.
+    pub color_map: ColorMapName,
+}
+
+/// One of the built-in color maps.
+#[derive(Debug, Copy, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum ColorMapName {
+    #[default]
+    Viridis,
+    RedBlue,
+    Greys,
+}
+
+impl From<ColorMapName> for ColorMap {
+    fn from(name: ColorMapName) -> Self {
+        match name {
+            ColorMapName::Viridis => ColorMap::viridis(),
+            ColorMapName::RedBlue => ColorMap::red_blue(),
+            ColorMapName::Greys => ColorMap::greys(),
+        }
+    }
+}
+
+impl Default for ChartSpec {
+    fn default() -> Self {
+        Self {
+            traces: vec![],
+            x_axis: AxisSpec::default(),
+            y_axis: AxisSpec::default(),
+            theme: ThemeSpec::default(),
+            padding: 0.,
+            aspect_ratio: None,
+        }
+    }
+}
+
+impl Default for AxisSpec {
+    fn default() -> Self {
+        Self {
+            show: true,
+            grid: true,
+            range: None,
+            include_zero: false,
+        }
+    }
+}
+
+fn default_size_range() -> (f64, f64) {
+    (2., 10.)
+}
+
+impl TraceSpec {
+    pub fn new(mark: Mark, x: impl Into<String>) -> Self {
+        Self {
+            mark,
+            encoding: Encoding {
+                x: x.into(),
+                ..Encoding::default()
+            },
+            aggregate: None,
+            color: None,
+            size_range: default_size_range(),
+        }
+    }
+
+    pub fn with_y(mut self, y: impl Into<String>) -> Self {
+        self.encoding.y = Some(y.into());
+        self
+    }
+
+    pub fn with_color_column(mut self, color: impl Into<String>) -> Self {
+        self.encoding.color = Some(color.into());
+        self
+    }
+
+    pub fn with_size_column(mut self, size: impl Into<String>) -> Self {
+        self.encoding.size = Some(size.into());
+        self
+    }
+
+    pub fn with_aggregate(mut self, aggregate: Aggregate) -> Self {
+        self.aggregate = Some(aggregate);
+        self
+    }
+
+    /// The `y` column, which all but `Bar` traces need.
+    fn y(&self) -> Result<&str, Error> {
+        self.encoding
+            .y
+            .as_deref()
+            .ok_or_else(|| Error::InvalidSpec(format!("a {:?} trace needs a y column", self.mark)))
+    }
+}
+
+/// A series of `(x, y)` values from a trace, with the values of any numeric color and size
+/// columns at the same index.
+struct Series {
+    values: Vec<(f64, f64)>,
+    colors: Option<Vec<f64>>,
+    sizes: Option<Vec<f64>>,
+}
+
+impl ChartSpec {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_trace(mut self, trace: TraceSpec) -> Self {
+        self.traces.push(trace);
+        self
+    }
+
+    /// Build the chart described by this spec from the columns of `table`.
+    pub fn to_chart(&self, table: &Table) -> Result<Chart, Error> {
+        if !(self.padding >= 0. && self.padding.is_finite()) {
+            return Err(Error::InvalidSpec(format!(
+                "padding must be finite and not negative, not {}",
+                self.padding
+            )));
+        }
+        for trace in &self.traces {
+            let (min, max) = trace.size_range;
+            if !(min >= 0. && min.is_finite() && max >= 0. && max.is_finite()) {
+                return Err(Error::InvalidSpec(format!(
+                    "size range must be finite and not negative, not {:?}",
+                    trace.size_range
+                )));
+            }
+        }
+        let mut chart = match self.traces.as_slice() {
+            [] => {
+                return Err(Error::InvalidSpec(
+                    "a chart needs at least one trace".into(),
+                ))
+            }
+            [trace] if trace.mark.is_categorical() => self.categorical_chart(trace, table)?,
+            traces if traces.iter().any(|trace| trace.mark.is_categorical()) => {
+                return Err(Error::InvalidSpec(
+                    "bar and box traces must be the only trace in a chart".into(),
+                ))
+            }
+            _ => self.numeric_chart(table)?,
+        };
+        chart.set_padding(Insets::uniform(self.padding));
+        chart
+            .try_set_aspect_ratio(self.aspect_ratio)
+            .map_err(|e| Error::InvalidSpec(e.to_string()))?;
+        Ok(chart)
+    }
+
+    fn palette_color(&self, idx: usize) -> Result<Color, Error> {
+        match self.theme.palette.len() {
+            0 => Ok(theme::series_color(idx)),
+            len => parse_color(&self.theme.palette[idx % len]),
+        }
+    }
+
+    fn grid_style(&self) -> Result<GridStyle, Error> {
+        let mut style = GridStyle::default();
+        if let Some(color) = &self.theme.grid_color {
+            style.color = parse_color(color)?;
+        }
+        Ok(style)
+    }
+
+    /// Add a left axis for `y_range`, and a bottom axis using `x_ticker`.
+    fn with_axes(
+        &self,
+        mut chart: Chart,
+        x_ticker: impl Ticker + 'static,
+        y_range: Interval,
+    ) -> Result<Chart, Error> {
+        if self.x_axis.show {
+            chart = chart.with_bottom_axis(x_ticker);
+            if self.x_axis.grid {
+                chart = chart.with_bottom_grid(self.grid_style()?);
+            }
+        }
+        if self.y_axis.show {
+            chart = chart.with_left_axis(y_range.ticker().reverse());
+            if self.y_axis.grid {
+                chart = chart.with_left_grid(self.grid_style()?);
+            }
+        }
+        Ok(chart)
+    }
+
+    fn categorical_chart(&self, trace: &TraceSpec, table: &Table) -> Result<Chart, Error> {
+        if trace.encoding.color.is_some() || trace.encoding.size.is_some() {
+            return Err(Error::InvalidSpec(format!(
+                "a {:?} trace can't have color or size columns",
+                trace.mark
+            )));
+        }
+        let color = match &trace.color {
+            Some(color) => parse_color(color)?,
+            None if self.theme.palette.is_empty() => theme::BAR_COLOR,
+            None => self.palette_color(0)?,
+        };
+        let x = &trace.encoding.x;
+        let (labels, y_range, chart) = match (trace.mark, &trace.encoding.y) {
+            (Mark::Bar, None) => {
+                if !matches!(trace.aggregate, None | Some(Aggregate::Count)) {
+                    return Err(Error::InvalidSpec(
+                        "a bar trace without a y column can only count rows".into(),
+                    ));
+                }
+                let (labels, counts) = table.value_counts(x)?;
+                let (y_range, bars) = self.bars(counts.into(), color)?;
+                (Categorical::new(labels), y_range, bars)
+            }
+            (Mark::Bar, Some(y)) => {
+                let groups = GroupBy::from_table(table, x, y)?;
+                let (labels, values) = groups.aggregate(trace.aggregate.unwrap_or(Aggregate::Sum));
+                let (y_range, bars) = self.bars(values, color)?;
+                (labels, y_range, bars)
+            }
+            _ => {
+                let groups = GroupBy::from_table(table, x, trace.y()?)?;
+                let values = groups.groups().iter().flatten().copied();
+                let y_range = self.y_axis.range_of(values, false)?;
+                let boxes = BoxPlotTrace::new(groups.groups(), y_range).with_color(color);
+                (groups.categories(), y_range, Chart::new().with_trace(boxes))
+            }
+        };
+        self.with_axes(chart, labels.space_around_ticker(), y_range)
+    }
+
+    /// A chart with a bar for each value, and the y range of the bars.
+    fn bars(&self, values: Arc<[f64]>, color: Color) -> Result<(Interval, Chart), Error> {
+        let y_range = self.y_axis.range_of(values.iter().copied(), true)?;
+        let mut bars = HistogramTrace::new(values).with_y_range(y_range);
+        bars.bar_color = color;
+        Ok((y_range, Chart::new().with_trace(bars)))
+    }
+
+    fn numeric_chart(&self, table: &Table) -> Result<Chart, Error> {
+        let mut traces = vec![];
+        for trace in &self.traces {
+            traces.push((trace, self.series(trace, table)?));
+        }
+        let points = || {
+            traces
+                .iter()
+                .flat_map(|(_, series)| series.iter())
+                .flat_map(|series| series.values.iter().copied())
+        };
+        let x_range = self.x_axis.range_of(points().map(|(x, _)| x), false)?;
+        let has_area = self.traces.iter().any(|trace| trace.mark == Mark::Area);
+        let y_range = self.y_axis.range_of(points().map(|(_, y)| y), has_area)?;
+
+        let mut chart = Chart::new();
+        let mut color_idx = 0;
+        for (trace, series) in traces {
+            for series in series {
+                let color = match &trace.color {
+                    Some(color) => parse_color(color)?,
+                    None => self.palette_color(color_idx)?,
+                };
+                color_idx += 1;
+                chart = match trace.mark {
+                    Mark::Scatter => {
+                        let mut points = ScatterTrace::new(series.values, x_range, y_range)
+                            .with_point_color(color);
+                        if let Some(sizes) = series.sizes {
+                            points.set_sizes(sizes, trace.size_range);
+                        }
+                        if let Some(colors) = series.colors {
+                            points.set_colors(colors, self.theme.color_map.into());
+                        }
+                        if let Some(scale) = points.color_scale() {
+                            chart = chart.with_colorbar(Colorbar::new(scale.clone()));
+                        }
+                        chart.with_trace(points)
+                    }
+                    Mark::Line => chart.with_trace(
+                        LineTrace::new(series.values, x_range, y_range).with_color(color),
+                    ),
+                    Mark::Area => chart.with_trace(
+                        AreaTrace::new(series.values, Baseline::Value(0.), x_range, y_range)
+                            .with_color(color),
+                    ),
+                    Mark::Bar | Mark::Box => unreachable!(),
+                };
+            }
+        }
+        self.with_axes(chart, x_range.ticker(), y_range)
+    }
+
+    /// The series of a numeric trace: one per category of a categorical color column, otherwise
+    /// just one.
+    fn series(&self, trace: &TraceSpec, table: &Table) -> Result<Vec<Series>, Error> {
+        let values = table.pairs(&trace.encoding.x, trace.y()?)?;
+        let sizes = match &trace.encoding.size {
+            Some(name) if trace.mark == Mark::Scatter => Some(table.numbers(name)?),
+            Some(_) => {
+                return Err(Error::InvalidSpec(
+                    "only scatter traces can have a size column".into(),
+                ))
+            }
+            None => None,
+        };
+        // The indices of the rows in each series.
+        let mut groups: Vec<Vec<usize>> = vec![];
+        let mut colors = None;
+        match trace
+            .encoding
+            .color
+            .as_deref()
+            .map(|name| table.try_column(name))
+            .transpose()?
+        {
+            Some(Column::Category(labels)) => {
+                // Categories in the order they first appear, skipping rows with no category.
+                let mut categories: Vec<&Arc<str>> = vec![];
+                for (idx, label) in labels.iter().enumerate() {
+                    let label = match label {
+                        Some(label) => label,
+                        None => continue,
+                    };
+                    match categories.iter().position(|c| *c == label) {
+                        Some(pos) => groups[pos].push(idx),
+                        None => {
+                            categories.push(label);
+                            groups.push(vec![idx]);
+                        }
+                    }
+                }
+            }
+            Some(Column::Number(values) | Column::Timestamp(values))
+                if trace.mark == Mark::Scatter =>
+            {
+                colors = Some(values.clone());
+                groups.push((0..table.len()).collect());
+            }
+            Some(_) => {
+                return Err(Error::InvalidSpec(
+                    "only scatter traces can be colored by a numeric column".into(),
+                ))
+            }
+            None => groups.push((0..table.len()).collect()),
+        }
+
+        Ok(groups
+            .into_iter()
+            .map(|mut rows| {
+                if trace.mark != Mark::Scatter {
+                    rows.sort_by(|&a, &b| values[a].0.total_cmp(&values[b].0));
+                }
+                Series {
+                    values: rows.iter().map(|&idx| values[idx]).collect(),
+                    colors: colors
+                        .as_ref()
+                        .map(|colors| rows.iter().map(|&idx| colors[idx]).collect()),
+                    sizes: sizes
+                        .as_ref()
+                        .map(|sizes| rows.iter().map(|&idx| sizes[idx]).collect()),
+                }
+            })
+            .collect())
+    }
+}
+
+impl AxisSpec {
+    /// The range shown on this axis for `values`.
+    fn range_of(
+        &self,
+        values: impl Iterator<Item = f64>,
+        include_zero: bool,
+    ) -> Result<Interval, Error> {
+        if let Some((min, max)) = self.range {
+            return Interval::try_new(min, max);
+        }
+        let mut range: Interval = values.collect();
+        if include_zero || self.include_zero {
+            range = range.include_zero();
+        }
+        Ok(range.to_rounded())
+    }
+}
+
+fn parse_color(color: &str) -> Result<Color, Error> {
+    Color::from_hex_str(color)
+        .map_err(|_| Error::InvalidSpec(format!("invalid color \"{}\"", color)))
+}
+
+#[test]
+fn test_spec_to_chart() {
+    use crate::chart::test_render;
+    use piet_common::kurbo::Size;
+    let table = Table::new()
+        .with_column("age", vec![30., 40., 50., 60.])
+        .with_column("systm", vec![120., 130., f64::NAN, 150.])
+        .with_column("sex", vec!["f", "m", "f", "m"])
+        .with_column("edlevel", vec!["a", "b", "a", "a"]);
+
+    let scatter = ChartSpec::new()
+        .with_trace(
+            TraceSpec::new(Mark::Scatter, "age")
+                .with_y("systm")
+                .with_color_column("sex"),
+        )
+        .with_trace(TraceSpec::new(Mark::Line, "age").with_y("systm"));
+    let chart = test_render(scatter.to_chart(&table).unwrap());
+    assert_eq!(chart.size(), Size::new(400., 300.));
+
+    let bubbles = ChartSpec::new().with_trace(
+        TraceSpec::new(Mark::Scatter, "age")
+            .with_y("systm")
+            .with_color_column("age")
+            .with_size_column("age"),
+    );
+    test_render(bubbles.to_chart(&table).unwrap());
+
+    for trace in [
+        TraceSpec::new(Mark::Bar, "edlevel"),
+        TraceSpec::new(Mark::Bar, "edlevel")
+            .with_y("systm")
+            .with_aggregate(Aggregate::Mean),
+        TraceSpec::new(Mark::Box, "edlevel").with_y("systm"),
+    ] {
+        test_render(ChartSpec::new().with_trace(trace).to_chart(&table).unwrap());
+    }
+
+    let mut invalid = vec![
+        ChartSpec::new(),
+        ChartSpec::new().with_trace(TraceSpec::new(Mark::Line, "age")),
+        ChartSpec::new()
+            .with_trace(TraceSpec::new(Mark::Bar, "edlevel"))
+            .with_trace(TraceSpec::new(Mark::Line, "age").with_y("systm")),
+        ChartSpec::new().with_trace(
+            TraceSpec::new(Mark::Line, "age")
+                .with_y("systm")
+                .with_color_column("age"),
+        ),
+    ];
+    let valid = || ChartSpec::new().with_trace(TraceSpec::new(Mark::Line, "age").with_y("systm"));
+    for padding in [-1., f64::NAN, f64::INFINITY] {
+        invalid.push(ChartSpec { padding, ..valid() });
+    }
+    for size_range in [(-5., 10.), (2., f64::NAN), (2., f64::INFINITY)] {
+        let mut spec = valid();
+        spec.traces[0].size_range = size_range;
+        invalid.push(spec);
+    }
+    for ratio in [0., -1., f64::NAN] {
+        invalid.push(ChartSpec {
+            aspect_ratio: Some(ratio),
+            ..valid()
+        });
+    }
+    for spec in invalid {
+        assert!(matches!(spec.to_chart(&table), Err(Error::InvalidSpec(_))));
+    }
+    let constrained = ChartSpec {
+        padding: 10.,
+        aspect_ratio: Some(1.),
+        ..valid()
+    };
+    test_render(constrained.to_chart(&table).unwrap());
+    assert!(matches!(
+        ChartSpec::new()
+            .with_trace(TraceSpec::new(Mark::Scatter, "sex").with_y("systm"))
+            .to_chart(&table),
+        Err(Error::WrongColumnType { .. })
+    ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_spec_round_trip() {
+    let json = r##"{
+        "traces": [
+            { "mark": "bar", "encoding": { "x": "edlevel", "y": "systm" }, "aggregate": "mean" },
+            { "mark": "scatter", "encoding": { "x": "age", "y": "systm" }, "color": "#ff0000" }
+        ],
+        "y_axis": { "include_zero": true },
+        "theme": { "color_map": "red_blue" }
+    }"##;
+    let spec: ChartSpec = serde_json::from_str(json).unwrap();
+    assert_eq!(spec.traces[0].aggregate, Some(Aggregate::Mean));
+    assert_eq!(spec.traces[1].size_range, (2., 10.));
+    assert!(spec.y_axis.include_zero && spec.x_axis.show);
+    assert_eq!(spec.theme.color_map, ColorMapName::RedBlue);
+    let round_trip: ChartSpec =
+        serde_json::from_str(&serde_json::to_string(&spec).unwrap()).unwrap();
+    assert_eq!(round_trip, spec);
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_spec_toml() {
+    // The TOML example from the module docs.
+    let text = r#"
+        [[traces]]
+        mark = "scatter"
+        encoding = { x = "age", y = "systm", color = "sex" }
+
+        [y_axis]
+        include_zero = true
+    "#;
+    let spec: ChartSpec = toml::from_str(text).unwrap();
+    assert_eq!(spec.traces.len(), 1);
+    assert_eq!(spec.traces[0].mark, Mark::Scatter);
+    assert_eq!(spec.traces[0].encoding.color.as_deref(), Some("sex"));
+    assert!(spec.y_axis.include_zero && spec.x_axis.show);
+    // Going through `toml::Value` writes plain values before tables, as TOML needs.
+    let text = toml::to_string(&toml::Value::try_from(&spec).unwrap()).unwrap();
+    let round_trip: ChartSpec = toml::from_str(&text).unwrap();
+    assert_eq!(round_trip, spec);
+}
@@ -0,0 +1,214 @@
+//! Summary statistics used by the distribution traces.
+use crate::missing::is_missing;
+use std::f64::consts::PI;
+
+/// The non-missing values of `values`, sorted in ascending order.
+pub(crate) fn sorted_present(values: &[f64]) -> Vec<f64> {
+    let mut out: Vec<f64> = values.iter().copied().filter(|v| !is_missing(*v)).collect();
+    out.sort_by(f64::total_cmp);
+    out
+}
+
+/// The `p`th quantile of some sorted values, linearly interpolating between values (the same as
+/// numpy's default method).
+///
+/// Returns NaN if `sorted` is empty.
+pub(crate) fn quantile(sorted: &[f64], p: f64) -> f64 {
+    if sorted.is_empty() {
+        return f64::NAN;
+    }
+    let pos = p.clamp(0., 1.) * (sorted.len() - 1) as f64;
+    let (lo, hi) = (pos.floor() as usize, pos.ceil() as usize);
+    sorted[lo] + (sorted[hi] - sorted[lo]) * (pos - lo as f64)
+}
+
+/// The mean and sample standard deviation of some values.
+///
+/// The standard deviation is 0 if there are fewer than 2 values.
+pub(crate) fn mean_sd(values: &[f64]) -> (f64, f64) {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    if values.len() < 2 {
+        return (mean, 0.);
+    }
+    let var = values.iter().map(|v| (v - mean) * (v - mean)).sum::<f64>() / (n - 1.);
+    (mean, var.sqrt())
+}
+
+/// The quantile function (inverse CDF) of the standard normal distribution.
+///
+/// Uses Acklam's rational approximation, which has a relative error below 1.2e-9. Returns ±∞
+/// for `p` of 0 or 1, and NaN outside that range.
+pub(crate) fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969683028665376e1,
+        2.209460984245205e2,
+        -2.759285104469687e2,
+        1.38357751867269e2,
+        -3.066479806614716e1,
+        2.506628277459239,
+    ];
+    const B: [f64; 5] = [
+        -5.447609879822406e1,
+        1.615858368580409e2,
+        -1.556989798598866e2,
+        6.680131188771972e1,
+        -1.328068155288572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784894002430293e-3,
+        -3.223964580411365e-1,
+        -2.400758277161838,
+        -2.549732539343734,
+        4.374664141464968,
+        2.938163982698783,
+    ];
+    const D: [f64; 4] = [
+        7.784695709041462e-3,
+        3.224671290700398e-1,
+        2.445134137142996,
+        3.754408661907416,
+    ];
+    const P_LOW: f64 = 0.02425;
+
+    // The lower tail, also used for the upper tail by symmetry.
+    let tail = |p: f64| {
+        let q = (-2. * p.ln()).sqrt();
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.)
+    };
+    if !(0. ..=1.).contains(&p) {
+        f64::NAN
+    } else if p == 0. {
+        f64::NEG_INFINITY
+    } else if p == 1. {
+        f64::INFINITY
+    } else if p < P_LOW {
+        tail(p)
+    } else if p > 1. - P_LOW {
+        -tail(1. - p)
+    } else {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.)
+    }
+}
+
+/// The quantile function of Student's t distribution with `df` degrees of freedom.
+///
+/// Uses the exact closed forms for 1 and 2 degrees of freedom, interpolating linearly for
+/// fractional `df` below 3. Above that it uses the Cornish–Fisher expansion about the normal
+/// quantile, which is accurate to about 1% between the 2.5% and 97.5% quantiles. Returns NaN if
+/// `df < 1`.
+pub(crate) fn t_quantile(p: f64, df: f64) -> f64 {
+    if df.is_nan() || df < 1. {
+        return f64::NAN;
+    }
+    if df >= 3. {
+        return t_quantile_cornish_fisher(p, df);
+    }
+    let at = |df: f64| match df as usize {
+        1 => (PI * (p - 0.5)).tan(),
+        2 => (2. * p - 1.) / (2. * p * (1. - p)).sqrt(),
+        _ => t_quantile_cornish_fisher(p, df),
+    };
+    let lower = df.floor();
+    let frac = df - lower;
+    if frac == 0. {
+        at(lower)
+    } else {
+        at(lower) + (at(lower + 1.) - at(lower)) * frac
+    }
+}
+
+/// The Cornish–Fisher approximation of the quantile function of Student's t distribution.
+fn t_quantile_cornish_fisher(p: f64, df: f64) -> f64 {
+    let z = normal_quantile(p);
+    let (z3, z5, z7) = (z.powi(3), z.powi(5), z.powi(7));
+    z + (z3 + z) / (4. * df)
+        + (5. * z5 + 16. * z3 + 3. * z) / (96. * df * df)
+        + (3. * z7 + 19. * z5 + 17. * z3 - 15. * z) / (384. * df.powi(3))
+}
+
+/// The quartiles and whisker ends of a box plot.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub(crate) struct BoxStats {
+    pub low: f64,
+    pub q1: f64,
+    pub median: f64,
+    pub q3: f64,
+    pub high: f64,
+}
+
+impl BoxStats {
+    /// Box plot statistics for some sorted values. The whiskers reach the furthest values within
+    /// 1.5 × the interquartile range of the box.
+    ///
+    /// Returns `None` if `sorted` is empty.
+    pub fn new(sorted: &[f64]) -> Option<Self> {
+        let (first, last) = (*sorted.first()?, *sorted.last()?);
+        let q1 = quantile(sorted, 0.25);
+        let q3 = quantile(sorted, 0.75);
+        let reach = (q3 - q1) * 1.5;
+        let low = sorted
+            .iter()
+            .copied()
+            .find(|v| *v >= q1 - reach)
+            .unwrap_or(first);
+        let high = sorted
+            .iter()
+            .rev()
+            .copied()
+            .find(|v| *v <= q3 + reach)
+            .unwrap_or(last);
+        Some(Self {
+            low,
+            q1,
+            median: quantile(sorted, 0.5),
+            q3,
+            high,
+        })
+    }
+}
+
+#[test]
+fn test_quantile() {
+    let sorted = sorted_present(&[4., f64::NAN, 1., 3., 2.]);
+    assert_eq!(sorted, vec![1., 2., 3., 4.]);
+    assert_eq!(quantile(&sorted, 0.), 1.);
+    assert_eq!(quantile(&sorted, 0.5), 2.5);
+    assert_eq!(quantile(&sorted, 1.), 4.);
+    let stats = BoxStats::new(&[1., 2., 3., 4., 100.]).unwrap();
+    assert_eq!((stats.low, stats.median, stats.high), (1., 3., 4.));
+}
+
+#[test]
+fn test_normal_quantile() {
+    assert_eq!(normal_quantile(0.5), 0.);
+    assert!((normal_quantile(0.975) - 1.959964).abs() < 1e-6);
+    assert!((normal_quantile(0.001) + 3.090232).abs() < 1e-6);
+    assert!((t_quantile(0.975, 10.) - 2.228).abs() < 0.005);
+}
+
+#[test]
+fn test_t_quantile_small_df() {
+    // Table values for the 95% and 97.5% quantiles.
+    for (df, t95, t975) in [(1., 6.314, 12.706), (2., 2.920, 4.303), (3., 2.353, 3.182)] {
+        assert!(
+            (t_quantile(0.95, df) - t95).abs() < t95 * 0.01,
+            "df = {}",
+            df
+        );
+        assert!(
+            (t_quantile(0.975, df) - t975).abs() < t975 * 0.01,
+            "df = {}",
+            df
+        );
+    }
+    assert!((t_quantile(0.975, 1.) - 12.706).abs() < 0.001);
+    assert!((t_quantile(0.025, 2.) + 4.303).abs() < 0.001);
+    let between = t_quantile(0.975, 1.5);
+    assert!(between < 12.706 && between > 4.303);
+    assert!(t_quantile(0.975, 0.5).is_nan());
+}
@@ -0,0 +1,467 @@
+//! A simple column-oriented table of data, e.g. loaded from a CSV file.
+use crate::{format_value, missing::is_missing, Error};
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt,
+    sync::Arc,
+};
+
+/// Cell values that are treated as missing when inferring column types.
+const MISSING_VALUES: &[&str] = &["", "na", "n/a", "nan", "null", "none", "-"];
+
+/// The type of the values in a `Column`.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ColumnType {
+    Number,
+    Category,
+    Timestamp,
+}
+
+impl fmt::Display for ColumnType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ColumnType::Number => "number",
+            ColumnType::Category => "category",
+            ColumnType::Timestamp => "timestamp",
+        })
+    }
+}
+
+/// A column of values of a single type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Column {
+    /// Numbers, with NaN for missing values.
+    Number(Arc<[f64]>),
+    /// Labels, with `None` for missing values.
+    Category(Arc<[Option<Arc<str>>]>),
+    /// Seconds since the Unix epoch (in UTC), with NaN for missing values.
+    Timestamp(Arc<[f64]>),
+}
+
+impl Column {
+    /// Infer the type of a column of text cells, and parse them.
+    ///
+    /// Cells like `""`, `"NA"` or `"null"` are missing. If every other cell is a number, the
+    /// column is numeric. Otherwise if every other cell is an ISO 8601 date or date and time
+    /// (e.g. `2021-03-04` or `2021-03-04T05:06:07Z`) the column contains timestamps. Otherwise,
+    /// the column is categorical.
+    pub fn infer<S: AsRef<str>>(cells: &[S]) -> Self {
+        let present = || {
+            cells
+                .iter()
+                .map(|cell| cell.as_ref().trim())
+                .filter(|cell| !is_missing_cell(cell))
+        };
+        let parse_all = |parse: fn(&str) -> Option<f64>| -> Option<Arc<[f64]>> {
+            if !present().all(|cell| parse(cell).is_some()) {
+                return None;
+            }
+            Some(
+                cells
+                    .iter()
+                    .map(|cell| parse(cell.as_ref().trim()).unwrap_or(f64::NAN))
+                    .collect(),
+            )
+        };
+        if let Some(values) = parse_all(parse_number) {
+            Column::Number(values)
+        } else if let Some(values) = parse_all(parse_timestamp) {
+            Column::Timestamp(values)
+        } else {
+            Column::Category(
+                cells
+                    .iter()
+                    .map(|cell| {
+                        let cell = cell.as_ref().trim();
+                        if is_missing_cell(cell) {
+                            None
+                        } else {
+                            Some(Arc::from(cell))
+                        }
+                    })
+                    .collect(),
+            )
+        }
+    }
+
+    pub fn column_type(&self) -> ColumnType {
+        match self {
+            Column::Number(_) => ColumnType::Number,
+            Column::Category(_) => ColumnType::Category,
+            Column::Timestamp(_) => ColumnType::Timestamp,
+        }
+    }
+
+    /// The number of values in the column.
+    pub fn len(&self) -> usize {
+        match self {
+            Column::Number(values) | Column::Timestamp(values) => values.len(),
+            Column::Category(values) => values.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The value at `idx` as a label, or `None` if it is missing.
+    ///
+    /// Numbers and timestamps are formatted like axis labels.
+    pub fn label(&self, idx: usize) -> Option<Arc<str>> {
+        match self {
+            Column::Number(values) | Column::Timestamp(values) => {
+                let value = values[idx];
+                (!is_missing(value)).then(|| format_value(value).into())
+            }
+            Column::Category(values) => values[idx].clone(),
+        }
+    }
+}
+
+impl From<Vec<f64>> for Column {
+    fn from(values: Vec<f64>) -> Self {
+        Column::Number(values.into())
+    }
+}
+
+impl From<Arc<[f64]>> for Column {
+    fn from(values: Arc<[f64]>) -> Self {
+        Column::Number(values)
+    }
+}
+
+impl From<Vec<&str>> for Column {
+    fn from(values: Vec<&str>) -> Self {
+        Column::Category(values.into_iter().map(|v| Some(Arc::from(v))).collect())
+    }
+}
+
+impl From<Vec<String>> for Column {
+    fn from(values: Vec<String>) -> Self {
+        Column::Category(values.into_iter().map(|v| Some(Arc::from(v))).collect())
+    }
+}
+
+/// A table of named columns, all the same length.
+#[derive(Debug, Clone, Default)]
+pub struct Table {
+    names: Vec<String>,
+    columns: Vec<Column>,
+}
+
+impl Table {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a column.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the column is a different length to the columns already in the table.
+    pub fn with_column(mut self, name: impl Into<String>, column: impl Into<Column>) -> Self {
+        if let Err(e) = self.push_column(name, column) {
+            panic!("{}", e);
+        }
+        self
+    }
+
+    /// Add a column, or return an error if it is a different length to the columns already in
+    /// the table.
+    ///
+    /// A column with the same name as an existing column replaces it.
+    pub fn push_column(
+        &mut self,
+        name: impl Into<String>,
+        column: impl Into<Column>,
+    ) -> Result<(), Error> {
+        let (name, column) = (name.into(), column.into());
+        let existing = self.names.iter().position(|n| *n == name);
+        // A replaced column only has to match the other columns.
+        let other = (0..self.columns.len()).find(|idx| Some(*idx) != existing);
+        if let Some(other) = other.map(|idx| &self.columns[idx]) {
+            if other.len() != column.len() {
+                return Err(Error::MismatchedLengths {
+                    expected: other.len(),
+                    found: column.len(),
+                });
+            }
+        }
+        match existing {
+            Some(idx) => self.columns[idx] = column,
+            None => {
+                self.names.push(name);
+                self.columns.push(column);
+            }
+        }
+        Ok(())
+    }
+
+    /// Build a table from columns of text cells, inferring the type of each column (see
+    /// `Column::infer`).
+    ///
+    /// Returns an error if the number of names and columns is different, or the columns are
+    /// different lengths.
+    pub fn from_text_columns<S: AsRef<str>>(
+        names: impl IntoIterator<Item = impl Into<String>>,
+        cells: &[Vec<S>],
+    ) -> Result<Self, Error> {
+        let names: Vec<String> = names.into_iter().map(Into::into).collect();
+        if names.len() != cells.len() {
+            return Err(Error::MismatchedLengths {
+                expected: names.len(),
+                found: cells.len(),
+            });
+        }
+        let mut table = Table::new();
+        for (name, cells) in names.into_iter().zip(cells) {
+            table.push_column(name, Column::infer(cells))?;
+        }
+        Ok(table)
+    }
+
+    /// The number of rows.
+    pub fn len(&self) -> usize {
+        self.columns.first().map(Column::len).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn column_names(&self) -> impl Iterator<Item = &str> {
+        self.names.iter().map(String::as_str)
+    }
+
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        let idx = self.names.iter().position(|n| n == name)?;
+        Some(&self.columns[idx])
+    }
+
+    /// Like `column`, but returns an error if there is no column called `name`.
+    pub fn try_column(&self, name: &str) -> Result<&Column, Error> {
+        self.column(name)
+            .ok_or_else(|| Error::UnknownColumn(name.to_string()))
+    }
+
+    /// The values of a numeric (or timestamp) column.
+    pub fn numbers(&self, name: &str) -> Result<Arc<[f64]>, Error> {
+        match self.try_column(name)? {
+            Column::Number(values) | Column::Timestamp(values) => Ok(values.clone()),
+            column => Err(Error::WrongColumnType {
+                column: name.to_string(),
+                expected: ColumnType::Number,
+                found: column.column_type(),
+            }),
+        }
+    }
+
+    /// `(x, y)` pairs from two numeric (or timestamp) columns.
+    pub fn pairs(&self, x: &str, y: &str) -> Result<Arc<[(f64, f64)]>, Error> {
+        let (xs, ys) = (self.numbers(x)?, self.numbers(y)?);
+        Ok(xs.iter().copied().zip(ys.iter().copied()).collect())
+    }
+
+    /// Each row's value from column `name` as a label (see `Column::label`).
+    pub fn labels(&self, name: &str) -> Result<Vec<Option<Arc<str>>>, Error> {
+        let column = self.try_column(name)?;
+        Ok((0..column.len()).map(|idx| column.label(idx)).collect())
+    }
+
+    /// The number of times each value appears in column `name`, skipping missing values.
+    ///
+    /// Numeric columns are in ascending order, and categorical columns are in the order each
+    /// value first appears.
+    pub fn value_counts(&self, name: &str) -> Result<(Vec<Arc<str>>, Vec<f64>), Error> {
+        let column = self.try_column(name)?;
+        let mut order: Vec<usize> = (0..column.len()).collect();
+        if let Column::Number(values) | Column::Timestamp(values) = column {
+            order.sort_by(|&a, &b| values[a].total_cmp(&values[b]));
+        }
+        let mut labels: Vec<Arc<str>> = vec![];
+        let mut counts: Vec<f64> = vec![];
+        let mut index: HashMap<Arc<str>, usize> = HashMap::new();
+        for label in order.into_iter().filter_map(|idx| column.label(idx)) {
+            match index.entry(label) {
+                Entry::Occupied(pos) => counts[*pos.get()] += 1.,
+                Entry::Vacant(entry) => {
+                    labels.push(entry.key().clone());
+                    counts.push(1.);
+                    entry.insert(labels.len() - 1);
+                }
+            }
+        }
+        Ok((labels, counts))
+    }
+}
+
+#[cfg(feature = "csv")]
+impl Table {
+    /// Read a table from CSV data with a header row, inferring the type of each column (see
+    /// `Column::infer`).
+    pub fn from_csv(reader: impl std::io::Read) -> Result<Self, Error> {
+        let mut reader = csv::ReaderBuilder::new()
+            .trim(csv::Trim::All)
+            .from_reader(reader);
+        let names: Vec<String> = reader.headers()?.iter().map(String::from).collect();
+        let mut cells: Vec<Vec<String>> = vec![vec![]; names.len()];
+        for record in reader.records() {
+            let record = record?;
+            for (idx, column) in cells.iter_mut().enumerate() {
+                column.push(record.get(idx).unwrap_or("").to_string());
+            }
+        }
+        Self::from_text_columns(names, &cells)
+    }
+
+    /// Read a table from a CSV file (see `from_csv`).
+    pub fn from_csv_path(path: impl AsRef<std::path::Path>) -> Result<Self, Error> {
+        let file = std::fs::File::open(path).map_err(csv::Error::from)?;
+        Self::from_csv(std::io::BufReader::new(file))
+    }
+}
+
+fn is_missing_cell(cell: &str) -> bool {
+    MISSING_VALUES
+        .iter()
+        .any(|missing| cell.eq_ignore_ascii_case(missing))
+}
+
+fn parse_number(cell: &str) -> Option<f64> {
+    cell.parse().ok().filter(|v: &f64| v.is_finite())
+}
+
+/// Parse an ISO 8601 date (`2021-03-04`) or UTC date and time (`2021-03-04T05:06:07.5Z`, with
+/// optional seconds, fraction and `Z`, and `T` or a space as the separator) into seconds since
+/// the Unix epoch.
+fn parse_timestamp(cell: &str) -> Option<f64> {
+    let (date, time) = match cell.find(['T', ' ']) {
+        Some(idx) => (&cell[..idx], Some(&cell[idx + 1..])),
+        None => (cell, None),
+    };
+    let mut parts = date.split('-');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+    let (year, month, day): (i64, u32, u32) =
+        (year.parse().ok()?, month.parse().ok()?, day.parse().ok()?);
+    if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+        return None;
+    }
+    let mut seconds = days_from_civil(year, month, day) as f64 * 86400.;
+    if let Some(time) = time {
+        let time = time.strip_suffix('Z').unwrap_or(time);
+        let mut parts = time.split(':');
+        let hours: u32 = parts.next()?.parse().ok()?;
+        let minutes: u32 = parts.next()?.parse().ok()?;
+        let secs: f64 = match parts.next() {
+            Some(secs) => secs.parse().ok()?,
+            None => 0.,
+        };
+        if parts.next().is_some() || hours > 23 || minutes > 59 || !(0. ..61.).contains(&secs) {
+            return None;
+        }
+        seconds += f64::from(hours * 3600 + minutes * 60) + secs;
+    }
+    Some(seconds)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// The number of days from 1970-01-01 to the given date in the proleptic Gregorian calendar.
+///
+/// From Howard Hinnant's [date algorithms](http://howardhinnant.github.io/date_algorithms.html).
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = i64::from(month);
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(day) - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+#[test]
+fn test_infer_columns() {
+    let table = Table::from_text_columns(
+        ["n", "c", "t"],
+        &[
+            vec!["1", "NA", "2.5"],
+            vec!["a", "", "b"],
+            vec!["1970-01-02", "2000-03-01T12:00:00Z", "null"],
+        ],
+    )
+    .unwrap();
+    assert_eq!(table.len(), 3);
+    let n = table.numbers("n").unwrap();
+    assert_eq!((n[0], n[2]), (1., 2.5));
+    assert!(n[1].is_nan());
+    assert_eq!(
+        table.column("c"),
+        Some(&Column::Category(
+            vec![Some("a".into()), None, Some("b".into())].into()
+        ))
+    );
+    let t = table.numbers("t").unwrap();
+    assert_eq!(
+        table.column("t").unwrap().column_type(),
+        ColumnType::Timestamp
+    );
+    assert_eq!(t[0], 86400.);
+    assert_eq!(t[1], 951_912_000.);
+    assert!(t[2].is_nan());
+    assert!(matches!(
+        table.numbers("c"),
+        Err(Error::WrongColumnType { .. })
+    ));
+    assert!(matches!(table.numbers("x"), Err(Error::UnknownColumn(_))));
+    assert!(matches!(
+        Table::from_text_columns(["a", "b"], &[vec!["1"]]),
+        Err(Error::MismatchedLengths {
+            expected: 2,
+            found: 1
+        })
+    ));
+}
+
+#[test]
+fn test_push_column() {
+    let mut table = Table::new().with_column("a", vec![1., 2.]);
+    // The only column can be replaced with one of a different length.
+    table.push_column("a", vec![1., 2., 3.]).unwrap();
+    assert_eq!(table.len(), 3);
+    table.push_column("b", vec!["x", "y", "z"]).unwrap();
+    assert!(matches!(
+        table.push_column("b", vec![1.]),
+        Err(Error::MismatchedLengths {
+            expected: 3,
+            found: 1
+        })
+    ));
+    assert!(matches!(
+        table.push_column("c", vec![1.]),
+        Err(Error::MismatchedLengths { .. })
+    ));
+    assert_eq!(table.column_names().collect::<Vec<_>>(), ["a", "b"]);
+}
+
+#[test]
+fn test_value_counts() {
+    let table = Table::new()
+        .with_column("n", vec![3., 1., f64::NAN, 3.])
+        .with_column("c", vec!["b", "a", "b", "b"]);
+    let (labels, counts) = table.value_counts("n").unwrap();
+    assert_eq!(labels, vec![Arc::from("1"), Arc::from("3")]);
+    assert_eq!(counts, vec![1., 2.]);
+    let (labels, counts) = table.value_counts("c").unwrap();
+    assert_eq!(labels, vec![Arc::from("b"), Arc::from("a")]);
+    assert_eq!(counts, vec![3., 1.]);
+}
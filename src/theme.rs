@@ -7,3 +7,20 @@ pub const BAR_SPACING: f64 = 10.;
 pub const AXES_COLOR: Color = Color::grey8(100);
 pub const GRID_COLOR: Color = Color::rgba8(127, 127, 127, 127);
 pub const BAR_COLOR: Color = Color::rgb8(13, 109, 91);
+pub const DATA_LABEL_FONT_SIZE: f64 = 12.;
+pub const DATA_LABEL_COLOR: Color = Color::grey8(40);
+pub const COLORBAR_THICKNESS: f64 = 16.;
+
+/// The color to use for the `idx`th series in a chart with several series.
+pub fn series_color(idx: usize) -> Color {
+    // use a number that is fairly coprime with 360.
+    Color::hlc(idx as f64 * 140. + 200., 50., 50.)
+}
+pub const NO_DATA_COLOR: Color = Color::rgba8(127, 127, 127, 160);
+pub const AREA_FILL_OPACITY: f64 = 0.4;
+pub const UP_COLOR: Color = Color::rgb8(38, 166, 91);
+pub const DOWN_COLOR: Color = Color::rgb8(214, 69, 65);
+pub const ANNOTATION_COLOR: Color = Color::rgb8(200, 60, 40);
+/// The message shown in the chart area when there is no data to draw.
+pub const NO_DATA_MESSAGE: &str = "No data";
+pub const NO_DATA_FONT_SIZE: f64 = 14.;
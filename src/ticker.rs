@@ -1,3 +1,4 @@
+use crate::Interval;
 use std::{any::Any, fmt, sync::Arc};
 
 /// A position on an axis that we can mark and label.
@@ -34,6 +35,16 @@ pub trait Ticker: fmt::Debug {
     /// This should return `Some` if `idx < Ticker::len(self)`, `None` otherwise.
     fn get(&self, idx: usize) -> Option<Tick>;
 
+    /// The interval of values shown on the axis, if this ticker is for a continuous interval.
+    fn interval(&self) -> Option<Interval> {
+        None
+    }
+
+    /// Change the interval of values shown on the axis. Call `layout` afterwards.
+    ///
+    /// Tickers that don't show a continuous interval ignore this.
+    fn set_interval(&mut self, _interval: Interval) {}
+
     fn as_any(&self) -> &dyn Any
     where
         Self: 'static;
@@ -70,6 +81,14 @@ impl Ticker for Box<dyn Ticker> {
         (**self).get(idx)
     }
 
+    fn interval(&self) -> Option<Interval> {
+        (**self).interval()
+    }
+
+    fn set_interval(&mut self, interval: Interval) {
+        (**self).set_interval(interval)
+    }
+
     fn as_any(&self) -> &dyn Any {
         (**self).as_any()
     }
@@ -103,6 +122,14 @@ impl<T: Ticker> Ticker for ReverseTicker<T> {
         })
     }
 
+    fn interval(&self) -> Option<Interval> {
+        self.ticker.interval()
+    }
+
+    fn set_interval(&mut self, interval: Interval) {
+        self.ticker.set_interval(interval)
+    }
+
     fn as_any(&self) -> &dyn Any
     where
         T: 'static,
@@ -1,14 +1,11 @@
+use crate::{Error, Interval};
 use piet_common::{kurbo::Size, Error as PietError, Piet};
 use std::any::Any;
 
 /// A drawing that represents some data. Used inside the chart.
 pub trait Trace: 'static {
     /// This function can be used to calculate things that depend on the size of the trace.
-    fn layout(
-        &mut self,
-        #[allow(unused)] size: Size,
-        #[allow(unused)] rc: &mut Piet,
-    ) -> Result<(), PietError> {
+    fn layout(&mut self, _size: Size, _rc: &mut Piet) -> Result<(), PietError> {
         Ok(())
     }
 
@@ -17,11 +14,40 @@ pub trait Trace: 'static {
     /// This function is allowed to panic if `layout` hasn't been called.
     fn size(&self) -> Size;
 
-    /// Draw the trace into the chart.
+    /// Like `size`, but returns `Error::LayoutNotPerformed` rather than panicking if `layout`
+    /// hasn't been called since the trace last changed.
+    ///
+    /// The default calls `size`. Override it so that `Chart::try_draw` can return an error
+    /// instead of panicking.
+    fn try_size(&self) -> Result<Size, Error> {
+        Ok(self.size())
+    }
+
+    /// Draw the trace into the chart. `Chart::try_draw` checks `try_size` first, so this may
+    /// panic if `layout` hasn't been called.
     ///
     /// The chart area will start at `(0, 0)` and finish at
     /// `(self.size().width, self.size().height)`.
     fn draw(&self, rc: &mut Piet);
 
+    /// Change the x and y intervals the trace is drawn over, e.g. so that the chart can make the
+    /// scales equal. `layout` will be called afterwards.
+    ///
+    /// Traces that don't draw over x and y intervals ignore this.
+    fn set_intervals(&mut self, _x_range: Interval, _y_range: Interval) {}
+
+    /// Whether `set_intervals` changes where the trace draws. If any trace in a chart returns
+    /// `false`, `EqualScale::AdjustIntervals` falls back to `EqualScale::AdjustArea`.
+    fn has_intervals(&self) -> bool {
+        false
+    }
+
+    /// Whether the trace has nothing to draw (e.g. all its values are missing).
+    ///
+    /// If every trace in a chart is empty, the chart shows a "no data" message.
+    fn is_empty(&self) -> bool {
+        false
+    }
+
     fn as_any(&mut self) -> &mut dyn Any;
 }
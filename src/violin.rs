@@ -0,0 +1,277 @@
+use crate::{
+    histogram::auto_bar_width, kde::Kde, prelude::*, stats::BoxStats, theme, Categorical, Chart,
+    Error, GridStyle, Interval, Trace,
+};
+use piet_common::{
+    kurbo::{BezPath, Circle, Line, Point, Rect, Size},
+    Color, Error as PietError, Piet, RenderContext,
+};
+use std::{any::Any, fmt};
+
+/// The number of points each side of a violin is evaluated at.
+const VIOLIN_SAMPLES: usize = 100;
+
+/// Create a violin plot, with a violin showing the distribution of each group of values.
+///
+/// Groups are paired with `labels` by index. NaN values are skipped.
+///
+/// # Panics
+///
+/// Panics if the number of groups and labels is different.
+pub fn violin_plot<L, S>(
+    labels: impl Into<Categorical<L>>,
+    groups: impl IntoIterator<Item = S>,
+) -> Chart
+where
+    L: Clone + fmt::Debug + fmt::Display + 'static,
+    S: AsRef<[f64]>,
+{
+    match try_violin_plot(labels, groups) {
+        Ok(chart) => chart,
+        Err(e) => panic!("{}", e),
+    }
+}
+
+/// Like `violin_plot`, but returns an error if the number of groups and labels is different.
+pub fn try_violin_plot<L, S>(
+    labels: impl Into<Categorical<L>>,
+    groups: impl IntoIterator<Item = S>,
+) -> Result<Chart, Error>
+where
+    L: Clone + fmt::Debug + fmt::Display + 'static,
+    S: AsRef<[f64]>,
+{
+    let labels = labels.into();
+    let kdes: Vec<Kde> = groups
+        .into_iter()
+        .map(|values| Kde::new(values.as_ref()))
+        .collect();
+    if kdes.len() != labels.categories().len() {
+        return Err(Error::MismatchedLengths {
+            expected: labels.categories().len(),
+            found: kdes.len(),
+        });
+    }
+    let y_range = kdes
+        .iter()
+        .filter_map(|kde| kde.support())
+        .reduce(|a, b| a.extend_to(b.min()).extend_to(b.max()))
+        .unwrap_or_default()
+        .to_rounded();
+    Ok(Chart::new()
+        .with_left_axis(y_range.ticker().reverse())
+        .with_left_grid(GridStyle::default())
+        .with_bottom_axis(labels.space_around_ticker())
+        .with_trace(ViolinTrace::new(kdes, y_range).with_inner_box(true)))
+}
+
+/// The shape and box of a violin, in chart area coordinates.
+struct Violin {
+    outline: BezPath,
+    center: f64,
+    stats: Option<BoxStats>,
+}
+
+/// Draws the density of each of several groups of values as a violin (a density curve mirrored
+/// about the center of the group's category).
+///
+/// Groups are evenly spaced across the chart, to match a `space_around_ticker` on a `Categorical`
+/// axis. Each violin is scaled to the same maximum width.
+pub struct ViolinTrace {
+    /// The density estimate for each group.
+    ///
+    /// Not public because we have retained state that depends on them.
+    kdes: Vec<Kde>,
+    /// The range that values should be shown over
+    y_range: Interval,
+    /// The color of the violins.
+    pub color: Color,
+    /// The opacity of the inside of the violins.
+    pub fill_opacity: f64,
+    /// The width of the violin outlines.
+    pub stroke_width: f64,
+    /// The maximum width of each violin. Chosen from the spacing of the groups if `None`.
+    pub width: Option<f64>,
+    /// Whether to draw a small box plot inside each violin.
+    pub inner_box: bool,
+
+    // Retained
+    /// The size of the chart area.
+    pub size: Option<Size>,
+    violins: Vec<Violin>,
+}
+
+impl ViolinTrace {
+    pub fn new(kdes: impl Into<Vec<Kde>>, y_range: Interval) -> Self {
+        Self {
+            kdes: kdes.into(),
+            y_range,
+            color: theme::BAR_COLOR,
+            fill_opacity: theme::AREA_FILL_OPACITY,
+            stroke_width: 1.5,
+            width: None,
+            inner_box: false,
+            size: None,
+            violins: vec![],
+        }
+    }
+
+    pub fn with_color(mut self, color: Color) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_width(mut self, width: f64) -> Self {
+        self.width = Some(width);
+        self
+    }
+
+    /// Draw a small box plot (quartiles, median and whiskers) inside each violin.
+    pub fn with_inner_box(mut self, inner_box: bool) -> Self {
+        self.inner_box = inner_box;
+        self
+    }
+
+    pub fn kdes(&self) -> &[Kde] {
+        &self.kdes
+    }
+
+    pub fn set_kdes(&mut self, kdes: impl Into<Vec<Kde>>) {
+        self.kdes = kdes.into();
+        self.size = None;
+    }
+
+    fn y_pos(&self, y: f64, size: Size) -> f64 {
+        (1. - self.y_range.t(y)) * size.height
+    }
+
+    fn layout_violin(&self, kde: &Kde, center: f64, half_width: f64, size: Size) -> Violin {
+        let samples = match kde.support() {
+            Some(support) => kde.sample(support, VIOLIN_SAMPLES),
+            None => vec![],
+        };
+        let max = samples.iter().map(|(_, d)| *d).fold(0., f64::max);
+        let scale = if max > 0. { half_width / max } else { 0. };
+        let mut outline = BezPath::new();
+        for (idx, &(y, density)) in samples.iter().enumerate() {
+            let point = Point::new(center - density * scale, self.y_pos(y, size));
+            if idx == 0 {
+                outline.move_to(point);
+            } else {
+                outline.line_to(point);
+            }
+        }
+        for &(y, density) in samples.iter().rev() {
+            outline.line_to((center + density * scale, self.y_pos(y, size)));
+        }
+        if !samples.is_empty() {
+            outline.close_path();
+        }
+        Violin {
+            outline,
+            center,
+            stats: BoxStats::new(kde.values()),
+        }
+    }
+
+    fn draw_box(&self, violin: &Violin, box_width: f64, rc: &mut Piet) {
+        let (size, stats) = match (self.size, violin.stats) {
+            (Some(size), Some(stats)) => (size, stats),
+            _ => return,
+        };
+        let x = violin.center;
+        let whisker = Line::new(
+            (x, self.y_pos(stats.low, size)),
+            (x, self.y_pos(stats.high, size)),
+        );
+        rc.stroke(whisker, &theme::AXES_COLOR, 1.);
+        let quartiles = Rect::new(
+            x - box_width * 0.5,
+            self.y_pos(stats.q3, size),
+            x + box_width * 0.5,
+            self.y_pos(stats.q1, size),
+        );
+        rc.fill(quartiles, &theme::AXES_COLOR);
+        let median = Circle::new((x, self.y_pos(stats.median, size)), box_width * 0.4);
+        rc.fill(median, &Color::WHITE);
+    }
+}
+
+impl Trace for ViolinTrace {
+    fn layout(&mut self, size: Size, _rc: &mut Piet) -> Result<(), PietError> {
+        if self.size == Some(size) {
+            return Ok(());
+        }
+        self.size = Some(size);
+        let gap = size.width / self.kdes.len() as f64;
+        let half_width = self.width.unwrap_or_else(|| auto_bar_width(gap)) * 0.5;
+        self.violins = self
+            .kdes
+            .iter()
+            .enumerate()
+            .map(|(idx, kde)| self.layout_violin(kde, gap * (0.5 + idx as f64), half_width, size))
+            .collect();
+        Ok(())
+    }
+
+    fn size(&self) -> Size {
+        self.size.unwrap()
+    }
+
+    fn try_size(&self) -> Result<Size, Error> {
+        self.size.ok_or(Error::LayoutNotPerformed)
+    }
+
+    fn draw(&self, rc: &mut Piet) {
+        let fill = self.color.clone().with_alpha(self.fill_opacity);
+        let gap = self.size.unwrap().width / self.kdes.len() as f64;
+        let box_width = (self.width.unwrap_or_else(|| auto_bar_width(gap)) * 0.1).max(3.);
+        for violin in &self.violins {
+            rc.fill(&violin.outline, &fill);
+            rc.stroke(&violin.outline, &self.color, self.stroke_width);
+            if self.inner_box {
+                self.draw_box(violin, box_width, rc);
+            }
+        }
+    }
+
+    /// The x positions come from the order of the groups, so only the y interval changes.
+    fn set_intervals(&mut self, _x_range: Interval, y_range: Interval) {
+        self.y_range = y_range;
+        self.size = None;
+    }
+
+    fn has_intervals(&self) -> bool {
+        true
+    }
+
+    fn is_empty(&self) -> bool {
+        self.kdes.iter().all(|kde| kde.values().is_empty())
+    }
+
+    fn as_any(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[test]
+fn test_violin_plot_degenerate_data() {
+    use crate::chart::test_degenerate;
+    test_degenerate(
+        violin_plot(Vec::<&str>::new(), Vec::<Vec<f64>>::new()),
+        true,
+    );
+    let (_, y_range) = test_degenerate(violin_plot(vec!["a", "b"], [vec![], vec![1.]]), false);
+    assert!(y_range.unwrap().contains(1.));
+    let (_, y_range) = test_degenerate(violin_plot(vec!["a"], [vec![2., 2.]]), false);
+    assert!(y_range.unwrap().contains(2.));
+    let (_, y_range) = test_degenerate(violin_plot(vec!["a"], [vec![1e17, 1e17]]), false);
+    assert!(y_range.unwrap().contains(1e17));
+    assert!(matches!(
+        try_violin_plot(vec!["a", "b"], [vec![1.]]),
+        Err(Error::MismatchedLengths {
+            expected: 2,
+            found: 1
+        })
+    ));
+}